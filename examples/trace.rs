@@ -1,108 +1,15 @@
-use reqwest::Client;
-use revm::context::BlockEnv;
-use revm::primitives::U256;
-use serde_json::json;
-use trace_prestate::{block::{create_block_env_from_block_details, GetBlockByNumberResponse}, database::PrestateTracerResponse, json_rpc::JsonRpcResponse, trace::{op_trace_transaction, trace_transaction}};
+use revm::primitives::{Bytes, U256};
 use std::env;
-
 use std::str::FromStr;
-
-
-use revm::primitives::Bytes;
-
-async fn get_block_by_number(
-    rpc_url: &str
-) -> Result<GetBlockByNumberResponse, Box<dyn std::error::Error>> {
-    // JSON-RPC request payload
-    let request_body = json!({
-        "jsonrpc":"2.0",
-        "method":"eth_getBlockByNumber",
-        "params":["latest", false],
-        "id":1
-    });
-    // Create HTTP client
-    let client = Client::new();
-
-    // Send request
-    let res = client
-        .post(rpc_url) // replace with your RPC endpoint
-        .json(&request_body)
-        .send()
-        .await?;
-
-    // Parse response
-    let response: GetBlockByNumberResponse = res.json().await?;
-    Ok(response)
-}
-
-async fn get_prestate_trace(
-    rpc_url: &str, from: &str, to: &str, data: &str, value: u128, block_number_hex: String
-) -> Result<PrestateTracerResponse, Box<dyn std::error::Error>> {
-    // JSON-RPC request payload
-    let request_body = json!({
-        "jsonrpc":"2.0",
-        "method":"debug_traceCall",
-        "params":[
-            {
-                "from": from,
-                "to": to,
-                "data": data,
-                "value": value
-            },
-            block_number_hex,
-            {
-              "tracer": "prestateTracer"
-            }
-        ],
-        "id":1
-    });
-    // Create HTTP client
-    let client = Client::new();
-
-    // Send request
-    let res = client
-        .post(rpc_url) // replace with your RPC endpoint
-        .json(&request_body)
-        .send()
-        .await?;
-
-    // Parse response
-    let response: PrestateTracerResponse = res.json().await?;
-    Ok(response)
-}
-
-async fn get_nonce(
-    rpc_url: &str, address: &str
-) -> Result<JsonRpcResponse<U256>, Box<dyn std::error::Error>> {
-    // JSON-RPC request payload
-    let request_body = json!({
-        "jsonrpc":"2.0",
-        "method":"eth_getTransactionCount",
-        "params":[address, "latest"],
-        "id":1
-    });
-    // Create HTTP client
-    let client = Client::new();
-
-    // Send request
-    let res = client
-        .post(rpc_url) // replace with your RPC endpoint
-        .json(&request_body)
-        .send()
-        .await?;
-
-    // Parse response
-    let response: JsonRpcResponse<U256> = res.json().await?;
-    Ok(response)
-}
+use trace_prestate::rpc::{fetch_and_trace_transaction, op_fetch_and_trace_transaction, RpcClient};
 
 async fn trace_sepolia() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
     // RPC endpoint
-    let rpc_url_string = env::var("SEPOLIA_RPC_URL")
+    let rpc_url = env::var("SEPOLIA_RPC_URL")
             .expect("SEPOLIA_RPC_URL must be set.");
-    let rpc_url = rpc_url_string.as_str();
+    let client = RpcClient::new(&rpc_url)?;
 
     // Transaction details
     let chain_id = 11155111;
@@ -113,51 +20,18 @@ async fn trace_sepolia() -> Result<(), Box<dyn std::error::Error>> {
     let gas_price = 0x10c8ea;
     let gas_priority_fee = 0x10c8e0;
 
-    // Get current nonce from the network
-    let from_nonce:u64 = match get_nonce(rpc_url, from).await? {
-        JsonRpcResponse::Result(result) => {
-            u64::from_str(result.result.to_string().as_str())?
-        }
-        JsonRpcResponse::Error(error) =>{
-            return Err(error.into())
-        }
-    };
-
-    let latest_block:BlockEnv;
-    match get_block_by_number(rpc_url).await? {
-        GetBlockByNumberResponse::Result(result) => {
-            latest_block = create_block_env_from_block_details(result.result)?;
-        }
-        GetBlockByNumberResponse::Error(error) => {
-            return Err(error.into())
-        }
-    };
-
-    let prestate_tracer_response: PrestateTracerResponse = get_prestate_trace(
-        rpc_url, from, to, data, 4, format!("0x{:x}", latest_block.number)
-    ).await?;
-    let prestate_tracer_result = match prestate_tracer_response {
-        PrestateTracerResponse::Result(result) => {
-            result.result
-        }
-        PrestateTracerResponse::Error(error) => {
-            return Err(error.into())
-        }
-    };
-
-    let result = trace_transaction(
+    // Fetch the latest block, the nonce and the prestate, then trace
+    let result = fetch_and_trace_transaction(
+        &client,
         chain_id,
         from.parse()?,
-        from_nonce,
         to.parse()?,
         Bytes::from_str(data)?,
-        U256::from_str("0x4").unwrap(),
+        U256::from(4),
         gas_limit,
         gas_price,
-        gas_priority_fee,
-        latest_block,
-        prestate_tracer_result
-    )?;
+        gas_priority_fee
+    ).await?;
     println!("Execution result: {:?}", result.0);
     println!("State Diff: {:?}", result.1);
     println!("Trace result: {:#}", result.2);
@@ -169,9 +43,9 @@ async fn trace_op_sepolia() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
     // RPC endpoint
-    let rpc_url_string = env::var("OP_SEPOLIA_RPC_URL")
+    let rpc_url = env::var("OP_SEPOLIA_RPC_URL")
             .expect("OP_SEPOLIA_RPC_URL must be set.");
-    let rpc_url = rpc_url_string.as_str();
+    let client = RpcClient::new(&rpc_url)?;
 
     // Transaction details
     let chain_id = 11155420; //optimism sepolia
@@ -182,51 +56,18 @@ async fn trace_op_sepolia() -> Result<(), Box<dyn std::error::Error>> {
     let gas_price = 0x10c8ea;
     let gas_priority_fee = 0x10c8e0;
 
-    // Get current nonce from the network
-    let from_nonce:u64 = match get_nonce(rpc_url, from).await? {
-        JsonRpcResponse::Result(result) => {
-            u64::from_str(result.result.to_string().as_str())?
-        }
-        JsonRpcResponse::Error(error) =>{
-            return Err(error.into())
-        }
-    };
-
-    let latest_block:BlockEnv;
-    match get_block_by_number(rpc_url).await? {
-        GetBlockByNumberResponse::Result(result) => {
-            latest_block = create_block_env_from_block_details(result.result)?;
-        }
-        GetBlockByNumberResponse::Error(error) => {
-            return Err(error.into())
-        }
-    };
-
-    let prestate_tracer_response: PrestateTracerResponse = get_prestate_trace(
-        rpc_url, from, to, data, 0, format!("0x{:x}", latest_block.number)
-    ).await?;
-    let prestate_tracer_result = match prestate_tracer_response {
-        PrestateTracerResponse::Result(result) => {
-            result.result
-        }
-        PrestateTracerResponse::Error(error) => {
-            return Err(error.into())
-        }
-    };
-
-    let result = op_trace_transaction(
+    // Fetch the latest block, the nonce and the prestate, then trace
+    let result = op_fetch_and_trace_transaction(
+        &client,
         chain_id,
         from.parse()?,
-        from_nonce,
         to.parse()?,
         Bytes::from_str(data)?,
         U256::ZERO,
         gas_limit,
         gas_price,
-        gas_priority_fee,
-        latest_block,
-        prestate_tracer_result
-    )?;
+        gas_priority_fee
+    ).await?;
     println!("Execution result: {:?}", result.0);
     println!("State Diff: {:?}", result.1);
     println!("Trace result: {:#}", result.2);
@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::Path;
+
+use alloy_dyn_abi::{DynSolValue, EventExt, FunctionExt, JsonAbiExt};
+use alloy_json_abi::{Event, Function, JsonAbi, Param};
+use revm::primitives::{Address, Bytes, HashMap, Log};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::revert::{annotate_revert_reasons, frame_output, RevertDecoder};
+use crate::signatures::SignatureDatabase;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: Value,
+}
+
+/// JSON form of a decoded ABI value. Integers are decimal strings so that
+/// 256 bit values survive, byte strings are hex.
+pub fn dyn_sol_value_to_json(value: &DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(value) => json!(value),
+        DynSolValue::Int(value, _) => json!(value.to_string()),
+        DynSolValue::Uint(value, _) => json!(value.to_string()),
+        DynSolValue::FixedBytes(word, size) => {
+            json!(revm::primitives::hex::encode_prefixed(&word[..*size]))
+        }
+        DynSolValue::Address(address) => json!(address.to_checksum(None)),
+        DynSolValue::Function(function) => json!(function.to_string()),
+        DynSolValue::Bytes(bytes) => json!(revm::primitives::hex::encode_prefixed(bytes)),
+        DynSolValue::String(value) => json!(value),
+        DynSolValue::Array(values)
+        | DynSolValue::FixedArray(values)
+        | DynSolValue::Tuple(values) => {
+            Value::Array(values.iter().map(dyn_sol_value_to_json).collect())
+        }
+    }
+}
+
+pub fn decoded_params(params: &[Param], values: &[DynSolValue]) -> Vec<DecodedParam> {
+    params.iter().zip(values.iter()).map(|(param, value)| DecodedParam {
+        name: param.name.clone(),
+        ty: param.selector_type().into_owned(),
+        value: dyn_sol_value_to_json(value),
+    }).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedCall {
+    pub name: String,
+    pub signature: String,
+    pub inputs: Vec<DecodedParam>,
+    pub outputs: Option<Vec<DecodedParam>>,
+    /// Decoded from a signature database match rather than the contract ABI.
+    pub guessed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedEvent {
+    pub name: String,
+    pub signature: String,
+    pub params: Vec<DecodedParam>,
+    pub guessed: bool,
+}
+
+/// Contract ABIs keyed by the address they are deployed at.
+#[derive(Debug, Clone, Default)]
+pub struct AbiRegistry {
+    abis: HashMap<Address, JsonAbi>,
+    signatures: SignatureDatabase,
+}
+
+impl AbiRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges `abi` into the one already known for `address`, e.g. a proxy
+    /// and its implementation.
+    pub fn add_abi(&mut self, address: Address, abi: JsonAbi) {
+        let merged = match self.abis.remove(&address) {
+            Some(known) => known.into_items().chain(abi.into_items()).collect(),
+            None => abi,
+        };
+        self.abis.insert(address, merged);
+    }
+
+    /// Accepts a plain JSON ABI as well as a Foundry or Hardhat artifact,
+    /// both of which keep the ABI under `"abi"`.
+    pub fn add_abi_json(&mut self, address: Address, json: &str) -> Result<(), String> {
+        let value: Value = serde_json::from_str(json).map_err(|error| error.to_string())?;
+        let abi = match value {
+            Value::Object(mut artifact) => match artifact.remove("abi") {
+                Some(abi) => abi,
+                None => return Err(String::from("artifact has no \"abi\" field")),
+            },
+            abi => abi,
+        };
+        let abi: JsonAbi = serde_json::from_value(abi).map_err(|error| error.to_string())?;
+        self.add_abi(address, abi);
+        Ok(())
+    }
+
+    pub fn add_abi_file(&mut self, address: Address, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        self.add_abi_json(address, &json)
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Signatures used to guess calls, events and errors of contracts
+    /// without a registered ABI.
+    pub fn set_signature_database(&mut self, signatures: SignatureDatabase) {
+        self.signatures = signatures;
+    }
+
+    pub fn abi(&self, address: &Address) -> Option<&JsonAbi> {
+        self.abis.get(address)
+    }
+
+    /// Revert decoder knowing the custom errors of every registered ABI and
+    /// of the signature database.
+    pub fn revert_decoder(&self) -> RevertDecoder {
+        let mut decoder = self.signatures.revert_decoder();
+        for abi in self.abis.values() {
+            decoder.add_abi(abi);
+        }
+        decoder
+    }
+
+    /// Decodes calldata sent to `address`, and `output` when the call
+    /// returned successfully. `None` for unknown contracts or selectors.
+    pub fn decode_call(
+        &self,
+        address: &Address,
+        input: &[u8],
+        output: Option<&[u8]>
+    ) -> Option<DecodedCall> {
+        let abi = self.abis.get(address)?;
+        let selector: [u8; 4] = input.get(..4)?.try_into().ok()?;
+        let function = abi.function_by_selector(selector.into())?;
+        decode_function_call(function, input, output, false)
+    }
+
+    /// Decodes a log with the ABI of the contract that emitted it, or
+    /// guesses it from the signature database.
+    pub fn decode_log(&self, log: &Log) -> Option<DecodedEvent> {
+        self.abis.get(&log.address)
+            .and_then(|abi| decode_event(abi.events(), log, false))
+            .or_else(|| self.signatures.decode_log(log))
+    }
+}
+
+/// Decodes calldata, and the return data if any, with `function`.
+pub(crate) fn decode_function_call(
+    function: &Function,
+    input: &[u8],
+    output: Option<&[u8]>,
+    guessed: bool
+) -> Option<DecodedCall> {
+    let inputs = function.abi_decode_input(input.get(4..)?).ok()?;
+    // signatures do not carry return types
+    let outputs = output
+        .filter(|_| !guessed)
+        .and_then(|output| function.abi_decode_output(output).ok())
+        .map(|outputs| decoded_params(&function.outputs, &outputs));
+    Some(DecodedCall {
+        name: function.name.clone(),
+        signature: function.signature(),
+        inputs: decoded_params(&function.inputs, &inputs),
+        outputs,
+        guessed,
+    })
+}
+
+/// Decodes `log` with the first of `events` whose topic and layout match.
+pub(crate) fn decode_event<'a>(
+    events: impl IntoIterator<Item = &'a Event>,
+    log: &Log,
+    guessed: bool
+) -> Option<DecodedEvent> {
+    let topic0 = log.topics().first()?;
+    events.into_iter()
+        .filter(|event| !event.anonymous && event.selector() == *topic0)
+        .find_map(|event| {
+            let decoded = event.decode_log(&log.data).ok()?;
+            let mut indexed = decoded.indexed.iter();
+            let mut body = decoded.body.iter();
+            let params = event.inputs.iter().map(|input| {
+                let value = if input.indexed { indexed.next() } else { body.next() };
+                DecodedParam {
+                    name: input.name.clone(),
+                    ty: input.selector_type().into_owned(),
+                    value: value.map(dyn_sol_value_to_json).unwrap_or(Value::Null),
+                }
+            }).collect();
+            Some(DecodedEvent {
+                name: event.name.clone(),
+                signature: event.signature(),
+                params,
+                guessed,
+            })
+        })
+}
+
+pub(crate) fn frame_address(frame: &Value, field: &str) -> Option<Address> {
+    serde_json::from_value(frame["inputs"][field].clone()).ok()
+}
+
+pub(crate) fn frame_logs(frame: &Value) -> Vec<Log> {
+    serde_json::from_value(frame["logs"].clone()).unwrap_or_default()
+}
+
+fn annotate_frame(frame: &mut Value, registry: &AbiRegistry) {
+    if frame.get("isCreate").is_none() {
+        let input: Option<Bytes> = serde_json::from_value(frame["input_bytes"].clone()).ok();
+        let output: Option<Vec<u8>> = match frame["outcome"]["result"]["result"].as_str() {
+            Some("Return") | Some("Stop") => frame_output(frame).map(Vec::from),
+            _ => None,
+        };
+        // a proxy is registered under its own address, its implementation
+        // under the address it was delegated to
+        let decoded = input.and_then(|input| {
+            [frame_address(frame, "target_address"), frame_address(frame, "bytecode_address")]
+                .into_iter()
+                .flatten()
+                .find_map(|address| registry.decode_call(&address, &input, output.as_deref()))
+                .or_else(|| registry.signatures.decode_call(&input, output.as_deref()))
+        });
+        if let Some(decoded) = decoded {
+            frame["decoded"] = json!(decoded);
+        }
+    }
+
+    let logs = frame_logs(frame);
+    if !logs.is_empty() {
+        let decoded_logs: Vec<Option<DecodedEvent>> = logs.iter()
+            .map(|log| registry.decode_log(log))
+            .collect();
+        frame["decodedLogs"] = json!(decoded_logs);
+    }
+
+    if let Some(calls) = frame.get_mut("calls").and_then(Value::as_array_mut) {
+        for call in calls.iter_mut() {
+            annotate_frame(call, registry);
+        }
+    }
+}
+
+/// Adds to every frame of a trace the decoded function call and return
+/// values (`decoded`) and the decoded events (`decodedLogs`, `null` for
+/// unknown ones), and re-decodes revert reasons with the registered custom
+/// errors. Contracts without an ABI fall back to the signature database;
+/// calldata with an unknown selector is left as is.
+pub fn annotate_trace_with_abis(trace: &mut Value, registry: &AbiRegistry) {
+    if !trace.is_object() {
+        return;
+    }
+    annotate_frame(trace, registry);
+    annotate_revert_reasons(trace, &registry.revert_decoder());
+}
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use revm::bytecode::opcode;
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::transaction::{AccessList, AccessListItem};
+use revm::context::{BlockEnv, CfgEnv, ContextTr, JournalTr};
+use revm::database::InMemoryDB;
+use revm::interpreter::interpreter::EthInterpreter;
+use revm::interpreter::interpreter_types::{InputsTr, Jumps};
+use revm::interpreter::Interpreter;
+use revm::primitives::{Address, Bytes, HashMap, B256, U256};
+use revm::Inspector;
+use serde::Serialize;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::trace::{inspect_mainnet_transaction, tx_env_build_error_to_string, tx_env_builder};
+
+/// Rounds after which a list that keeps changing is returned as is.
+pub const MAX_ACCESS_LIST_ITERATIONS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListResult {
+    pub access_list: AccessList,
+    /// Gas used with `access_list`.
+    pub gas_used: u64,
+    pub gas_used_without_access_list: u64,
+    /// Result of the run with `access_list`.
+    pub result: ExecutionResult<HaltReason>,
+}
+
+/// Collects the addresses and storage keys touched by a run, except the
+/// addresses in `excluded` and the precompiles.
+struct AccessListInspector {
+    excluded: BTreeSet<Address>,
+    accessed: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+impl AccessListInspector {
+    fn new(excluded: BTreeSet<Address>) -> Self {
+        Self { excluded, accessed: BTreeMap::new() }
+    }
+
+    fn into_access_list(self) -> AccessList {
+        AccessList(self.accessed.into_iter()
+            .map(|(address, keys)| AccessListItem {
+                address,
+                storage_keys: keys.into_iter().collect(),
+            })
+            .collect())
+    }
+}
+
+impl<CTX: ContextTr> Inspector<CTX, EthInterpreter> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, context: &mut CTX) {
+        let (address, slot) = match interp.bytecode.opcode() {
+            opcode::SLOAD | opcode::SSTORE => match interp.stack.peek(0) {
+                Ok(slot) => (interp.input.target_address(), Some(B256::from(slot))),
+                Err(_) => return,
+            },
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::SELFDESTRUCT => match interp.stack.peek(0) {
+                Ok(address) => (Address::from_word(address.into()), None),
+                Err(_) => return,
+            },
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                match interp.stack.peek(1) {
+                    Ok(address) => (Address::from_word(address.into()), None),
+                    Err(_) => return,
+                }
+            }
+            _ => return,
+        };
+        // storage keys are listed for any contract, the always warm
+        // addresses are only left out when accessed as accounts
+        match slot {
+            Some(slot) => {
+                self.accessed.entry(address).or_default().insert(slot);
+            }
+            None => {
+                if self.excluded.contains(&address)
+                    || context.journal_ref().precompile_addresses().contains(&address) {
+                    return;
+                }
+                self.accessed.entry(address).or_default();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_with_access_list(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    block_env: &BlockEnv,
+    db: &InMemoryDB,
+    access_list: &AccessList
+) -> Result<(ExecutionResult<HaltReason>, AccessList), String> {
+    let tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value, gas_limit, gas_price, gas_priority_fee
+    ).access_list(access_list.clone()).build().map_err(tx_env_build_error_to_string)?;
+    let mut inspector = AccessListInspector::new(BTreeSet::from([from, to]));
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, _, _) = inspect_mainnet_transaction(
+        cfg_env, block_env.clone(), db.clone(), tx, &mut inspector
+    )?;
+    Ok((result, inspector.into_access_list()))
+}
+
+/// `eth_createAccessList` on the prestate: the transaction is run with the
+/// access list of the previous run until the list no longer changes, since
+/// a different list can change the path execution takes. The sender, the
+/// recipient and the precompiles are always warm, so they are only listed
+/// for their storage keys.
+#[allow(clippy::too_many_arguments)]
+pub fn create_access_list(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<AccessListResult, String> {
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+    let run = |access_list: &AccessList| run_with_access_list(
+        chain_id, from, from_nonce, to, data.clone(), value, gas_limit, gas_price,
+        gas_priority_fee, &latest_block_env, &db, access_list
+    );
+
+    let (result_without, mut accessed) = run(&AccessList::default())?;
+    let mut access_list = AccessList::default();
+    let mut result = result_without.clone();
+    for _ in 0..MAX_ACCESS_LIST_ITERATIONS {
+        if accessed == access_list {
+            break;
+        }
+        access_list = accessed;
+        (result, accessed) = run(&access_list)?;
+    }
+
+    Ok(AccessListResult {
+        access_list,
+        gas_used: result.gas_used(),
+        gas_used_without_access_list: result_without.gas_used(),
+        result,
+    })
+}
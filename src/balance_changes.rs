@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+
+use revm::context::result::ExecutionResult;
+use revm::context::BlockEnv;
+use revm::primitives::{Address, HashMap, U256};
+use revm::state::Account;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::abi::frame_address;
+use crate::database::AccountDetails;
+use crate::revert::is_failed_frame;
+use crate::transfers::{extract_asset_movements, AssetMovement, TokenStandard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Asset {
+    Native,
+    Erc20 { token: Address },
+    Erc721 { token: Address, id: U256 },
+    Erc1155 { token: Address, id: U256 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValueTransferKind {
+    Call,
+    Create,
+    SelfDestruct,
+}
+
+/// Ether moved by a frame that did not revert or halt. `frame` is the path of
+/// indexes into `calls` from the root frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueTransfer {
+    pub kind: ValueTransferKind,
+    pub frame: Vec<usize>,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+/// Fees of the transaction: the sender pays `gas_used * effective_gas_price`,
+/// the base fee part is burned and the rest is credited to the coinbase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasFees {
+    pub payer: Address,
+    pub coinbase: Address,
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+    pub total_fee: U256,
+    pub burned_fee: U256,
+    pub priority_fee: U256,
+}
+
+/// Net change of a balance as a sign and a magnitude, so that a transfer of
+/// any `U256` amount fits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceDelta {
+    pub negative: bool,
+    pub amount: U256,
+}
+
+impl BalanceDelta {
+    pub fn increase(amount: U256) -> Self {
+        Self { negative: false, amount }
+    }
+
+    pub fn decrease(amount: U256) -> Self {
+        Self { negative: !amount.is_zero(), amount }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    /// `None` when the magnitude overflows a `U256`.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        if self.negative == other.negative || self.is_zero() || other.is_zero() {
+            let negative = if self.is_zero() { other.negative } else { self.negative };
+            return Some(Self { negative, amount: self.amount.checked_add(other.amount)? });
+        }
+        Some(match self.amount >= other.amount {
+            true => Self { negative: self.negative, amount: self.amount - other.amount },
+            false => Self { negative: other.negative, amount: other.amount - self.amount },
+        }.normalized())
+    }
+
+    fn normalized(self) -> Self {
+        Self { negative: self.negative && !self.is_zero(), amount: self.amount }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceChange {
+    pub address: Address,
+    pub asset: Asset,
+    pub delta: BalanceDelta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceChangeReport {
+    pub value_transfers: Vec<ValueTransfer>,
+    pub gas_fees: GasFees,
+    /// Net change per address and asset, zero changes left out.
+    pub changes: Vec<BalanceChange>,
+}
+
+fn frame_u256(value: &Value) -> Option<U256> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+fn collect_value_transfers(frame: &Value, path: &mut Vec<usize>, transfers: &mut Vec<ValueTransfer>) {
+    if is_failed_frame(frame) {
+        return;
+    }
+    let caller = frame_address(frame, "caller");
+    if frame.get("isCreate").is_some() {
+        let created = serde_json::from_value::<Address>(frame["outcome"]["address"].clone()).ok();
+        let value = frame_u256(&frame["inputs"]["value"]).unwrap_or_default();
+        if let (Some(from), Some(to), false) = (caller, created, value.is_zero()) {
+            transfers.push(ValueTransfer {
+                kind: ValueTransferKind::Create, frame: path.clone(), from, to, value
+            });
+        }
+    } else {
+        // only a `Transfer` moves ether, `Apparent` is the value seen by a
+        // DELEGATECALL, and CALLCODE sends it to the caller itself
+        let value = frame_u256(&frame["inputs"]["value"]["Transfer"]).unwrap_or_default();
+        let to = frame_address(frame, "target_address");
+        if let (Some(from), Some(to), false) = (caller, to, value.is_zero())
+            && from != to {
+            transfers.push(ValueTransfer {
+                kind: ValueTransferKind::Call, frame: path.clone(), from, to, value
+            });
+        }
+    }
+    if let Some(calls) = frame["calls"].as_array() {
+        for (index, call) in calls.iter().enumerate() {
+            path.push(index);
+            collect_value_transfers(call, path, transfers);
+            path.pop();
+        }
+    }
+    // SELFDESTRUCT ends the frame, after every call it made
+    if let Some(selfdestruct) = frame.get("selfdestruct") {
+        let from = serde_json::from_value::<Address>(selfdestruct["contract"].clone()).ok();
+        let to = serde_json::from_value::<Address>(selfdestruct["target"].clone()).ok();
+        let value = frame_u256(&selfdestruct["value"]).unwrap_or_default();
+        if let (Some(from), Some(to), false) = (from, to, value.is_zero()) {
+            transfers.push(ValueTransfer {
+                kind: ValueTransferKind::SelfDestruct, frame: path.clone(), from, to, value
+            });
+        }
+    }
+}
+
+/// Ether moved by calls, creates and selfdestructs of a trace, the
+/// transaction value included.
+pub fn extract_value_transfers(trace: &Value) -> Vec<ValueTransfer> {
+    let mut transfers = Vec::new();
+    if trace.is_object() {
+        collect_value_transfers(trace, &mut Vec::new(), &mut transfers);
+    }
+    transfers
+}
+
+/// Fees paid by `from` for an EIP-1559 style `gas_price` (max fee) and
+/// `gas_priority_fee`, on top of `block_env.basefee`.
+pub fn compute_gas_fees(
+    from: Address,
+    gas_price: u128,
+    gas_priority_fee: Option<u128>,
+    gas_used: u64,
+    block_env: &BlockEnv
+) -> GasFees {
+    let base_fee = block_env.basefee as u128;
+    let effective_gas_price = match gas_priority_fee {
+        Some(priority_fee) => gas_price.min(base_fee.saturating_add(priority_fee)),
+        None => gas_price,
+    };
+    let total_fee = U256::from(gas_used) * U256::from(effective_gas_price);
+    let burned_fee = U256::from(gas_used) * U256::from(base_fee.min(effective_gas_price));
+    GasFees {
+        payer: from,
+        coinbase: block_env.beneficiary,
+        gas_used,
+        effective_gas_price,
+        total_fee,
+        burned_fee,
+        priority_fee: total_fee - burned_fee,
+    }
+}
+
+fn add_delta(
+    deltas: &mut BTreeMap<(Address, Asset), BalanceDelta>,
+    address: Address,
+    asset: Asset,
+    delta: BalanceDelta
+) -> Result<(), String> {
+    let entry = deltas.entry((address, asset)).or_default();
+    *entry = entry.checked_add(delta).ok_or(format!(
+        "balance change of {} in {:?} overflows 256 bits", address, asset
+    ))?;
+    Ok(())
+}
+
+/// Builds the asset changes of a transaction traced by
+/// [`trace_transaction`](crate::trace::trace_transaction). Ether deltas come
+/// from the state diff against the prestate balances, so they include the
+/// fees; token deltas come from the transfer events of frames that did not
+/// revert or halt. Mints and burns are not credited to the zero address.
+/// Fails when the token events of an address add up to more than a `U256`.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_balance_changes<HaltReasonTy>(
+    from: Address,
+    gas_price: u128,
+    gas_priority_fee: Option<u128>,
+    block_env: &BlockEnv,
+    execution_result: &ExecutionResult<HaltReasonTy>,
+    state_diff: &HashMap<Address, Account>,
+    prestate_tracer_result: &HashMap<Address, AccountDetails>,
+    trace: &Value
+) -> Result<BalanceChangeReport, String> {
+    let mut deltas: BTreeMap<(Address, Asset), BalanceDelta> = BTreeMap::new();
+
+    for (address, account) in state_diff.iter() {
+        if !account.is_touched() {
+            continue;
+        }
+        let before = prestate_tracer_result.get(address)
+            .and_then(|account| account.balance)
+            .unwrap_or_default();
+        let delta = match account.info.balance >= before {
+            true => BalanceDelta::increase(account.info.balance - before),
+            false => BalanceDelta::decrease(before - account.info.balance),
+        };
+        add_delta(&mut deltas, *address, Asset::Native, delta)?;
+    }
+
+    for movement in extract_asset_movements(trace) {
+        let AssetMovement::Transfer { token, standard, from, to, id, amount, .. } = movement.movement else {
+            continue;
+        };
+        let asset = match (standard, id) {
+            (TokenStandard::Erc20, _) => Asset::Erc20 { token },
+            (TokenStandard::Erc721, Some(id)) => Asset::Erc721 { token, id },
+            (TokenStandard::Erc1155, Some(id)) => Asset::Erc1155 { token, id },
+            _ => continue,
+        };
+        if from != Address::ZERO {
+            add_delta(&mut deltas, from, asset, BalanceDelta::decrease(amount))?;
+        }
+        if to != Address::ZERO {
+            add_delta(&mut deltas, to, asset, BalanceDelta::increase(amount))?;
+        }
+    }
+
+    let changes = deltas.into_iter()
+        .filter(|(_, delta)| !delta.is_zero())
+        .map(|((address, asset), delta)| BalanceChange { address, asset, delta })
+        .collect();
+
+    Ok(BalanceChangeReport {
+        value_transfers: extract_value_transfers(trace),
+        gas_fees: compute_gas_fees(
+            from, gas_price, gas_priority_fee, execution_result.gas_used(), block_env
+        ),
+        changes,
+    })
+}
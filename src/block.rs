@@ -3,26 +3,68 @@ use revm::primitives::{Address, U256, B256};
 use revm::{
     context::BlockEnv,
     context_interface::block::BlobExcessGasAndPrice,
+    primitives::eip4844::{
+        BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN, BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE
+    },
+    primitives::hardfork::SpecId,
     primitives::ruint::FromUintError
 };
 
 use crate::json_rpc::JsonRpcResponse;
+use crate::transaction::TransactionDetails;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct BlockDetails {
+    pub hash: Option<B256>,
     pub number: U256,
+    #[serde(rename(deserialize = "parentHash"), default)]
+    pub parent_hash: B256,
     pub miner: Address,
     pub timestamp: U256,
+    // absent from the responses of some nodes
+    #[serde(rename(deserialize = "stateRoot"))]
+    pub state_root: Option<B256>,
     #[serde(rename(deserialize = "gasLimit"))]
     pub gas_limit: U256,
-    #[serde(rename(deserialize = "baseFeePerGas"))]
+    #[serde(rename(deserialize = "gasUsed"), default)]
+    pub gas_used: U256,
+    // absent before London
+    #[serde(rename(deserialize = "baseFeePerGas"), default)]
     pub base_fee_per_gas: U256,
     pub difficulty: U256,
-    #[serde(rename(deserialize = "excessBlobGas"))]
+    /// PREVRANDAO since the merge.
+    #[serde(rename(deserialize = "mixHash"))]
+    pub mix_hash: Option<B256>,
+    // absent before Cancun
+    #[serde(rename(deserialize = "excessBlobGas"), default)]
     pub excess_blob_gas: U256,
+    #[serde(rename(deserialize = "parentBeaconBlockRoot"))]
+    pub parent_beacon_block_root: Option<B256>,
 }
 pub type GetBlockByNumberResponse = JsonRpcResponse<BlockDetails>;
 
+/// Validator withdrawal (EIP-4895), `amount` is in gwei.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Withdrawal {
+    pub index: U256,
+    #[serde(rename(deserialize = "validatorIndex"))]
+    pub validator_index: U256,
+    pub address: Address,
+    pub amount: U256,
+}
+
+/// `eth_getBlockByNumber` result with full transaction objects.
+#[derive(Debug, Deserialize)]
+pub struct BlockWithTransactions {
+    #[serde(flatten)]
+    pub details: BlockDetails,
+    pub transactions: Vec<TransactionDetails>,
+    // absent before Shanghai
+    #[serde(default)]
+    pub withdrawals: Vec<Withdrawal>,
+}
+pub type GetBlockWithTransactionsResponse = JsonRpcResponse<BlockWithTransactions>;
+
 pub fn create_block_env_from_block_details(
     block_details: BlockDetails
 )->Result<BlockEnv, FromUintError<u64>> {
@@ -33,7 +75,7 @@ pub fn create_block_env_from_block_details(
         gas_limit: block_details.gas_limit.try_into()?,
         basefee: block_details.base_fee_per_gas.try_into()?,
         difficulty: block_details.difficulty,
-        prevrandao: Some(B256::from(block_details.difficulty)),
+        prevrandao: Some(block_details.mix_hash.unwrap_or(B256::from(block_details.difficulty))),
         blob_excess_gas_and_price: Some(
             BlobExcessGasAndPrice::new(
                 1
@@ -46,3 +88,22 @@ pub fn create_block_env_from_block_details(
         )
     })
 }
+
+/// [`create_block_env_from_block_details`] with the blob gas price of the
+/// block, for replaying it at `spec_id`.
+pub(crate) fn create_block_env_for_spec(
+    block_details: &BlockDetails,
+    spec_id: SpecId
+) -> Result<BlockEnv, String> {
+    let excess_blob_gas: u64 = block_details.excess_blob_gas.try_into()
+        .map_err(|error| format!("excess blob gas: {}", error))?;
+    let mut block_env = create_block_env_from_block_details(block_details.clone())
+        .map_err(|error| format!("block header: {}", error))?;
+    let update_fraction = match spec_id.is_enabled_in(SpecId::PRAGUE) {
+        true => BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE,
+        false => BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN,
+    };
+    block_env.blob_excess_gas_and_price =
+        Some(BlobExcessGasAndPrice::new(excess_blob_gas, update_fraction));
+    Ok(block_env)
+}
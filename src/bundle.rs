@@ -0,0 +1,116 @@
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv};
+use revm::database::InMemoryDB;
+use revm::primitives::{Address, Bytes, HashMap, U256};
+use revm::state::{Account, EvmState};
+use revm::{Database, DatabaseCommit};
+use serde_json::Value;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::trace::{
+    inspect_mainnet_transaction, pop_trace_result, tx_env_build_error_to_string, tx_env_builder
+};
+
+#[derive(Debug, Clone)]
+pub struct BundleTransaction {
+    pub from: Address,
+    /// Taken from the bundle state when `None`, so that several
+    /// transactions of one sender follow each other.
+    pub nonce: Option<u64>,
+    pub to: Address,
+    pub data: Bytes,
+    pub value: U256,
+    pub gas_limit: u64,
+    pub gas_price: u128,
+    pub gas_priority_fee: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct BundleTransactionResult {
+    pub result: ExecutionResult<HaltReason>,
+    pub state_diff: EvmState,
+    pub trace: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct BundleResult {
+    pub transactions: Vec<BundleTransactionResult>,
+    /// State of every account touched by the bundle, with the storage
+    /// original values as they were before the first transaction.
+    pub state_diff: HashMap<Address, Account>,
+}
+
+/// Folds the state changes of a later transaction into `cumulative`.
+pub(crate) fn merge_state_diff(cumulative: &mut HashMap<Address, Account>, state_diff: &EvmState) {
+    for (address, account) in state_diff.iter() {
+        if !account.is_touched() {
+            continue;
+        }
+        let Some(merged) = cumulative.get_mut(address) else {
+            cumulative.insert(*address, account.clone());
+            continue;
+        };
+        merged.info = account.info.clone();
+        merged.status |= account.status;
+        for (slot, value) in account.storage.iter() {
+            match merged.storage.get_mut(slot) {
+                Some(merged_slot) => merged_slot.present_value = value.present_value,
+                None => {
+                    merged.storage.insert(*slot, value.clone());
+                }
+            }
+        }
+    }
+}
+
+fn trace_bundle_transaction(
+    chain_id: u64,
+    block_env: &BlockEnv,
+    db: &mut InMemoryDB,
+    transaction: &BundleTransaction
+) -> Result<BundleTransactionResult, String> {
+    let nonce = match transaction.nonce {
+        Some(nonce) => nonce,
+        None => db.basic(transaction.from)
+            .map_err(|error| error.to_string())?
+            .map(|info| info.nonce)
+            .unwrap_or(0),
+    };
+    let tx = tx_env_builder(
+        chain_id, transaction.from, nonce, transaction.to, transaction.data.clone(),
+        transaction.value, transaction.gas_limit, transaction.gas_price,
+        transaction.gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+
+    let buffer = &mut Vec::new();
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, state_diff, _) = inspect_mainnet_transaction(
+        cfg_env, block_env.clone(), &mut *db, tx, MyInspector::new(buffer)
+    )?;
+    Ok(BundleTransactionResult { result, state_diff, trace: pop_trace_result(buffer) })
+}
+
+/// Traces `transactions` in order on one database built from the prestate,
+/// committing the state of each transaction before the next one runs, as
+/// in a block. Reverted transactions are committed too since they still
+/// pay for gas; an invalid transaction aborts the bundle.
+pub fn trace_bundle(
+    chain_id: u64,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    transactions: Vec<BundleTransaction>
+) -> Result<BundleResult, String> {
+    let mut db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+    let mut results = Vec::with_capacity(transactions.len());
+    let mut state_diff = HashMap::default();
+
+    for (index, transaction) in transactions.iter().enumerate() {
+        let result = trace_bundle_transaction(chain_id, &latest_block_env, &mut db, transaction)
+            .map_err(|error| format!("transaction {}: {}", index, error))?;
+        merge_state_diff(&mut state_diff, &result.state_diff);
+        db.commit(result.state_diff.clone());
+        results.push(result);
+    }
+    Ok(BundleResult { transactions: results, state_diff })
+}
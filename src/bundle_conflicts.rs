@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+
+use revm::bytecode::opcode;
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv, ContextTr};
+use revm::database::InMemoryDB;
+use revm::interpreter::interpreter::EthInterpreter;
+use revm::interpreter::interpreter_types::{InputsTr, Jumps};
+use revm::interpreter::{CallInputs, CallOutcome, Interpreter};
+use revm::primitives::{Address, Bytes, HashMap, StorageKey, U256};
+use revm::state::EvmState;
+use revm::{Database, DatabaseCommit, Inspector};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::trace::{
+    inspect_mainnet_transaction, pop_trace_result, tx_env_build_error_to_string, tx_env_builder
+};
+
+/// `innerHandleOp` selectors of EntryPoint v0.6 and v0.7. The EntryPoint calls
+/// itself with this selector once validation is over, so every state access
+/// made before the first such call belongs to the validation phase.
+pub const INNER_HANDLE_OP_SELECTORS: [[u8; 4]; 2] = [
+    [0x1d, 0x73, 0x27, 0x56], // v0.6
+    [0x00, 0x42, 0xdc, 0x53], // v0.7
+];
+
+/// A `handleOps` transaction carrying a single UserOperation.
+#[derive(Debug, Clone)]
+pub struct UserOperationTransaction {
+    pub from: Address, // bundler
+    pub to: Address, // entry point
+    pub data: Bytes,
+    pub value: U256,
+    pub gas_limit: u64,
+    pub gas_price: u128,
+    pub gas_priority_fee: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StateAccess {
+    Balance { address: Address },
+    Storage { address: Address, slot: StorageKey },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateConflict {
+    pub earlier_op: usize,
+    pub access: StateAccess,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperationReport {
+    pub index: usize,
+    pub validation_reads: BTreeSet<StateAccess>,
+    pub writes: BTreeSet<StateAccess>,
+    pub standalone_result: ExecutionResult<HaltReason>,
+    pub bundled_result: ExecutionResult<HaltReason>,
+    pub conflicts: Vec<StateConflict>,
+    pub trace: Value,
+}
+
+impl UserOperationReport {
+    /// True when the op succeeds on its own but not after the earlier ops of
+    /// the bundle have been applied.
+    pub fn fails_in_bundle(&self) -> bool {
+        self.standalone_result.is_success() && !self.bundled_result.is_success()
+    }
+}
+
+struct ValidationReadInspector {
+    entry_point: Address,
+    in_validation: bool,
+    reads: BTreeSet<StateAccess>,
+}
+
+impl ValidationReadInspector {
+    fn new(entry_point: Address) -> Self {
+        Self { entry_point, in_validation: true, reads: BTreeSet::new() }
+    }
+}
+
+impl<CTX: ContextTr> Inspector<CTX, EthInterpreter> for ValidationReadInspector {
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        if !self.in_validation {
+            return;
+        }
+        let target = interp.input.target_address();
+        match interp.bytecode.opcode() {
+            opcode::SLOAD => {
+                if let Ok(slot) = interp.stack.peek(0) {
+                    self.reads.insert(StateAccess::Storage { address: target, slot });
+                }
+            }
+            opcode::BALANCE => {
+                if let Ok(address) = interp.stack.peek(0) {
+                    let address = Address::from_word(address.into());
+                    self.reads.insert(StateAccess::Balance { address });
+                }
+            }
+            opcode::SELFBALANCE => {
+                self.reads.insert(StateAccess::Balance { address: target });
+            }
+            _ => {}
+        }
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        // only the EntryPoint calling itself ends validation, accounts and
+        // paymasters may use the same selector
+        if inputs.caller != self.entry_point || inputs.target_address != self.entry_point {
+            return None;
+        }
+        let input = inputs.input.bytes(context);
+        if input.len() >= 4 && INNER_HANDLE_OP_SELECTORS.iter().any(|s| input[..4] == s[..]) {
+            self.in_validation = false;
+        }
+        None
+    }
+}
+
+struct OpRun {
+    result: ExecutionResult<HaltReason>,
+    state: EvmState,
+    validation_reads: BTreeSet<StateAccess>,
+    trace: Value,
+}
+
+fn run_user_operation(
+    chain_id: u64,
+    block_env: &BlockEnv,
+    db: &mut InMemoryDB,
+    op: &UserOperationTransaction,
+) -> Result<OpRun, String> {
+    // the bundler nonce moves with every committed op
+    let from_nonce = match db.basic(op.from) {
+        Ok(account) => account.map(|info| info.nonce).unwrap_or(0),
+        Err(error) => return Err(error.to_string()),
+    };
+    let tx = tx_env_builder(
+        chain_id, op.from, from_nonce, op.to, op.data.clone(), op.value,
+        op.gas_limit, op.gas_price, op.gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+
+    let buffer = &mut Vec::new();
+    let mut reads = ValidationReadInspector::new(op.to);
+    let inspector = (MyInspector::new(buffer), &mut reads);
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, state, _) = inspect_mainnet_transaction(
+        cfg_env, block_env.clone(), &mut *db, tx, inspector
+    )?;
+    let trace = pop_trace_result(buffer);
+
+    Ok(OpRun { result, state, validation_reads: reads.reads, trace })
+}
+
+fn collect_writes(
+    db: &mut InMemoryDB,
+    state: &EvmState,
+) -> Result<BTreeSet<StateAccess>, String> {
+    let mut writes = BTreeSet::new();
+    for (address, account) in state.iter() {
+        if !account.is_touched() {
+            continue;
+        }
+        for (slot, _) in account.changed_storage_slots() {
+            writes.insert(StateAccess::Storage { address: *address, slot: *slot });
+        }
+        let balance_before = match db.basic(*address) {
+            Ok(info) => info.map(|info| info.balance).unwrap_or(U256::ZERO),
+            Err(error) => return Err(error.to_string()),
+        };
+        if balance_before != account.info.balance {
+            writes.insert(StateAccess::Balance { address: *address });
+        }
+    }
+    Ok(writes)
+}
+
+/// Traces `user_operations` one by one on a database that accumulates the
+/// state of every op that went through, and reports for each op its
+/// validation read set, its write set and the earlier ops whose writes
+/// overlap what it read during validation.
+///
+/// Every op is also run alone on the prestate so that
+/// [`UserOperationReport::fails_in_bundle`] can tell ops that are broken on
+/// their own from ops that are invalidated by the rest of the bundle. Ops
+/// that fail in the bundle are not committed, as a bundler would drop them.
+pub fn detect_user_operation_conflicts(
+    chain_id: u64,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    user_operations: Vec<UserOperationTransaction>
+) -> Result<Vec<UserOperationReport>, String> {
+    let prestate_db = create_in_memory_database_from_prestate_trace(
+        prestate_tracer_result
+    );
+    let mut bundle_db = prestate_db.clone();
+    let mut committed_writes: Vec<(usize, BTreeSet<StateAccess>)> = Vec::new();
+    let mut reports = Vec::with_capacity(user_operations.len());
+
+    for (index, op) in user_operations.iter().enumerate() {
+        let standalone = run_user_operation(
+            chain_id, &latest_block_env, &mut prestate_db.clone(), op
+        )?;
+        let bundled = run_user_operation(
+            chain_id, &latest_block_env, &mut bundle_db, op
+        )?;
+
+        let mut conflicts = Vec::new();
+        for (earlier_op, writes) in committed_writes.iter() {
+            for access in bundled.validation_reads.intersection(writes) {
+                conflicts.push(StateConflict {
+                    earlier_op: *earlier_op,
+                    access: access.clone(),
+                });
+            }
+        }
+
+        let writes = collect_writes(&mut bundle_db, &bundled.state)?;
+        if bundled.result.is_success() {
+            bundle_db.commit(bundled.state);
+            committed_writes.push((index, writes.clone()));
+        }
+
+        reports.push(UserOperationReport {
+            index,
+            validation_reads: bundled.validation_reads,
+            writes,
+            standalone_result: standalone.result,
+            bundled_result: bundled.result,
+            conflicts,
+            trace: bundled.trace,
+        });
+    }
+    Ok(reports)
+}
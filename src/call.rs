@@ -0,0 +1,90 @@
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv};
+use revm::primitives::{Address, Bytes, HashMap, Log, U256};
+use revm::state::EvmState;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::revert::{decode_execution_revert, RevertDecoder, RevertReason};
+use crate::trace::{
+    inspect_mainnet_transaction, pop_trace_result, tx_env_build_error_to_string, tx_env_builder
+};
+
+/// `eth_call` outcome. `output` is the return data, or the revert data when
+/// `success` is false.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallResult {
+    pub success: bool,
+    pub output: Bytes,
+    pub logs: Vec<Log>,
+    pub gas_used: u64,
+    pub revert_reason: Option<RevertReason>,
+    pub halt_reason: Option<HaltReason>,
+    #[serde(skip)]
+    pub state_diff: EvmState,
+    pub trace: Value,
+}
+
+/// Configuration of a call simulation: the nonce, balance, base fee, block
+/// gas limit and EIP-3607 (sender has code) checks are off, so any account
+/// can call with any gas price, zero included.
+pub fn call_cfg_env(chain_id: u64) -> CfgEnv {
+    let mut cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    cfg_env.disable_nonce_check = true;
+    cfg_env.disable_balance_check = true;
+    cfg_env.disable_base_fee = true;
+    cfg_env.disable_block_gas_limit = true;
+    cfg_env.disable_eip3607 = true;
+    cfg_env
+}
+
+pub(crate) fn call_result(
+    result: ExecutionResult<HaltReason>,
+    state_diff: EvmState,
+    trace: Value
+) -> CallResult {
+    let revert_reason = decode_execution_revert(&result, &RevertDecoder::default());
+    let halt_reason = match &result {
+        ExecutionResult::Halt { reason, .. } => Some(*reason),
+        _ => None,
+    };
+    CallResult {
+        success: result.is_success(),
+        output: result.output().cloned().unwrap_or_default(),
+        logs: result.logs().to_vec(),
+        gas_used: result.gas_used(),
+        revert_reason,
+        halt_reason,
+        state_diff,
+        trace,
+    }
+}
+
+/// Simulates a call like `eth_call` on the prestate, from any sender and
+/// without gas fees. `gas_limit` defaults to the block gas limit.
+#[allow(clippy::too_many_arguments)]
+pub fn call_transaction(
+    chain_id: u64,
+    from: Address,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: Option<u64>,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<CallResult, String> {
+    let gas_limit = gas_limit.unwrap_or(latest_block_env.gas_limit);
+    let tx = tx_env_builder(chain_id, from, 0, to, data, value, gas_limit, 0, 0)
+        .build()
+        .map_err(tx_env_build_error_to_string)?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let (result, state_diff, _) = inspect_mainnet_transaction(
+        call_cfg_env(chain_id), latest_block_env, db, tx, MyInspector::new(buffer)
+    )?;
+    Ok(call_result(result, state_diff, pop_trace_result(buffer)))
+}
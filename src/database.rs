@@ -1,36 +1,58 @@
-use serde::Deserialize;
-use revm::database::InMemoryDB;
+use std::cell::RefCell;
+use std::{error::Error, fmt};
+use serde::{Deserialize, Serialize};
+use revm::database::{CacheDB, EmptyDB, InMemoryDB};
+use revm::database_interface::DBErrorMarker;
 use revm::state::{AccountInfo, Bytecode};
-use revm::primitives::{Address, StorageKey, StorageValue, Bytes, HashMap, U256};
+use revm::primitives::{Address, StorageKey, StorageValue, Bytes, HashMap, B256, U256};
+use revm::DatabaseRef;
 use crate::json_rpc::JsonRpcResponse;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct AccountDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub balance: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub nonce: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<Bytes>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_storage"
+    )]
     pub storage: Option<HashMap<StorageKey, StorageValue>>,
 }
 
+// geth writes storage keys and values as 32 byte words
+fn serialize_storage<S: serde::Serializer>(
+    storage: &Option<HashMap<StorageKey, StorageValue>>,
+    serializer: S
+) -> Result<S::Ok, S::Error> {
+    let words: Option<std::collections::BTreeMap<B256, B256>> = storage.as_ref().map(|storage| {
+        storage.iter()
+            .map(|(key, value)| (B256::from(*key), B256::from(*value)))
+            .collect()
+    });
+    words.serialize(serializer)
+}
+
 pub type PrestateTracerResponse = JsonRpcResponse<HashMap<Address, AccountDetails>>;
 
 pub fn create_in_memory_database_from_prestate_trace(
     prestate_tracer_result: HashMap<Address, AccountDetails>
 )->InMemoryDB { 
-    let mut database = InMemoryDB::default();
+    create_database_from_prestate_trace(prestate_tracer_result, EmptyDB::default())
+}
+
+/// Same as [`create_in_memory_database_from_prestate_trace`] but anything
+/// missing from the prestate is read from `ext_db` instead of being zero.
+pub fn create_database_from_prestate_trace<ExtDB: DatabaseRef>(
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    ext_db: ExtDB
+)->CacheDB<ExtDB> { 
+    let mut database = CacheDB::new(ext_db);
     for account_result in prestate_tracer_result.into_iter() {
         let account_address = account_result.0;
-        match account_result.1.storage {
-            Some(storage) => {
-                for storage_result in storage.into_iter() {
-                    _ = database.insert_account_storage(
-                            account_address, storage_result.0, storage_result.1
-                    ).unwrap();
-                };
-            }
-            None =>{ }
-        }
-
         let balance: U256 = account_result.1.balance.unwrap_or(U256::ZERO);
         let nonce: u64 = account_result.1.nonce.unwrap_or(0);
         let code_hash;
@@ -54,6 +76,121 @@ pub fn create_in_memory_database_from_prestate_trace(
                 code,
             }
         );
+
+        // the account is already cached, so this never reaches ext_db
+        if let Some(storage) = account_result.1.storage {
+            for storage_result in storage.into_iter() {
+                database.insert_account_storage(
+                        account_address, storage_result.0, storage_result.1
+                ).unwrap();
+            };
+        }
     };
     database
 }
+
+/// What a strict database does when execution reads state that is not in
+/// the prestate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictMode {
+    /// Keep going with empty values (like [`InMemoryDB`]) and record the read.
+    Record,
+    /// Abort execution on the first read.
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MissingState {
+    Account { address: Address },
+    Storage { address: Address, slot: StorageKey },
+}
+
+impl fmt::Display for MissingState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MissingState::Account { address } => {
+                write!(f, "account {} missing from prestate", address)
+            }
+            MissingState::Storage { address, slot } => {
+                write!(f, "storage slot {} of {} missing from prestate", slot, address)
+            }
+        }
+    }
+}
+
+impl Error for MissingState {}
+impl DBErrorMarker for MissingState {}
+
+/// Backing database of [`StrictDatabase`]. Every read that reaches it was
+/// not answered by the prestate cache above it.
+#[derive(Debug)]
+pub struct MissingStateRecorder {
+    mode: StrictMode,
+    missing: RefCell<Vec<MissingState>>,
+    fallback: EmptyDB,
+}
+
+impl MissingStateRecorder {
+    pub fn new(mode: StrictMode) -> Self {
+        Self { mode, missing: RefCell::new(Vec::new()), fallback: EmptyDB::default() }
+    }
+
+    /// Missing reads in the order they happened, without duplicates.
+    pub fn missing(&self) -> Vec<MissingState> {
+        self.missing.borrow().clone()
+    }
+
+    fn report(&self, missing: MissingState) -> Result<(), MissingState> {
+        if !self.missing.borrow().contains(&missing) {
+            self.missing.borrow_mut().push(missing.clone());
+        }
+        match self.mode {
+            StrictMode::Record => Ok(()),
+            StrictMode::Fail => Err(missing),
+        }
+    }
+}
+
+impl DatabaseRef for MissingStateRecorder {
+    type Error = MissingState;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.report(MissingState::Account { address })?;
+        Ok(None)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Ok(self.fallback.code_by_hash_ref(code_hash).unwrap())
+    }
+
+    fn storage_ref(
+        &self,
+        address: Address,
+        index: StorageKey
+    ) -> Result<StorageValue, Self::Error> {
+        self.report(MissingState::Storage { address, slot: index })?;
+        Ok(StorageValue::ZERO)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        // block hashes are never part of a prestate
+        Ok(self.fallback.block_hash_ref(number).unwrap())
+    }
+}
+
+pub type StrictDatabase = CacheDB<MissingStateRecorder>;
+
+/// Strict version of [`create_in_memory_database_from_prestate_trace`]: reads
+/// of accounts or storage slots absent from `prestate_tracer_result` are
+/// recorded (or rejected) according to `mode`. The list is available through
+/// `database.db.missing()` after execution.
+pub fn create_strict_database_from_prestate_trace(
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    mode: StrictMode
+)->StrictDatabase { 
+    create_database_from_prestate_trace(
+        prestate_tracer_result,
+        MissingStateRecorder::new(mode)
+    )
+}
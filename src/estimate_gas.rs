@@ -0,0 +1,151 @@
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv};
+use revm::database::InMemoryDB;
+use revm::inspector::NoOpInspector;
+use revm::interpreter::gas::CALL_STIPEND;
+use revm::primitives::{Address, Bytes, HashMap, U256};
+use revm::DatabaseRef;
+use serde::Serialize;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::revert::{RevertDecoder, RevertReason};
+use crate::trace::{inspect_mainnet_transaction, tx_env_build_error_to_string, tx_env_builder};
+
+/// Why the transaction fails even with the highest gas limit tried.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum GasEstimateFailure {
+    Revert { output: Bytes, reason: Option<RevertReason> },
+    Halt { reason: HaltReason },
+    Invalid { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasEstimate {
+    /// Lowest gas limit the transaction succeeds with, or the highest limit
+    /// tried when it never does.
+    pub gas: u64,
+    /// Number of executions of the transaction.
+    pub runs: usize,
+    pub failure: Option<GasEstimateFailure>,
+}
+
+struct Estimator<'a> {
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    block_env: &'a BlockEnv,
+    db: &'a InMemoryDB,
+    runs: usize,
+}
+
+impl Estimator<'_> {
+    /// Every attempt runs on its own copy of the prestate.
+    fn run(&mut self, gas_limit: u64) -> Result<ExecutionResult<HaltReason>, String> {
+        self.runs += 1;
+        let tx = tx_env_builder(
+            self.chain_id, self.from, self.from_nonce, self.to, self.data.clone(), self.value,
+            gas_limit, self.gas_price, self.gas_priority_fee
+        ).build().map_err(tx_env_build_error_to_string)?;
+        let cfg_env = CfgEnv::new().with_chain_id(self.chain_id);
+        let (result, _, _) = inspect_mainnet_transaction(
+            cfg_env, self.block_env.clone(), self.db.clone(), tx, NoOpInspector
+        )?;
+        Ok(result)
+    }
+
+    fn succeeds(&mut self, gas_limit: u64) -> bool {
+        self.run(gas_limit).is_ok_and(|result| result.is_success())
+    }
+}
+
+fn failure(result: Result<ExecutionResult<HaltReason>, String>) -> Option<GasEstimateFailure> {
+    match result {
+        Ok(ExecutionResult::Success { .. }) => None,
+        Ok(ExecutionResult::Revert { output, .. }) => Some(GasEstimateFailure::Revert {
+            reason: RevertDecoder::default().decode(&output),
+            output,
+        }),
+        Ok(ExecutionResult::Halt { reason, .. }) => Some(GasEstimateFailure::Halt { reason }),
+        Err(error) => Some(GasEstimateFailure::Invalid { error }),
+    }
+}
+
+/// `eth_estimateGas` on the prestate: binary search of the lowest gas limit
+/// the transaction succeeds with, between what it used when given the most
+/// gas and that upper bound. The upper bound is `gas_cap` (the block gas
+/// limit by default), lowered to what the sender can pay for.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_gas(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    gas_cap: Option<u64>
+) -> Result<GasEstimate, String> {
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let mut hi = gas_cap.unwrap_or(latest_block_env.gas_limit);
+    if gas_price > 0 {
+        let balance = db.basic_ref(from)
+            .map_err(|error| error.to_string())?
+            .map(|info| info.balance)
+            .unwrap_or_default();
+        let allowance = balance.saturating_sub(value) / U256::from(gas_price);
+        hi = hi.min(allowance.saturating_to::<u64>());
+    }
+
+    let mut estimator = Estimator {
+        chain_id, from, from_nonce, to, data, value, gas_price, gas_priority_fee,
+        block_env: &latest_block_env,
+        db: &db,
+        runs: 0,
+    };
+
+    let result = estimator.run(hi);
+    if let Some(failure) = failure(result.clone()) {
+        return Ok(GasEstimate { gas: hi, runs: estimator.runs, failure: Some(failure) });
+    }
+    let result = result?;
+    let refunded = match &result {
+        ExecutionResult::Success { gas_refunded, .. } => *gas_refunded,
+        _ => 0,
+    };
+
+    // refunds are paid back after execution, so the limit has to cover the
+    // gas spent before them; a limit below what was used cannot succeed
+    let mut lo = result.gas_used().saturating_sub(1);
+
+    // calls only forward 63/64 of the gas left, try the spent gas scaled
+    // back up first, it is usually enough and saves most of the search
+    let optimistic = (result.gas_used() + refunded + CALL_STIPEND) * 64 / 63;
+    if optimistic < hi {
+        if estimator.succeeds(optimistic) {
+            hi = optimistic;
+        } else {
+            lo = optimistic;
+        }
+    }
+
+    while lo + 1 < hi {
+        let mid = lo + (hi - lo) / 2;
+        if estimator.succeeds(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    Ok(GasEstimate { gas: hi, runs: estimator.runs, failure: None })
+}
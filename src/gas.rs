@@ -0,0 +1,101 @@
+use revm::context::result::ExecutionResult;
+use revm::primitives::Address;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::abi::frame_address;
+
+/// The `gas` field of a trace frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameGas {
+    pub provided: u64,
+    pub used: u64,
+    pub self_used: u64,
+    pub children_used: u64,
+    pub stipend: u64,
+    pub refunded: i64,
+    pub memory_expansion: u64,
+    /// Only set on the top frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intrinsic: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameGasEntry {
+    /// Path of indexes into `calls` from the root frame.
+    pub frame: Vec<usize>,
+    /// Callee, or created contract.
+    pub address: Option<Address>,
+    pub gas: FrameGas,
+}
+
+/// Where the gas of a transaction went. `gas_used` is
+/// `intrinsic_gas + execution_gas - refund_applied`, unless the EIP-7623
+/// calldata floor is higher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasBreakdown {
+    pub gas_limit: u64,
+    pub intrinsic_gas: u64,
+    pub execution_gas: u64,
+    pub memory_expansion: u64,
+    /// Refund counter at the end of execution, before the cap.
+    pub refund_counter: i64,
+    pub refund_applied: u64,
+    pub floor_applied: bool,
+    pub gas_used: u64,
+    pub frames: Vec<FrameGasEntry>,
+}
+
+pub(crate) fn frame_gas(frame: &Value) -> Option<FrameGas> {
+    serde_json::from_value(frame["gas"].clone()).ok()
+}
+
+fn collect_frames(frame: &Value, path: &mut Vec<usize>, frames: &mut Vec<FrameGasEntry>) {
+    let address = if frame.get("isCreate").is_some() {
+        serde_json::from_value(frame["outcome"]["address"].clone()).ok()
+    } else {
+        frame_address(frame, "target_address")
+    };
+    frames.push(FrameGasEntry {
+        frame: path.clone(),
+        address,
+        gas: frame_gas(frame).unwrap_or_default(),
+    });
+    if let Some(calls) = frame["calls"].as_array() {
+        for (index, call) in calls.iter().enumerate() {
+            path.push(index);
+            collect_frames(call, path, frames);
+            path.pop();
+        }
+    }
+}
+
+/// Transaction level gas breakdown of a trace and the result it produced.
+pub fn gas_breakdown<HaltReasonTy>(
+    trace: &Value,
+    execution_result: &ExecutionResult<HaltReasonTy>
+) -> Result<GasBreakdown, String> {
+    let Some(root) = frame_gas(trace) else {
+        return Err(String::from("trace has no gas information"));
+    };
+    let intrinsic_gas = root.intrinsic.unwrap_or(0);
+    let mut frames = Vec::new();
+    collect_frames(trace, &mut Vec::new(), &mut frames);
+
+    let gas_used = execution_result.gas_used();
+    let spent = intrinsic_gas + root.used;
+    Ok(GasBreakdown {
+        gas_limit: intrinsic_gas + root.provided,
+        intrinsic_gas,
+        execution_gas: root.used,
+        memory_expansion: frames.iter().map(|entry| entry.gas.memory_expansion).sum(),
+        refund_counter: root.refunded,
+        refund_applied: spent.saturating_sub(gas_used),
+        floor_applied: gas_used > spent,
+        gas_used,
+        frames,
+    })
+}
@@ -1,36 +1,65 @@
-use revm::{context::ContextTr, interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter, InterpreterTypes}, primitives::{HashMap, Log}, Inspector};
+use revm::{context::{ContextTr, Transaction}, interpreter::{gas::CALL_STIPEND, CallInputs, CallOutcome, CreateInputs, CreateOutcome, Gas, Interpreter, InterpreterTypes}, primitives::{Address, Log, U256}, Inspector};
 use serde_json::{json, Value};
 
+use crate::revert::RevertDecoder;
+
 pub struct MyInspector<'a> {
-    pub gas_used: u64,
     pub call_count: usize,
     pub trace_stack: &'a mut Vec<(u64, Value)>,
-    logs_stack: Vec<Log>,
+    logs_stack: Vec<Vec<Log>>, // logs of each open frame
+    selfdestruct_stack: Vec<Option<Value>>, // SELFDESTRUCT of each open frame
     current_depth: u64,
+    intrinsic_gas: u64, // of the top frame
+    revert_decoder: RevertDecoder,
 }
 
 impl<'a> MyInspector<'a> {
     // Constructor function
     pub fn new(trace_stack: &'a mut Vec<(u64, Value)>) -> Self {
        Self{
-           gas_used: 0,
            call_count: 0,
            trace_stack,
-           logs_stack: Vec::<Log>::new(),
-           current_depth: 0
+           logs_stack: Vec::new(),
+           selfdestruct_stack: Vec::new(),
+           current_depth: 0,
+           intrinsic_gas: 0,
+           revert_decoder: RevertDecoder::default()
        }
     }
 }
+
+/// Gas of a frame. `selfUsed` excludes what the frame paid for its `calls`,
+/// which is what they spent minus the stipend they were given for free.
+fn frame_gas(gas: &Gas, calls: &[Value], stipend: u64) -> Value {
+    let (children_used, children_stipend) = calls.iter().fold((0u64, 0u64), |(used, stipend), call| {
+        (
+            used + call["gas"]["used"].as_u64().unwrap_or(0),
+            stipend + call["gas"]["stipend"].as_u64().unwrap_or(0),
+        )
+    });
+    json!({
+        "provided": gas.limit(),
+        "used": gas.spent(),
+        "selfUsed": (gas.spent() + children_stipend).saturating_sub(children_used),
+        "childrenUsed": children_used,
+        "stipend": stipend,
+        "refunded": gas.refunded(),
+        "memoryExpansion": gas.memory().expansion_cost
+    })
+}
+
 impl<'a, CTX: ContextTr, INTR: InterpreterTypes> Inspector<CTX, INTR> for MyInspector<'a> {
-    fn step(&mut self, interp: &mut Interpreter<INTR>, _context: &mut CTX) {
-        self.gas_used += interp.gas.spent();
-    }
     fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.current_depth == 0 {
+            self.intrinsic_gas = context.tx().gas_limit().saturating_sub(inputs.gas_limit);
+        }
         self.call_count += 1;
         self.current_depth += 1;
+        self.logs_stack.push(Vec::new());
+        self.selfdestruct_stack.push(None);
         None // Don't override the call
     }
-    fn create_end(&mut self, context: &mut CTX, inputs: &CreateInputs, outcome: &mut CreateOutcome) {
+    fn create_end(&mut self, _context: &mut CTX, inputs: &CreateInputs, outcome: &mut CreateOutcome) {
         let calls = &mut Vec::new();
 
         while let Some(stack_top) = self.trace_stack.pop(){
@@ -43,29 +72,45 @@ impl<'a, CTX: ContextTr, INTR: InterpreterTypes> Inspector<CTX, INTR> for MyInsp
             }
             else{
                 assert!(
-                    !(stack_top.0 > self.current_depth),
+                    stack_top.0 <= self.current_depth,
                     "Invalid trace stack. stack top depth: {}, current depth: {}",
                     stack_top.0, self.current_depth
                 );
             }
         }
+        // children were popped last first
+        calls.reverse();
 
-        let call_info = json!({
+        let mut call_info = json!({
             "isCreate": "true",
             "inputs": inputs,
             "outcome": outcome,
+            "gas": frame_gas(&outcome.result.gas, calls, 0),
             "calls": calls,
-            "logs": self.logs_stack
+            "logs": self.logs_stack.pop().unwrap_or_default()
         });
+        if self.current_depth == 1 {
+            call_info["gas"]["intrinsic"] = json!(self.intrinsic_gas);
+        }
+        if let Some(selfdestruct) = self.selfdestruct_stack.pop().flatten() {
+            call_info["selfdestruct"] = selfdestruct;
+        }
+        if outcome.result.result.is_revert() {
+            call_info["revertReason"] = json!(self.revert_decoder.decode(&outcome.result.output));
+        }
 
-        self.logs_stack.clear();
         self.trace_stack.push((self.current_depth, call_info));
         self.current_depth -= 1;
      }
    
-    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.current_depth == 0 {
+            self.intrinsic_gas = context.tx().gas_limit().saturating_sub(inputs.gas_limit);
+        }
         self.call_count += 1;
         self.current_depth += 1;
+        self.logs_stack.push(Vec::new());
+        self.selfdestruct_stack.push(None);
         None // Don't override the call
     }
      fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
@@ -81,26 +126,51 @@ impl<'a, CTX: ContextTr, INTR: InterpreterTypes> Inspector<CTX, INTR> for MyInsp
             }
             else{
                 assert!(
-                    !(stack_top.0 > self.current_depth),
+                    stack_top.0 <= self.current_depth,
                     "Invalid trace stack. stack top depth: {}, current depth: {}",
                     stack_top.0, self.current_depth
                 );
             }
         }
+        // children were popped last first
+        calls.reverse();
 
-        let call_info = json!({
+        // CALL and CALLCODE with value give the callee a stipend, the
+        // transaction value does not
+        let stipend = if self.current_depth > 1 && inputs.transfers_value() { CALL_STIPEND } else { 0 };
+        let mut call_info = json!({
             "inputs": inputs,
             "outcome": outcome,
             "input_bytes":inputs.input.bytes(context),
+            "gas": frame_gas(&outcome.result.gas, calls, stipend),
             "calls": calls,
-            "logs": self.logs_stack
+            "logs": self.logs_stack.pop().unwrap_or_default()
         });
+        if self.current_depth == 1 {
+            call_info["gas"]["intrinsic"] = json!(self.intrinsic_gas);
+        }
+        if let Some(selfdestruct) = self.selfdestruct_stack.pop().flatten() {
+            call_info["selfdestruct"] = selfdestruct;
+        }
+        if outcome.result.result.is_revert() {
+            call_info["revertReason"] = json!(self.revert_decoder.decode(&outcome.result.output));
+        }
 
-        self.logs_stack.clear();
         self.trace_stack.push((self.current_depth, call_info));
         self.current_depth -= 1;
      }
     fn log(&mut self, _interp: &mut Interpreter<INTR>, _ctx: &mut CTX, log: Log) {
-        self.logs_stack.push(log);
+        if let Some(logs) = self.logs_stack.last_mut() {
+            logs.push(log);
+        }
+    }
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if let Some(selfdestruct) = self.selfdestruct_stack.last_mut() {
+            *selfdestruct = Some(json!({
+                "contract": contract,
+                "target": target,
+                "value": value
+            }));
+        }
     }
 }
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+#[cfg(feature = "http")]
+use std::time::Duration;
+use std::{error::Error, fmt};
+
+use revm::database::CacheDB;
+use revm::database_interface::DBErrorMarker;
+use revm::primitives::{Address, Bytes, HashMap, StorageKey, StorageValue, B256, KECCAK_EMPTY, U256};
+use revm::primitives::alloy_primitives::U64;
+use revm::state::{AccountInfo, Bytecode};
+use revm::DatabaseRef;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::database::{create_database_from_prestate_trace, AccountDetails};
+use crate::json_rpc::{JsonRpcError, JsonRpcResponse};
+
+/// Sends one JSON-RPC request and returns the raw response object.
+pub trait JsonRpcTransport {
+    fn request(&self, method: &str, params: Value) -> Result<Value, String>;
+}
+
+impl<T: JsonRpcTransport + ?Sized> JsonRpcTransport for &T {
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        (**self).request(method, params)
+    }
+}
+
+/// Blocking HTTP transport, usable from inside `revm::Database` calls.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    url: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http")]
+impl HttpTransport {
+    pub fn new(url: &str) -> Self {
+        Self::with_timeout(url, Duration::from_secs(30))
+    }
+
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .build()
+            .into();
+        Self { url: url.to_string(), agent }
+    }
+}
+
+#[cfg(feature = "http")]
+impl JsonRpcTransport for HttpTransport {
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request_body = json!({
+            "jsonrpc":"2.0",
+            "method":method,
+            "params":params,
+            "id":1
+        });
+        self.agent
+            .post(&self.url)
+            .send_json(&request_body)
+            .map_err(|error| error.to_string())?
+            .body_mut()
+            .read_json::<Value>()
+            .map_err(|error| error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageProof {
+    pub key: U256,
+    pub value: U256,
+    pub proof: Vec<Bytes>,
+}
+
+/// `eth_getProof` result (EIP-1186).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: Address,
+    pub balance: U256,
+    pub nonce: U64,
+    pub code_hash: B256,
+    pub storage_hash: B256,
+    pub account_proof: Vec<Bytes>,
+    pub storage_proof: Vec<StorageProof>,
+}
+pub type GetProofResponse = JsonRpcResponse<AccountProof>;
+
+#[derive(Debug)]
+pub enum JsonRpcDatabaseError {
+    Transport(String),
+    Rpc(JsonRpcError),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for JsonRpcDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonRpcDatabaseError::Transport(error) => write!(f, "Transport error: {}", error),
+            JsonRpcDatabaseError::Rpc(error) => write!(f, "{}", error),
+            JsonRpcDatabaseError::InvalidResponse(error) => {
+                write!(f, "Invalid response: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for JsonRpcDatabaseError {}
+impl DBErrorMarker for JsonRpcDatabaseError {}
+
+impl From<JsonRpcDatabaseError> for String {
+    fn from(error: JsonRpcDatabaseError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Sends `method` over `transport` and decodes the result.
+pub(crate) fn request_result<T: JsonRpcTransport, R: DeserializeOwned>(
+    transport: &T,
+    method: &str,
+    params: Value
+) -> Result<R, JsonRpcDatabaseError> {
+    let response = transport.request(method, params)
+        .map_err(JsonRpcDatabaseError::Transport)?;
+    match serde_json::from_value::<JsonRpcResponse<R>>(response) {
+        Ok(JsonRpcResponse::Result(result)) => Ok(result.result),
+        Ok(JsonRpcResponse::Error(error)) => Err(JsonRpcDatabaseError::Rpc(error)),
+        Err(error) => Err(JsonRpcDatabaseError::InvalidResponse(
+            format!("{}: {}", method, error)
+        )),
+    }
+}
+
+/// Read-only state pinned at `block_number`, fetched from a JSON-RPC node on
+/// demand. Meant to sit under a [`CacheDB`] filled with the prestate so only
+/// what the prestate misses goes over the wire.
+#[derive(Debug)]
+pub struct JsonRpcDatabase<T: JsonRpcTransport> {
+    transport: T,
+    block_number: u64,
+    contracts: RefCell<HashMap<B256, Bytecode>>,
+}
+
+pub type LazyDatabase<T> = CacheDB<JsonRpcDatabase<T>>;
+
+impl<T: JsonRpcTransport> JsonRpcDatabase<T> {
+    pub fn new(transport: T, block_number: u64) -> Self {
+        Self { transport, block_number, contracts: RefCell::new(HashMap::default()) }
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
+    fn block_tag(&self) -> String {
+        format!("0x{:x}", self.block_number)
+    }
+
+    fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value
+    ) -> Result<R, JsonRpcDatabaseError> {
+        request_result(&self.transport, method, params)
+    }
+
+    pub fn get_proof(
+        &self,
+        address: Address,
+        keys: &[StorageKey]
+    ) -> Result<AccountProof, JsonRpcDatabaseError> {
+        let keys: Vec<B256> = keys.iter().map(|key| B256::from(*key)).collect();
+        self.call("eth_getProof", json!([address, keys, self.block_tag()]))
+    }
+
+    fn get_code(&self, address: Address) -> Result<Bytes, JsonRpcDatabaseError> {
+        self.call("eth_getCode", json!([address, self.block_tag()]))
+    }
+
+    fn cache_code(&self, code: Bytes) -> (B256, Option<Bytecode>) {
+        if code.is_empty() {
+            return (KECCAK_EMPTY, None);
+        }
+        let bytecode = Bytecode::new_raw(code);
+        let code_hash = bytecode.hash_slow();
+        self.contracts.borrow_mut().insert(code_hash, bytecode.clone());
+        (code_hash, Some(bytecode))
+    }
+}
+
+impl<T: JsonRpcTransport> DatabaseRef for JsonRpcDatabase<T> {
+    type Error = JsonRpcDatabaseError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        // eth_getProof answers balance, nonce and code hash in a single call,
+        // nodes that do not serve it get the three legacy calls instead
+        let (balance, nonce, code) = match self.get_proof(address, &[]) {
+            Ok(proof) => {
+                let code = if proof.code_hash == KECCAK_EMPTY || proof.code_hash.is_zero() {
+                    Bytes::new()
+                } else {
+                    self.get_code(address)?
+                };
+                (proof.balance, proof.nonce.to::<u64>(), code)
+            }
+            Err(JsonRpcDatabaseError::Rpc(_)) => {
+                let balance: U256 = self.call(
+                    "eth_getBalance", json!([address, self.block_tag()])
+                )?;
+                let nonce: U64 = self.call(
+                    "eth_getTransactionCount", json!([address, self.block_tag()])
+                )?;
+                (balance, nonce.to::<u64>(), self.get_code(address)?)
+            }
+            Err(error) => return Err(error),
+        };
+
+        if balance.is_zero() && nonce == 0 && code.is_empty() {
+            return Ok(None);
+        }
+        let (code_hash, code) = self.cache_code(code);
+        Ok(Some(AccountInfo { balance, nonce, code_hash, code }))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+        // code is always returned by basic_ref, so it has been seen already
+        match self.contracts.borrow().get(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => Err(JsonRpcDatabaseError::InvalidResponse(
+                format!("unknown code hash {}", code_hash)
+            )),
+        }
+    }
+
+    fn storage_ref(
+        &self,
+        address: Address,
+        index: StorageKey
+    ) -> Result<StorageValue, Self::Error> {
+        let value: B256 = self.call(
+            "eth_getStorageAt",
+            json!([address, B256::from(index), self.block_tag()])
+        )?;
+        Ok(value.into())
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        let block: Value = self.call(
+            "eth_getBlockByNumber", json!([format!("0x{:x}", number), false])
+        )?;
+        match block.get("hash").map(|hash| serde_json::from_value::<B256>(hash.clone())) {
+            Some(Ok(hash)) => Ok(hash),
+            _ => Err(JsonRpcDatabaseError::InvalidResponse(
+                format!("block {} has no hash", number)
+            )),
+        }
+    }
+}
+
+/// Builds a database from the prestate that falls back to `transport` for
+/// accounts, code and storage slots the prestate does not contain.
+pub fn create_lazy_database_from_prestate_trace<T: JsonRpcTransport>(
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    transport: T,
+    block_number: u64
+) -> LazyDatabase<T> {
+    create_database_from_prestate_trace(
+        prestate_tracer_result,
+        JsonRpcDatabase::new(transport, block_number)
+    )
+}
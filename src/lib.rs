@@ -1,5 +1,32 @@
 pub mod trace;
+pub mod abi;
+pub mod access_list;
+pub mod balance_changes;
+pub mod call;
 pub mod json_rpc;
 mod inspector;
 pub mod database;
+pub mod estimate_gas;
+pub mod gas;
 pub mod block;
+pub mod bundle;
+pub mod bundle_conflicts;
+pub mod lazy_database;
+pub mod mined;
+pub mod profiler;
+pub mod proof;
+pub mod raw_transaction;
+pub mod receipt;
+pub mod recording_database;
+pub mod replay;
+pub mod revert;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod signatures;
+pub mod simulate;
+pub mod storage_trace;
+pub mod transaction;
+pub mod transfers;
+mod trie;
+pub mod validation;
+pub mod witness;
@@ -0,0 +1,138 @@
+use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
+use op_revm::{OpHaltReason, OpSpecId};
+use revm::context::result::HaltReason;
+use revm::context::CfgEnv;
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::ruint::aliases::U64;
+use revm::primitives::{Address, Bytes, HashMap, B256};
+use serde_json::json;
+
+use crate::block::{create_block_env_for_spec, BlockDetails};
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::lazy_database::{request_result, JsonRpcTransport};
+use crate::trace::{inspect_mainnet_transaction, inspect_op_transaction, pop_trace_result, TraceOutput};
+use crate::transaction::{
+    create_op_transaction_from_transaction_details, create_tx_env_from_transaction_details,
+    TransactionDetails
+};
+
+/// `eth_getTransactionByHash`, failing for unknown and pending transactions.
+pub fn fetch_transaction<T: JsonRpcTransport>(
+    transport: &T,
+    transaction_hash: B256
+) -> Result<TransactionDetails, String> {
+    let transaction: Option<TransactionDetails> = request_result(
+        transport, "eth_getTransactionByHash", json!([transaction_hash])
+    )?;
+    let transaction = transaction.ok_or(format!("transaction {} not found", transaction_hash))?;
+    if transaction.block_hash.is_none() {
+        return Err(format!("transaction {} is not mined", transaction_hash));
+    }
+    Ok(transaction)
+}
+
+/// Header of the block `transaction` was mined in.
+pub fn fetch_transaction_block<T: JsonRpcTransport>(
+    transport: &T,
+    transaction: &TransactionDetails
+) -> Result<BlockDetails, String> {
+    let block_hash = transaction.block_hash
+        .ok_or(format!("transaction {} is not mined", transaction.hash))?;
+    let block: Option<BlockDetails> = request_result(
+        transport, "eth_getBlockByHash", json!([block_hash, false])
+    )?;
+    block.ok_or(format!("block {} not found", block_hash))
+}
+
+/// `debug_traceTransaction` with the `prestateTracer`: the state the
+/// transaction read, after the transactions before it in its block.
+pub fn fetch_transaction_prestate<T: JsonRpcTransport>(
+    transport: &T,
+    transaction_hash: B256
+) -> Result<HashMap<Address, AccountDetails>, String> {
+    let prestate = request_result(
+        transport, "debug_traceTransaction",
+        json!([transaction_hash, { "tracer": "prestateTracer" }])
+    )?;
+    Ok(prestate)
+}
+
+/// Replays a mined transaction in the environment of its block on its
+/// prestate, see [`fetch_transaction_prestate`].
+pub fn trace_mined_transaction(
+    chain_id: u64,
+    spec_id: SpecId,
+    transaction: &TransactionDetails,
+    block_details: &BlockDetails,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<TraceOutput<HaltReason>, String> {
+    let tx = create_tx_env_from_transaction_details(transaction)?;
+    let block_env = create_block_env_for_spec(block_details, spec_id)?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let cfg_env = CfgEnv::new_with_spec(spec_id).with_chain_id(chain_id);
+    let (result, state_diff, _) = inspect_mainnet_transaction(
+        cfg_env, block_env, db, tx, MyInspector::new(buffer)
+    )?;
+    Ok((result, state_diff, pop_trace_result(buffer)))
+}
+
+/// [`trace_mined_transaction`] on an OP Stack chain. `enveloped_tx` is the
+/// raw transaction, unused for deposits. The L1 data fee is only charged
+/// right when the prestate holds the L1Block contract storage.
+pub fn op_trace_mined_transaction(
+    chain_id: u64,
+    op_spec: OpSpecId,
+    transaction: &TransactionDetails,
+    enveloped_tx: Option<Bytes>,
+    block_details: &BlockDetails,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<TraceOutput<OpHaltReason>, String> {
+    let op_tx = create_op_transaction_from_transaction_details(transaction, enveloped_tx)?;
+    let block_env = create_block_env_for_spec(block_details, op_spec.into_eth_spec())?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let cfg_env = CfgEnv::new_with_spec(op_spec.into_eth_spec()).with_chain_id(chain_id);
+    let (result, state_diff) = inspect_op_transaction(
+        cfg_env, op_spec, block_env, db, op_tx, MyInspector::new(buffer)
+    )?;
+    Ok((result, state_diff, pop_trace_result(buffer)))
+}
+
+/// Fetches a mined transaction, its block and its prestate from the node
+/// behind `transport` and traces it locally.
+pub fn trace_transaction_by_hash<T: JsonRpcTransport>(
+    transport: &T,
+    chain_id: u64,
+    spec_id: SpecId,
+    transaction_hash: B256
+) -> Result<TraceOutput<HaltReason>, String> {
+    let transaction = fetch_transaction(transport, transaction_hash)?;
+    let block_details = fetch_transaction_block(transport, &transaction)?;
+    let prestate = fetch_transaction_prestate(transport, transaction_hash)?;
+    trace_mined_transaction(chain_id, spec_id, &transaction, &block_details, prestate)
+}
+
+/// [`trace_transaction_by_hash`] on an OP Stack chain.
+pub fn op_trace_transaction_by_hash<T: JsonRpcTransport>(
+    transport: &T,
+    chain_id: u64,
+    op_spec: OpSpecId,
+    transaction_hash: B256
+) -> Result<TraceOutput<OpHaltReason>, String> {
+    let transaction = fetch_transaction(transport, transaction_hash)?;
+    let enveloped_tx = match transaction.tx_type == U64::from(DEPOSIT_TRANSACTION_TYPE) {
+        true => None,
+        false => Some(request_result(
+            transport, "eth_getRawTransactionByHash", json!([transaction_hash])
+        )?),
+    };
+    let block_details = fetch_transaction_block(transport, &transaction)?;
+    let prestate = fetch_transaction_prestate(transport, transaction_hash)?;
+    op_trace_mined_transaction(
+        chain_id, op_spec, &transaction, enveloped_tx, &block_details, prestate
+    )
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use revm::bytecode::opcode::{self, OpCode};
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv, ContextTr, JournalTr, Transaction};
+use revm::interpreter::gas::CALL_STIPEND;
+use revm::interpreter::interpreter::EthInterpreter;
+use revm::interpreter::interpreter_types::Jumps;
+use revm::interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter};
+use revm::primitives::{hex, Address, Bytes, HashMap, U256};
+use revm::Inspector;
+use serde::Serialize;
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::trace::{inspect_mainnet_transaction, tx_env_build_error_to_string, tx_env_builder};
+
+/// Stack entry for the gas charged before execution starts.
+pub const INTRINSIC_FRAME: &str = "[intrinsic]";
+
+struct ProfiledFrame {
+    path: String,
+    // opcode being executed and the gas left before it
+    step: Option<(u8, u64)>,
+    // CALL or CREATE whose cost still contains the gas it forwards
+    pending_call: Option<(u8, u64)>,
+}
+
+/// Inspector aggregating the gas spent by each opcode under its call path.
+/// Frames are labelled `address:selector` with the address of the code
+/// that runs (the library of a DELEGATECALL), or `address:create` for
+/// contract creations. Gas is counted when spent, refunds are not
+/// subtracted.
+#[derive(Default)]
+pub struct GasProfiler {
+    frames: Vec<ProfiledFrame>,
+    // (call path, opcode) -> (gas, count)
+    samples: BTreeMap<(String, u8), (u64, u64)>,
+    intrinsic_gas: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallPathGas {
+    pub path: String,
+    /// Gas spent by the opcodes of the frame itself.
+    pub self_gas: u64,
+    /// Gas of the frame and every frame below it.
+    pub total_gas: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpcodeGas {
+    pub opcode: String,
+    pub gas: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasProfileSummary {
+    pub intrinsic_gas: u64,
+    pub execution_gas: u64,
+    pub top_call_paths: Vec<CallPathGas>,
+    pub top_opcodes: Vec<OpcodeGas>,
+}
+
+fn opcode_name(opcode: u8) -> String {
+    match OpCode::new(opcode) {
+        Some(opcode) => opcode.as_str().to_string(),
+        None => format!("0x{:02x}", opcode),
+    }
+}
+
+fn frame_label(address: Address, input: &[u8]) -> String {
+    match input.get(..4) {
+        Some(selector) => format!("{}:{}", address, hex::encode_prefixed(selector)),
+        None => format!("{}:fallback", address),
+    }
+}
+
+impl GasProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&mut self, path: String, opcode: u8, gas: u64) {
+        let sample = self.samples.entry((path, opcode)).or_default();
+        sample.0 += gas;
+        sample.1 += 1;
+    }
+
+    fn flush_pending_call(&mut self, forwarded: u64) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        if let Some((opcode, cost)) = frame.pending_call.take() {
+            let path = frame.path.clone();
+            self.record(path, opcode, cost.saturating_sub(forwarded));
+        }
+    }
+
+    fn enter(&mut self, context_gas_limit: u64, gas_limit: u64, forwarded: u64, label: String) {
+        if self.frames.is_empty() {
+            self.intrinsic_gas = context_gas_limit.saturating_sub(gas_limit);
+        }
+        self.flush_pending_call(forwarded);
+        let path = match self.frames.last() {
+            Some(parent) => format!("{};{}", parent.path, label),
+            None => label,
+        };
+        self.frames.push(ProfiledFrame { path, step: None, pending_call: None });
+    }
+
+    fn exit(&mut self) {
+        // a CALL or CREATE that failed before starting a frame
+        self.flush_pending_call(0);
+        self.frames.pop();
+    }
+
+    /// Folded stacks, one `path;OPCODE gas` line per call path and opcode,
+    /// as read by `flamegraph.pl` and `inferno-flamegraph`.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines = Vec::with_capacity(self.samples.len() + 1);
+        if self.intrinsic_gas > 0 {
+            lines.push(format!("{} {}", INTRINSIC_FRAME, self.intrinsic_gas));
+        }
+        for ((path, opcode), (gas, _)) in self.samples.iter() {
+            if *gas > 0 {
+                lines.push(format!("{};{} {}", path, opcode_name(*opcode), gas));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Gas by call path, with the gas of every sub path added to `total_gas`.
+    pub fn call_paths(&self) -> Vec<CallPathGas> {
+        let mut self_gas: BTreeMap<&str, u64> = BTreeMap::new();
+        for ((path, _), (gas, _)) in self.samples.iter() {
+            *self_gas.entry(path).or_default() += gas;
+        }
+        self_gas.iter().map(|(path, gas)| {
+            let prefix = format!("{};", path);
+            let below: u64 = self_gas.iter()
+                .filter(|(other, _)| other.starts_with(&prefix))
+                .map(|(_, gas)| gas)
+                .sum();
+            CallPathGas { path: path.to_string(), self_gas: *gas, total_gas: gas + below }
+        }).collect()
+    }
+
+    pub fn opcodes(&self) -> Vec<OpcodeGas> {
+        let mut opcodes: BTreeMap<u8, (u64, u64)> = BTreeMap::new();
+        for ((_, opcode), (gas, count)) in self.samples.iter() {
+            let entry = opcodes.entry(*opcode).or_default();
+            entry.0 += gas;
+            entry.1 += count;
+        }
+        opcodes.into_iter()
+            .map(|(opcode, (gas, count))| OpcodeGas { opcode: opcode_name(opcode), gas, count })
+            .collect()
+    }
+
+    /// The `top` call paths by self gas and opcodes by gas.
+    pub fn summary(&self, top: usize) -> GasProfileSummary {
+        let mut top_call_paths = self.call_paths();
+        top_call_paths.sort_by_key(|path| std::cmp::Reverse(path.self_gas));
+        top_call_paths.truncate(top);
+        let mut top_opcodes = self.opcodes();
+        top_opcodes.sort_by_key(|opcode| std::cmp::Reverse(opcode.gas));
+        top_opcodes.truncate(top);
+        GasProfileSummary {
+            intrinsic_gas: self.intrinsic_gas,
+            execution_gas: self.samples.values().map(|(gas, _)| gas).sum(),
+            top_call_paths,
+            top_opcodes,
+        }
+    }
+}
+
+impl<CTX: ContextTr> Inspector<CTX, EthInterpreter> for GasProfiler {
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        self.flush_pending_call(0);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.step = Some((interp.bytecode.opcode(), interp.gas.remaining()));
+        }
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        let Some((opcode, gas_before)) = frame.step.take() else {
+            return;
+        };
+        let cost = gas_before.saturating_sub(interp.gas.remaining());
+        if matches!(
+            opcode,
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL
+                | opcode::CREATE | opcode::CREATE2
+        ) {
+            frame.pending_call = Some((opcode, cost));
+        } else {
+            let path = frame.path.clone();
+            self.record(path, opcode, cost);
+        }
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let stipend = if !self.frames.is_empty() && inputs.transfers_value() { CALL_STIPEND } else { 0 };
+        let label = frame_label(inputs.bytecode_address, &inputs.input.bytes(context));
+        self.enter(
+            context.tx().gas_limit(), inputs.gas_limit, inputs.gas_limit.saturating_sub(stipend), label
+        );
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, _outcome: &mut CallOutcome) {
+        self.exit();
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        // the caller nonce is only bumped once the frame starts
+        let label = match context.journal_mut().load_account(inputs.caller) {
+            Ok(caller) => format!("{}:create", inputs.created_address(caller.info.nonce)),
+            Err(_) => String::from("create"),
+        };
+        self.enter(context.tx().gas_limit(), inputs.gas_limit, inputs.gas_limit, label);
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut CTX, _inputs: &CreateInputs, _outcome: &mut CreateOutcome) {
+        self.exit();
+    }
+}
+
+/// Runs the transaction under a [`GasProfiler`].
+#[allow(clippy::too_many_arguments)]
+pub fn profile_transaction(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<(ExecutionResult<HaltReason>, GasProfiler), String> {
+    let tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value, gas_limit, gas_price, gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+    let mut profiler = GasProfiler::new();
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, _, _) = inspect_mainnet_transaction(
+        cfg_env, latest_block_env, db, tx, &mut profiler
+    )?;
+    Ok((result, profiler))
+}
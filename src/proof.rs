@@ -0,0 +1,187 @@
+use alloy_rlp::Decodable;
+use revm::database::InMemoryDB;
+use revm::primitives::{keccak256, Address, HashMap, B256, KECCAK_EMPTY, U256};
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::lazy_database::{AccountProof, JsonRpcDatabase, JsonRpcTransport};
+use crate::trie::{rlp_list_items, verify_proof, EMPTY_ROOT_HASH};
+
+/// Account fields as stored in the state trie leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: B256,
+    pub code_hash: B256,
+}
+
+impl Default for TrieAccount {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            balance: U256::ZERO,
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        }
+    }
+}
+
+impl TrieAccount {
+    pub fn decode_rlp(data: &[u8]) -> Result<Self, String> {
+        let items = rlp_list_items(data)?;
+        if items.len() != 4 {
+            return Err(format!("account leaf has {} fields, expected 4", items.len()));
+        }
+        let error = |error: alloy_rlp::Error| error.to_string();
+        Ok(Self {
+            nonce: u64::decode(&mut &items[0][..]).map_err(error)?,
+            balance: U256::decode(&mut &items[1][..]).map_err(error)?,
+            storage_root: B256::decode(&mut &items[2][..]).map_err(error)?,
+            code_hash: B256::decode(&mut &items[3][..]).map_err(error)?,
+        })
+    }
+}
+
+/// Verifies `account_proof` against `state_root` and returns the proven
+/// account, `TrieAccount::default()` for a proof of absence.
+pub fn verify_account_proof(
+    state_root: B256,
+    account_proof: &AccountProof
+) -> Result<TrieAccount, String> {
+    let address = account_proof.address;
+    let leaf = verify_proof(state_root, address.as_slice(), &account_proof.account_proof)
+        .map_err(|error| format!("account {}: {}", address, error))?;
+    let account = match leaf {
+        Some(leaf) => TrieAccount::decode_rlp(&leaf)
+            .map_err(|error| format!("account {}: {}", address, error))?,
+        None => TrieAccount::default(),
+    };
+
+    // nodes report missing accounts with zero hashes
+    let claimed_code_hash = if account_proof.code_hash.is_zero() {
+        KECCAK_EMPTY
+    } else {
+        account_proof.code_hash
+    };
+    let claimed_storage_root = if account_proof.storage_hash.is_zero() {
+        EMPTY_ROOT_HASH
+    } else {
+        account_proof.storage_hash
+    };
+    if account.balance != account_proof.balance
+        || account.nonce != account_proof.nonce.to::<u64>()
+        || account.code_hash != claimed_code_hash
+        || account.storage_root != claimed_storage_root {
+        return Err(format!("account {}: proof fields do not match the proven leaf", address));
+    }
+    Ok(account)
+}
+
+/// Verifies a storage slot against the proven `storage_root` of its account
+/// and returns its value, zero for a proof of absence.
+pub fn verify_storage_proof(
+    storage_root: B256,
+    account_proof: &AccountProof,
+    slot: U256
+) -> Result<U256, String> {
+    let address = account_proof.address;
+    let Some(storage_proof) = account_proof.storage_proof.iter().find(|proof| proof.key == slot) else {
+        return Err(format!("account {}: no proof for storage slot {}", address, slot));
+    };
+    let slot_key = B256::from(slot);
+    let leaf = verify_proof(storage_root, slot_key.as_slice(), &storage_proof.proof)
+        .map_err(|error| format!("account {} slot {}: {}", address, slot, error))?;
+    let value = match leaf {
+        Some(leaf) => U256::decode(&mut &leaf[..]).map_err(|error| error.to_string())?,
+        None => U256::ZERO,
+    };
+    if value != storage_proof.value {
+        return Err(format!(
+            "account {} slot {}: proof value does not match the proven leaf", address, slot
+        ));
+    }
+    Ok(value)
+}
+
+/// Checks every account (balance, nonce, code hash, storage root) and every
+/// storage slot of `prestate_tracer_result` against `state_root` using the
+/// EIP-1186 proofs in `proofs`.
+pub fn verify_prestate_trace(
+    state_root: B256,
+    prestate_tracer_result: &HashMap<Address, AccountDetails>,
+    proofs: &HashMap<Address, AccountProof>
+) -> Result<(), String> {
+    for (address, account_details) in prestate_tracer_result.iter() {
+        let Some(account_proof) = proofs.get(address) else {
+            return Err(format!("account {}: no proof", address));
+        };
+        if account_proof.address != *address {
+            return Err(format!("account {}: proof is for {}", address, account_proof.address));
+        }
+        let account = verify_account_proof(state_root, account_proof)?;
+
+        let balance = account_details.balance.unwrap_or(U256::ZERO);
+        if balance != account.balance {
+            return Err(format!(
+                "account {}: balance {} but proven {}", address, balance, account.balance
+            ));
+        }
+        let nonce = account_details.nonce.unwrap_or(0);
+        if nonce != account.nonce {
+            return Err(format!(
+                "account {}: nonce {} but proven {}", address, nonce, account.nonce
+            ));
+        }
+        let code_hash = match &account_details.code {
+            Some(code) if !code.is_empty() => keccak256(code),
+            _ => KECCAK_EMPTY,
+        };
+        if code_hash != account.code_hash {
+            return Err(format!(
+                "account {}: code hash {} but proven {}", address, code_hash, account.code_hash
+            ));
+        }
+
+        if let Some(storage) = &account_details.storage {
+            for (slot, value) in storage.iter() {
+                let proven = verify_storage_proof(account.storage_root, account_proof, *slot)?;
+                if proven != *value {
+                    return Err(format!(
+                        "account {} slot {}: value {} but proven {}", address, slot, value, proven
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// [`create_in_memory_database_from_prestate_trace`] that only builds the
+/// database once [`verify_prestate_trace`] succeeded.
+pub fn create_verified_in_memory_database_from_prestate_trace(
+    state_root: B256,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    proofs: &HashMap<Address, AccountProof>
+) -> Result<InMemoryDB, String> {
+    verify_prestate_trace(state_root, &prestate_tracer_result, proofs)?;
+    Ok(create_in_memory_database_from_prestate_trace(prestate_tracer_result))
+}
+
+/// Fetches with `eth_getProof` the proofs of every account and storage slot
+/// of the prestate at `block_number`.
+pub fn fetch_prestate_proofs<T: JsonRpcTransport>(
+    transport: T,
+    block_number: u64,
+    prestate_tracer_result: &HashMap<Address, AccountDetails>
+) -> Result<HashMap<Address, AccountProof>, String> {
+    let rpc = JsonRpcDatabase::new(transport, block_number);
+    let mut proofs = HashMap::default();
+    for (address, account_details) in prestate_tracer_result.iter() {
+        let slots: Vec<U256> = account_details.storage.as_ref()
+            .map(|storage| storage.keys().copied().collect())
+            .unwrap_or_default();
+        let proof = rpc.get_proof(*address, &slots).map_err(|error| error.to_string())?;
+        proofs.insert(*address, proof);
+    }
+    Ok(proofs)
+}
@@ -0,0 +1,277 @@
+use alloy_rlp::{Decodable, Encodable, Header};
+use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
+use op_revm::{OpHaltReason, OpSpecId};
+use revm::context::result::HaltReason;
+use revm::context::transaction::{AccessList, SignedAuthorization};
+use revm::context::{BlockEnv, CfgEnv};
+use revm::precompile::secp256k1::ecrecover;
+use revm::primitives::alloy_primitives::B512;
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::primitives::{keccak256, uint, Address, Bytes, HashMap, B256, U256};
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::trace::{inspect_mainnet_transaction, inspect_op_transaction, pop_trace_result, TraceOutput};
+use crate::transaction::{
+    create_op_transaction_from_transaction_details, create_tx_env_from_transaction_details,
+    TransactionDetails
+};
+use crate::trie::{rlp_list_items, rlp_string};
+
+/// Signatures with a larger `s` are rejected since EIP-2.
+const SECP256K1N_HALF: U256 =
+    uint!(0x7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF5D576E7357A4501DDFE92F46681B20A0_U256);
+
+fn decode_field<T: Decodable>(item: &[u8], name: &str) -> Result<T, String> {
+    T::decode(&mut &item[..]).map_err(|error| format!("{}: {}", name, error))
+}
+
+/// `None` for the empty string of a contract creation.
+fn decode_to(item: &[u8]) -> Result<Option<Address>, String> {
+    match rlp_string(item)? {
+        [] => Ok(None),
+        to if to.len() == 20 => Ok(Some(Address::from_slice(to))),
+        to => Err(format!("to: {} bytes", to.len())),
+    }
+}
+
+/// `prefix` followed by the RLP list of the already encoded `items`.
+fn encode_list(prefix: Option<u8>, items: &[&[u8]]) -> Vec<u8> {
+    let payload_length = items.iter().map(|item| item.len()).sum();
+    let mut out = Vec::with_capacity(payload_length + 10);
+    out.extend(prefix);
+    Header { list: true, payload_length }.encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+fn recover_sender(signing_hash: B256, y_parity: u64, r: U256, s: U256) -> Result<Address, String> {
+    if y_parity > 1 {
+        return Err(format!("invalid signature parity {}", y_parity));
+    }
+    if s > SECP256K1N_HALF {
+        return Err(String::from("signature s value too high"));
+    }
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&r.to_be_bytes::<32>());
+    signature[32..].copy_from_slice(&s.to_be_bytes::<32>());
+    let recovered = ecrecover(&B512::from(signature), y_parity as u8, &signing_hash)
+        .map_err(|_| String::from("invalid signature"))?;
+    Ok(Address::from_slice(&recovered[12..]))
+}
+
+fn empty_transaction(hash: B256, tx_type: u8) -> TransactionDetails {
+    TransactionDetails {
+        hash,
+        tx_type: U64::from(tx_type),
+        from: Address::ZERO,
+        to: None,
+        input: Bytes::new(),
+        value: U256::ZERO,
+        gas: U64::ZERO,
+        nonce: U64::ZERO,
+        chain_id: None,
+        gas_price: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        access_list: None,
+        max_fee_per_blob_gas: None,
+        blob_versioned_hashes: None,
+        authorization_list: None,
+        source_hash: None,
+        mint: None,
+        is_system_tx: None,
+        block_hash: None,
+        block_number: None,
+        transaction_index: None,
+    }
+}
+
+fn decode_legacy(raw: &[u8]) -> Result<TransactionDetails, String> {
+    let items = rlp_list_items(raw)?;
+    if items.len() != 9 {
+        return Err(format!("legacy transaction has {} fields", items.len()));
+    }
+    let mut transaction = empty_transaction(keccak256(raw), 0);
+    transaction.nonce = decode_field(items[0], "nonce")?;
+    transaction.gas_price = Some(decode_field(items[1], "gas price")?);
+    transaction.gas = decode_field(items[2], "gas")?;
+    transaction.to = decode_to(items[3])?;
+    transaction.value = decode_field(items[4], "value")?;
+    transaction.input = decode_field(items[5], "data")?;
+    let v: u64 = decode_field(items[6], "v")?;
+
+    // EIP-155 signs the chain id too, with two empty fields
+    let (chain_id, y_parity) = match v {
+        27 | 28 => (None, v - 27),
+        v if v >= 35 => (Some((v - 35) / 2), (v - 35) % 2),
+        v => return Err(format!("invalid signature v {}", v)),
+    };
+    let signing_payload = match chain_id {
+        Some(chain_id) => {
+            let mut chain_id_rlp = Vec::new();
+            chain_id.encode(&mut chain_id_rlp);
+            let empty: &[u8] = &[alloy_rlp::EMPTY_STRING_CODE];
+            let mut fields = items[..6].to_vec();
+            fields.extend([&chain_id_rlp[..], empty, empty]);
+            encode_list(None, &fields)
+        }
+        None => encode_list(None, &items[..6]),
+    };
+    transaction.chain_id = chain_id.map(U64::from);
+    transaction.from = recover_sender(
+        keccak256(signing_payload), y_parity,
+        decode_field(items[7], "r")?, decode_field(items[8], "s")?
+    )?;
+    Ok(transaction)
+}
+
+fn decode_deposit(raw: &[u8]) -> Result<TransactionDetails, String> {
+    let items = rlp_list_items(&raw[1..])?;
+    if items.len() != 8 {
+        return Err(format!("deposit transaction has {} fields", items.len()));
+    }
+    let mut transaction = empty_transaction(keccak256(raw), DEPOSIT_TRANSACTION_TYPE);
+    transaction.source_hash = Some(decode_field(items[0], "source hash")?);
+    transaction.from = decode_field(items[1], "from")?;
+    transaction.to = decode_to(items[2])?;
+    transaction.mint = Some(decode_field(items[3], "mint")?);
+    transaction.value = decode_field(items[4], "value")?;
+    transaction.gas = decode_field(items[5], "gas")?;
+    transaction.is_system_tx = Some(decode_field(items[6], "is system transaction")?);
+    transaction.input = decode_field(items[7], "data")?;
+    Ok(transaction)
+}
+
+/// EIP-2930, EIP-1559, EIP-4844 and EIP-7702 transactions: the fields
+/// before the access list differ only by the fee fields, the signature is
+/// always last. Returns the transaction with its canonical encoding, which
+/// leaves the EIP-4844 sidecar out.
+fn decode_typed(tx_type: u8, raw: &[u8]) -> Result<(TransactionDetails, Vec<u8>), String> {
+    let mut items = rlp_list_items(&raw[1..])?;
+    let mut canonical = raw.to_vec();
+    // EIP-4844 network form: [transaction, blobs, commitments, proofs]
+    if tx_type == 3 && items.first().is_some_and(|item| item.first().is_some_and(|byte| *byte >= 0xc0)) {
+        let body = items[0];
+        canonical = [&[tx_type][..], body].concat();
+        items = rlp_list_items(body)?;
+    }
+    let signed_fields = match tx_type {
+        1 => 8,
+        2 => 9,
+        3 => 11,
+        4 => 10,
+        tx_type => return Err(format!("unsupported transaction type {}", tx_type)),
+    };
+    if items.len() != signed_fields + 3 {
+        return Err(format!("type {} transaction has {} fields", tx_type, items.len()));
+    }
+
+    let mut transaction = empty_transaction(keccak256(&canonical), tx_type);
+    transaction.chain_id = Some(decode_field(items[0], "chain id")?);
+    transaction.nonce = decode_field(items[1], "nonce")?;
+    let fee_fields = match tx_type {
+        1 => {
+            transaction.gas_price = Some(decode_field(items[2], "gas price")?);
+            1
+        }
+        _ => {
+            transaction.max_priority_fee_per_gas = Some(decode_field(items[2], "max priority fee")?);
+            transaction.max_fee_per_gas = Some(decode_field(items[3], "max fee")?);
+            2
+        }
+    };
+    let fields = &items[2 + fee_fields..];
+    transaction.gas = decode_field(fields[0], "gas")?;
+    transaction.to = decode_to(fields[1])?;
+    // blob and set code transactions cannot create contracts
+    if transaction.to.is_none() && (tx_type == 3 || tx_type == 4) {
+        return Err(format!("type {} transaction without recipient", tx_type));
+    }
+    transaction.value = decode_field(fields[2], "value")?;
+    transaction.input = decode_field(fields[3], "data")?;
+    transaction.access_list = Some(decode_field::<AccessList>(fields[4], "access list")?);
+    match tx_type {
+        3 => {
+            transaction.max_fee_per_blob_gas = Some(decode_field::<U128>(fields[5], "max fee per blob gas")?);
+            transaction.blob_versioned_hashes = Some(decode_field(fields[6], "blob versioned hashes")?);
+        }
+        4 => {
+            transaction.authorization_list =
+                Some(decode_field::<Vec<SignedAuthorization>>(fields[5], "authorization list")?);
+        }
+        _ => {}
+    }
+
+    let signing_payload = encode_list(Some(tx_type), &items[..signed_fields]);
+    transaction.from = recover_sender(
+        keccak256(signing_payload),
+        decode_field(items[signed_fields], "y parity")?,
+        decode_field(items[signed_fields + 1], "r")?,
+        decode_field(items[signed_fields + 2], "s")?
+    )?;
+    Ok((transaction, canonical))
+}
+
+/// Decodes an EIP-2718 transaction as sent to `eth_sendRawTransaction`
+/// and recovers its sender. EIP-4844 transactions are accepted with or
+/// without their blob sidecar, which is not checked against the versioned
+/// hashes; the hash is the one of the transaction without it.
+pub fn decode_raw_transaction(raw: &[u8]) -> Result<TransactionDetails, String> {
+    decode_canonical(raw).map(|(transaction, _)| transaction)
+}
+
+/// [`decode_raw_transaction`] with the canonical encoding of the
+/// transaction, `raw` without any blob sidecar.
+fn decode_canonical(raw: &[u8]) -> Result<(TransactionDetails, Vec<u8>), String> {
+    match raw.first() {
+        None => Err(String::from("empty transaction")),
+        Some(byte) if *byte >= 0xc0 => Ok((decode_legacy(raw)?, raw.to_vec())),
+        Some(&DEPOSIT_TRANSACTION_TYPE) => Ok((decode_deposit(raw)?, raw.to_vec())),
+        Some(tx_type) => decode_typed(*tx_type, raw),
+    }
+}
+
+/// [`trace_transaction`](crate::trace::trace_transaction) of a raw signed
+/// transaction, see [`decode_raw_transaction`].
+pub fn trace_raw_transaction(
+    chain_id: u64,
+    raw: &[u8],
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<TraceOutput<HaltReason>, String> {
+    let transaction = decode_raw_transaction(raw)?;
+    let tx = create_tx_env_from_transaction_details(&transaction)?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, state_diff, _) = inspect_mainnet_transaction(
+        cfg_env, latest_block_env, db, tx, MyInspector::new(buffer)
+    )?;
+    Ok((result, state_diff, pop_trace_result(buffer)))
+}
+
+/// [`op_trace_transaction`](crate::trace::op_trace_transaction) of a raw
+/// signed transaction, whose canonical bytes are used for the L1 data fee.
+pub fn op_trace_raw_transaction(
+    chain_id: u64,
+    raw: &[u8],
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<TraceOutput<OpHaltReason>, String> {
+    let (transaction, canonical) = decode_canonical(raw)?;
+    let op_tx = create_op_transaction_from_transaction_details(
+        &transaction, Some(Bytes::from(canonical))
+    )?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, state_diff) = inspect_op_transaction(
+        cfg_env, OpSpecId::default(), latest_block_env, db, op_tx, MyInspector::new(buffer)
+    )?;
+    Ok((result, state_diff, pop_trace_result(buffer)))
+}
@@ -0,0 +1,147 @@
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{Transaction, TxEnv};
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::primitives::alloy_primitives::Bloom;
+use revm::primitives::{Address, Bytes, TxKind, B256};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::json_rpc::JsonRpcResponse;
+use crate::lazy_database::{request_result, JsonRpcTransport};
+
+/// Log of a receipt. `log_index` counts the logs of the whole block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+    pub log_index: U64,
+    pub transaction_index: U64,
+}
+
+/// Transaction receipt in the `eth_getTransactionReceipt` format, so that
+/// computed receipts and RPC receipts can be compared field by field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    pub transaction_hash: B256,
+    pub transaction_index: U64,
+    #[serde(rename = "type")]
+    pub tx_type: U64,
+    /// `0x1` on success, `0x0` otherwise.
+    pub status: U64,
+    pub gas_used: U64,
+    pub cumulative_gas_used: U64,
+    pub logs: Vec<ReceiptLog>,
+    pub logs_bloom: Bloom,
+    pub contract_address: Option<Address>,
+    pub effective_gas_price: U128,
+}
+pub type GetTransactionReceiptResponse = JsonRpcResponse<Receipt>;
+
+/// A field whose value differs, `path` as in `logs[0].data`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptMismatch {
+    pub path: String,
+    pub local: Value,
+    pub rpc: Value,
+}
+
+/// Receipt of the `transaction_index`-th transaction of a block, whose first
+/// log is the `first_log_index`-th of the block. The contract address is
+/// set for every creation, even a failed one, as nodes do.
+pub fn create_receipt(
+    transaction_hash: B256,
+    transaction_index: u64,
+    tx: &TxEnv,
+    result: &ExecutionResult<HaltReason>,
+    cumulative_gas_used: u64,
+    first_log_index: u64,
+    base_fee: u64
+) -> Receipt {
+    let mut logs_bloom = Bloom::default();
+    let logs = result.logs().iter().enumerate().map(|(index, log)| {
+        logs_bloom.accrue_log(log);
+        ReceiptLog {
+            address: log.address,
+            topics: log.topics().to_vec(),
+            data: log.data.data.clone(),
+            log_index: U64::from(first_log_index + index as u64),
+            transaction_index: U64::from(transaction_index),
+        }
+    }).collect();
+    let contract_address = match tx.kind {
+        TxKind::Create => Some(tx.caller.create(tx.nonce)),
+        TxKind::Call(_) => None,
+    };
+    Receipt {
+        transaction_hash,
+        transaction_index: U64::from(transaction_index),
+        tx_type: U64::from(tx.tx_type),
+        status: U64::from(result.is_success() as u64),
+        gas_used: U64::from(result.gas_used()),
+        cumulative_gas_used: U64::from(cumulative_gas_used),
+        logs,
+        logs_bloom,
+        contract_address,
+        effective_gas_price: U128::from(tx.effective_gas_price(base_fee as u128)),
+    }
+}
+
+fn diff_values(path: String, local: &Value, rpc: &Value, mismatches: &mut Vec<ReceiptMismatch>) {
+    match (local, rpc) {
+        (Value::Object(local_fields), Value::Object(rpc_fields)) => {
+            for (key, local_value) in local_fields {
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                let rpc_value = rpc_fields.get(key).unwrap_or(&Value::Null);
+                diff_values(path, local_value, rpc_value, mismatches);
+            }
+        }
+        (Value::Array(local_items), Value::Array(rpc_items))
+            if local_items.len() == rpc_items.len() => {
+            for (index, (local_item, rpc_item)) in local_items.iter().zip(rpc_items).enumerate() {
+                diff_values(format!("{}[{}]", path, index), local_item, rpc_item, mismatches);
+            }
+        }
+        _ if local != rpc => mismatches.push(ReceiptMismatch {
+            path,
+            local: local.clone(),
+            rpc: rpc.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Every field of `local` that differs from `rpc`. Logs are compared one
+/// by one when both receipts have as many, as a whole otherwise.
+pub fn compare_receipts(local: &Receipt, rpc: &Receipt) -> Vec<ReceiptMismatch> {
+    let mut mismatches = Vec::new();
+    diff_values(String::new(), &json!(local), &json!(rpc), &mut mismatches);
+    mismatches
+}
+
+pub fn fetch_transaction_receipt<T: JsonRpcTransport>(
+    transport: &T,
+    transaction_hash: B256
+) -> Result<Receipt, String> {
+    let receipt: Option<Receipt> = request_result(
+        transport, "eth_getTransactionReceipt", json!([transaction_hash])
+    )?;
+    receipt.ok_or(format!("no receipt for transaction {}", transaction_hash))
+}
+
+/// Compares `local` with the receipt the node returns for its transaction.
+/// Any mismatch means the prestate or the environment used for the local
+/// execution is not the one of the chain.
+pub fn verify_receipt<T: JsonRpcTransport>(
+    transport: &T,
+    local: &Receipt
+) -> Result<Vec<ReceiptMismatch>, String> {
+    let rpc = fetch_transaction_receipt(transport, local.transaction_hash)?;
+    Ok(compare_receipts(local, &rpc))
+}
@@ -0,0 +1,105 @@
+use revm::primitives::{Address, HashMap, StorageKey, StorageValue, B256, KECCAK_EMPTY};
+use revm::state::{Account, AccountInfo, Bytecode};
+use revm::{Database, DatabaseCommit};
+
+use crate::database::AccountDetails;
+
+/// Wraps a database and remembers the first value of every account, code and
+/// storage slot read through it, which is the state before execution. After a
+/// run, [`RecordingDatabase::prestate`] returns the minimal prestate (geth
+/// `prestateTracer` non-diff format) needed to replay it.
+#[derive(Debug)]
+pub struct RecordingDatabase<DB> {
+    pub db: DB,
+    accounts: HashMap<Address, Option<AccountInfo>>,
+    storage: HashMap<Address, HashMap<StorageKey, StorageValue>>,
+    codes: HashMap<B256, Bytecode>,
+}
+
+impl<DB> RecordingDatabase<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            accounts: HashMap::default(),
+            storage: HashMap::default(),
+            codes: HashMap::default(),
+        }
+    }
+
+    pub fn prestate(&self) -> HashMap<Address, AccountDetails> {
+        let mut prestate: HashMap<Address, AccountDetails> = HashMap::default();
+        for (address, info) in self.accounts.iter() {
+            let mut account_details = AccountDetails::default();
+            match info {
+                Some(info) => {
+                    account_details.balance = Some(info.balance);
+                    // geth omits zero nonces and empty code
+                    if info.nonce != 0 {
+                        account_details.nonce = Some(info.nonce);
+                    }
+                    let code = match &info.code {
+                        Some(code) => Some(code.original_bytes()),
+                        None => self.codes.get(&info.code_hash)
+                            .map(|code| code.original_bytes()),
+                    };
+                    account_details.code = code.filter(|code| !code.is_empty());
+                }
+                None => {
+                    account_details.balance = Some(Default::default());
+                }
+            }
+            prestate.insert(*address, account_details);
+        }
+        for (address, slots) in self.storage.iter() {
+            if slots.is_empty() {
+                continue;
+            }
+            prestate.entry(*address).or_default().storage = Some(slots.clone());
+        }
+        prestate
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.db
+    }
+}
+
+impl<DB: Database> Database for RecordingDatabase<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        self.accounts.entry(address).or_insert_with(|| info.clone());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.db.code_by_hash(code_hash)?;
+        if code_hash != KECCAK_EMPTY {
+            self.codes.entry(code_hash).or_insert_with(|| code.clone());
+        }
+        Ok(code)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey
+    ) -> Result<StorageValue, Self::Error> {
+        let value = self.db.storage(address, index)?;
+        self.storage.entry(address).or_default().entry(index).or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for RecordingDatabase<DB> {
+    // revm loads every account and slot before changing it, so the values
+    // recorded so far stay the ones from before execution
+    fn commit(&mut self, changes: HashMap<Address, Account>) {
+        self.db.commit(changes)
+    }
+}
@@ -0,0 +1,273 @@
+use revm::context::result::{ExecResultAndState, ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv};
+use revm::database::InMemoryDB;
+use revm::handler::system_call::SYSTEM_ADDRESS;
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::hash_map::Entry;
+use revm::primitives::{address, Address, Bytes, HashMap, B256, U256};
+use revm::state::{Account, EvmState};
+use revm::{Context, Database, DatabaseCommit, MainBuilder, MainContext, SystemCallEvm};
+use serde_json::Value;
+
+use crate::balance_changes::{compute_gas_fees, GasFees};
+use crate::block::{create_block_env_for_spec, BlockWithTransactions};
+use crate::bundle::merge_state_diff;
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::receipt::{create_receipt, Receipt};
+use crate::trace::{inspect_mainnet_transaction, pop_trace_result};
+use crate::transaction::create_tx_env_from_transaction_details;
+use crate::witness::{create_in_memory_database_from_execution_witness, ExecutionWitness};
+
+/// EIP-4788 contract, called with the parent beacon block root.
+pub const BEACON_ROOTS_ADDRESS: Address = address!("0x000F3df6D732807Ef1319fB7B8bB8522d0Beac02");
+/// EIP-2935 contract, called with the parent block hash.
+pub const HISTORY_STORAGE_ADDRESS: Address = address!("0x0000F90827F1C53a10cb7A02335B175320002935");
+/// EIP-7002 contract, called after the transactions to dequeue withdrawal
+/// requests.
+pub const WITHDRAWAL_REQUEST_ADDRESS: Address = address!("0x00000961Ef480Eb55e80D19ad83579A64c007002");
+/// EIP-7251 contract, called after the transactions to dequeue
+/// consolidation requests.
+pub const CONSOLIDATION_REQUEST_ADDRESS: Address = address!("0x0000BBdDc7CE488642fb579F8B00f3a590007251");
+
+/// Wei in a gwei, the unit of withdrawal amounts.
+const GWEI: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone)]
+pub struct ReplayedTransaction {
+    pub hash: B256,
+    pub result: ExecutionResult<HaltReason>,
+    pub state_diff: EvmState,
+    pub trace: Value,
+    pub fees: GasFees,
+    pub receipt: Receipt,
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemCallResult {
+    pub address: Address,
+    pub result: ExecutionResult<HaltReason>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockReplay {
+    pub block_env: BlockEnv,
+    /// Pre-block system calls, skipped when the system contract has no code.
+    pub system_calls: Vec<SystemCallResult>,
+    /// Post-block system calls, likewise.
+    pub post_block_system_calls: Vec<SystemCallResult>,
+    pub transactions: Vec<ReplayedTransaction>,
+    pub gas_used: u64,
+    pub burned_fees: U256,
+    /// Paid to the fee recipient.
+    pub priority_fees: U256,
+    /// Total of the withdrawals, in wei.
+    pub withdrawn: U256,
+    /// State of every account touched by the block, with the storage
+    /// original values of the parent state.
+    pub state_diff: HashMap<Address, Account>,
+}
+
+/// Merges the per-transaction `prestateTracer` results of a block (e.g.
+/// `debug_traceBlockByNumber`) into one prestate of the parent state: the
+/// first transaction that touches an account or slot saw its parent value.
+/// The tracer omits a zero nonce and empty code, so the account fields are
+/// all taken from the first transaction, only slots from later ones.
+pub fn merge_prestate_traces(
+    prestate_tracer_results: Vec<HashMap<Address, AccountDetails>>
+) -> HashMap<Address, AccountDetails> {
+    let mut merged: HashMap<Address, AccountDetails> = HashMap::default();
+    for prestate in prestate_tracer_results {
+        for (address, account) in prestate {
+            let Some(known) = merged.get_mut(&address) else {
+                merged.insert(address, account);
+                continue;
+            };
+            if let Some(storage) = account.storage {
+                let known_storage = known.storage.get_or_insert_default();
+                for (slot, value) in storage {
+                    known_storage.entry(slot).or_insert(value);
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Runs a system call and commits its changes, except to the system
+/// address and the fee recipient as nodes do.
+fn system_call(
+    cfg_env: &CfgEnv,
+    block_env: &BlockEnv,
+    db: &mut InMemoryDB,
+    state_diff: &mut HashMap<Address, Account>,
+    address: Address,
+    data: Bytes
+) -> Result<Option<SystemCallResult>, String> {
+    let has_code = db.basic(address)
+        .map_err(|error| error.to_string())?
+        .is_some_and(|info| !info.is_empty_code_hash());
+    if !has_code {
+        return Ok(None);
+    }
+    let mut evm = Context::mainnet()
+        .with_db(&mut *db)
+        .with_cfg(cfg_env.clone())
+        .with_block(block_env.clone())
+        .build_mainnet();
+    let ExecResultAndState { result, mut state } = evm.system_call(address, data)
+        .map_err(|error| format!("system call to {}: {}", address, error))?;
+    state.remove(&SYSTEM_ADDRESS);
+    state.remove(&block_env.beneficiary);
+    merge_state_diff(state_diff, &state);
+    db.commit(state);
+    Ok(Some(SystemCallResult { address, result }))
+}
+
+/// Credits the withdrawals, in gwei, to their recipients.
+fn apply_withdrawals(
+    db: &mut InMemoryDB,
+    block: &BlockWithTransactions
+) -> Result<(U256, EvmState), String> {
+    let mut withdrawn = U256::ZERO;
+    let mut state = EvmState::default();
+    for withdrawal in block.withdrawals.iter().filter(|withdrawal| !withdrawal.amount.is_zero()) {
+        let amount = withdrawal.amount * U256::from(GWEI);
+        withdrawn += amount;
+        let account = match state.entry(withdrawal.address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let info = db.basic(withdrawal.address)
+                    .map_err(|error| error.to_string())?
+                    .unwrap_or_default();
+                let mut account = Account::from(info);
+                account.mark_touch();
+                entry.insert(account)
+            }
+        };
+        account.info.balance += amount;
+    }
+    db.commit(state.clone());
+    Ok((withdrawn, state))
+}
+
+/// Replays `block` on `db`, the state of its parent, like a node would:
+/// the EIP-4788 and EIP-2935 system calls first, then every transaction
+/// in order with its state committed for the next one, then the EIP-7002
+/// and EIP-7251 system calls and the withdrawals. The requests they
+/// dequeue are not returned. An invalid transaction or a block over its gas limit
+/// aborts the replay. Uncle and pre-merge block rewards are not applied.
+pub fn replay_block(
+    chain_id: u64,
+    spec_id: SpecId,
+    block: &BlockWithTransactions,
+    mut db: InMemoryDB
+) -> Result<BlockReplay, String> {
+    let cfg_env = CfgEnv::new_with_spec(spec_id).with_chain_id(chain_id);
+    let block_env = create_block_env_for_spec(&block.details, spec_id)?;
+    let mut state_diff = HashMap::default();
+
+    let mut system_calls = Vec::new();
+    if let Some(parent_beacon_block_root) = block.details.parent_beacon_block_root {
+        system_calls.extend(system_call(
+            &cfg_env, &block_env, &mut db, &mut state_diff, BEACON_ROOTS_ADDRESS,
+            parent_beacon_block_root.into()
+        )?);
+    }
+    if spec_id.is_enabled_in(SpecId::PRAGUE) && !block_env.number.is_zero() {
+        system_calls.extend(system_call(
+            &cfg_env, &block_env, &mut db, &mut state_diff, HISTORY_STORAGE_ADDRESS,
+            block.details.parent_hash.into()
+        )?);
+    }
+
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    let mut gas_used = 0u64;
+    let mut log_index = 0u64;
+    let mut burned_fees = U256::ZERO;
+    let mut priority_fees = U256::ZERO;
+    for (index, transaction) in block.transactions.iter().enumerate() {
+        let error = |error: String| format!("transaction {} ({}): {}", index, transaction.hash, error);
+        let tx = create_tx_env_from_transaction_details(transaction).map_err(error)?;
+        if gas_used + tx.gas_limit > block_env.gas_limit {
+            return Err(error(format!(
+                "gas limit {} above the {} left in the block",
+                tx.gas_limit, block_env.gas_limit - gas_used
+            )));
+        }
+
+        let buffer = &mut Vec::new();
+        let (result, tx_state_diff, _) = inspect_mainnet_transaction(
+            cfg_env.clone(), block_env.clone(), &mut db, tx.clone(), MyInspector::new(buffer)
+        ).map_err(error)?;
+        merge_state_diff(&mut state_diff, &tx_state_diff);
+        db.commit(tx_state_diff.clone());
+
+        gas_used += result.gas_used();
+        let fees = compute_gas_fees(
+            tx.caller, tx.gas_price, tx.gas_priority_fee, result.gas_used(), &block_env
+        );
+        burned_fees += fees.burned_fee;
+        priority_fees += fees.priority_fee;
+        let receipt = create_receipt(
+            transaction.hash, index as u64, &tx, &result, gas_used, log_index, block_env.basefee
+        );
+        log_index += result.logs().len() as u64;
+        transactions.push(ReplayedTransaction {
+            hash: transaction.hash,
+            result,
+            state_diff: tx_state_diff,
+            trace: pop_trace_result(buffer),
+            fees,
+            receipt,
+        });
+    }
+
+    let mut post_block_system_calls = Vec::new();
+    if spec_id.is_enabled_in(SpecId::PRAGUE) {
+        for address in [WITHDRAWAL_REQUEST_ADDRESS, CONSOLIDATION_REQUEST_ADDRESS] {
+            post_block_system_calls.extend(system_call(
+                &cfg_env, &block_env, &mut db, &mut state_diff, address, Bytes::new()
+            )?);
+        }
+    }
+
+    let (withdrawn, withdrawals_state) = apply_withdrawals(&mut db, block)?;
+    merge_state_diff(&mut state_diff, &withdrawals_state);
+
+    Ok(BlockReplay {
+        block_env,
+        system_calls,
+        post_block_system_calls,
+        transactions,
+        gas_used,
+        burned_fees,
+        priority_fees,
+        withdrawn,
+        state_diff,
+    })
+}
+
+/// [`replay_block`] on the prestate of the whole block, see
+/// [`merge_prestate_traces`].
+pub fn replay_block_from_prestate(
+    chain_id: u64,
+    spec_id: SpecId,
+    block: &BlockWithTransactions,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<BlockReplay, String> {
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+    replay_block(chain_id, spec_id, block, db)
+}
+
+/// [`replay_block`] on the `debug_executionWitness` of the block.
+pub fn replay_block_from_execution_witness(
+    chain_id: u64,
+    spec_id: SpecId,
+    block: &BlockWithTransactions,
+    witness: &ExecutionWitness
+) -> Result<BlockReplay, String> {
+    let parent = witness.parent_header()?;
+    let db = create_in_memory_database_from_execution_witness(parent.state_root, witness)?;
+    replay_block(chain_id, spec_id, block, db)
+}
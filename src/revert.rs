@@ -0,0 +1,214 @@
+use std::fmt;
+
+use alloy_dyn_abi::JsonAbiExt;
+use alloy_json_abi::{Error, JsonAbi};
+use revm::context::result::ExecutionResult;
+use revm::interpreter::InstructionResult;
+use revm::primitives::{Bytes, HashMap, U256};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::abi::{decoded_params, DecodedParam};
+
+/// `Error(string)`
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)`
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+/// EntryPoint `FailedOp(uint256,string)`
+pub const FAILED_OP_SELECTOR: [u8; 4] = [0x22, 0x02, 0x66, 0xb6];
+/// EntryPoint `FailedOpWithRevert(uint256,string,bytes)`
+pub const FAILED_OP_WITH_REVERT_SELECTOR: [u8; 4] = [0x65, 0xc8, 0xfd, 0x4d];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RevertReason {
+    Error { message: String },
+    #[serde(rename_all = "camelCase")]
+    Panic { code: U256, meaning: String },
+    #[serde(rename_all = "camelCase")]
+    FailedOp { op_index: U256, reason: String },
+    #[serde(rename_all = "camelCase")]
+    FailedOpWithRevert {
+        op_index: U256,
+        reason: String,
+        inner: Bytes,
+        inner_reason: Option<Box<RevertReason>>,
+    },
+    Custom { name: String, signature: String, args: Vec<DecodedParam> },
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RevertReason::Error { message } => write!(f, "{}", message),
+            RevertReason::Panic { code, meaning } => {
+                write!(f, "Panic(0x{:x}): {}", code, meaning)
+            }
+            RevertReason::FailedOp { op_index, reason } => {
+                write!(f, "FailedOp({}, {})", op_index, reason)
+            }
+            RevertReason::FailedOpWithRevert { op_index, reason, inner, inner_reason } => {
+                match inner_reason {
+                    Some(inner_reason) => write!(
+                        f, "FailedOpWithRevert({}, {}, {})", op_index, reason, inner_reason
+                    ),
+                    None => write!(f, "FailedOpWithRevert({}, {}, {})", op_index, reason, inner),
+                }
+            }
+            RevertReason::Custom { signature, args, .. } => {
+                let args: Vec<String> = args.iter().map(|arg| match &arg.value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                }).collect();
+                write!(f, "{} [{}]", signature, args.join(", "))
+            }
+        }
+    }
+}
+
+/// Meaning of the Solidity `Panic(uint256)` codes.
+pub fn panic_code_meaning(code: U256) -> &'static str {
+    match code.saturating_to::<u64>() {
+        0x00 => "generic compiler inserted panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to uninitialized internal function",
+        _ => "unknown panic code",
+    }
+}
+
+/// Decodes revert data. `Error(string)`, `Panic(uint256)` and the EntryPoint
+/// `FailedOp` errors are always known; custom errors are added from ABIs or
+/// error signatures.
+#[derive(Debug, Clone, Default)]
+pub struct RevertDecoder {
+    errors: HashMap<[u8; 4], Vec<Error>>,
+}
+
+impl RevertDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_error(&mut self, error: Error) {
+        let candidates = self.errors.entry(error.selector().0).or_default();
+        if !candidates.contains(&error) {
+            candidates.push(error);
+        }
+    }
+
+    pub fn add_abi(&mut self, abi: &JsonAbi) {
+        for error in abi.errors() {
+            self.add_error(error.clone());
+        }
+    }
+
+    /// Adds an error from its signature, e.g. `InsufficientBalance(uint256 needed)`.
+    pub fn add_error_signature(&mut self, signature: &str) -> Result<(), String> {
+        let error = Error::parse(signature).map_err(|error| error.to_string())?;
+        self.add_error(error);
+        Ok(())
+    }
+
+    pub fn decode(&self, output: &[u8]) -> Option<RevertReason> {
+        if output.len() < 4 {
+            return None;
+        }
+        let selector: [u8; 4] = output[..4].try_into().unwrap();
+        let data = &output[4..];
+        let builtin = match selector {
+            ERROR_SELECTOR => "Error(string message)",
+            PANIC_SELECTOR => "Panic(uint256 code)",
+            FAILED_OP_SELECTOR => "FailedOp(uint256 opIndex, string reason)",
+            FAILED_OP_WITH_REVERT_SELECTOR => {
+                "FailedOpWithRevert(uint256 opIndex, string reason, bytes inner)"
+            }
+            _ => return self.decode_custom(selector, data),
+        };
+        let values = Error::parse(builtin).ok()?.abi_decode_input(data).ok()?;
+        match (selector, values.as_slice()) {
+            (ERROR_SELECTOR, [message]) => Some(RevertReason::Error {
+                message: message.as_str()?.to_string(),
+            }),
+            (PANIC_SELECTOR, [code]) => {
+                let code = code.as_uint()?.0;
+                Some(RevertReason::Panic { code, meaning: panic_code_meaning(code).to_string() })
+            }
+            (FAILED_OP_SELECTOR, [op_index, reason]) => Some(RevertReason::FailedOp {
+                op_index: op_index.as_uint()?.0,
+                reason: reason.as_str()?.to_string(),
+            }),
+            (FAILED_OP_WITH_REVERT_SELECTOR, [op_index, reason, inner]) => {
+                let inner = Bytes::copy_from_slice(inner.as_bytes()?);
+                Some(RevertReason::FailedOpWithRevert {
+                    op_index: op_index.as_uint()?.0,
+                    reason: reason.as_str()?.to_string(),
+                    inner_reason: self.decode(&inner).map(Box::new),
+                    inner,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn decode_custom(&self, selector: [u8; 4], data: &[u8]) -> Option<RevertReason> {
+        // selectors can collide, the first error that decodes cleanly wins
+        self.errors.get(&selector)?.iter().find_map(|error| {
+            let values = error.abi_decode_input(data).ok()?;
+            Some(RevertReason::Custom {
+                name: error.name.clone(),
+                signature: error.signature(),
+                args: decoded_params(&error.inputs, &values),
+            })
+        })
+    }
+}
+
+/// Decoded reason of a top-level `ExecutionResult::Revert`.
+pub fn decode_execution_revert<HaltReasonTy>(
+    execution_result: &ExecutionResult<HaltReasonTy>,
+    decoder: &RevertDecoder
+) -> Option<RevertReason> {
+    match execution_result {
+        ExecutionResult::Revert { output, .. } => decoder.decode(output),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_reverted_frame(frame: &Value) -> bool {
+    frame["outcome"]["result"]["result"] == "Revert"
+}
+
+/// Whether a frame reverted or halted (out of gas, invalid opcode, ...),
+/// either way its state changes are undone.
+pub(crate) fn is_failed_frame(frame: &Value) -> bool {
+    !serde_json::from_value::<InstructionResult>(frame["outcome"]["result"]["result"].clone())
+        .is_ok_and(InstructionResult::is_ok)
+}
+
+pub(crate) fn frame_output(frame: &Value) -> Option<Bytes> {
+    serde_json::from_value(frame["outcome"]["result"]["output"].clone()).ok()
+}
+
+/// Re-decodes the `revertReason` of every reverted frame of a trace with
+/// `decoder`, e.g. once custom error ABIs are known.
+pub fn annotate_revert_reasons(trace: &mut Value, decoder: &RevertDecoder) {
+    if !trace.is_object() {
+        return;
+    }
+    if is_reverted_frame(trace) {
+        let reason = frame_output(trace).and_then(|output| decoder.decode(&output));
+        trace["revertReason"] = serde_json::to_value(reason).unwrap_or(Value::Null);
+    }
+    if let Some(calls) = trace.get_mut("calls").and_then(Value::as_array_mut) {
+        for call in calls.iter_mut() {
+            annotate_revert_reasons(call, decoder);
+        }
+    }
+}
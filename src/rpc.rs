@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use op_revm::OpHaltReason;
+use revm::context::result::HaltReason;
+use revm::primitives::ruint::aliases::U64;
+use revm::primitives::{Address, Bytes, HashMap, U256};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::block::{create_block_env_from_block_details, BlockDetails};
+use crate::database::AccountDetails;
+use crate::json_rpc::JsonRpcResponse;
+use crate::lazy_database::JsonRpcTransport;
+use crate::trace::{op_trace_transaction, trace_transaction, TraceOutput};
+
+#[derive(Debug, Clone)]
+pub struct RpcClientConfig {
+    /// Of every single HTTP request.
+    pub timeout: Duration,
+    /// Retries after a failed HTTP request, JSON-RPC errors are not retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every next one.
+    pub initial_backoff: Duration,
+}
+
+impl Default for RpcClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Async JSON-RPC client. Clones share the same connection pool.
+///
+/// Also a blocking [`JsonRpcTransport`], so the lazy database, proofs,
+/// receipts and mined transactions go through the same timeouts and retries.
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    url: String,
+    client: reqwest::Client,
+    config: RpcClientConfig,
+    next_id: Arc<AtomicU64>,
+    runtime: Arc<BlockingRuntime>,
+}
+
+/// Runs the requests of the blocking transport. Shut down without waiting,
+/// so that the last client can be dropped from async code.
+#[derive(Debug)]
+struct BlockingRuntime(Option<tokio::runtime::Runtime>);
+
+impl BlockingRuntime {
+    fn get(&self) -> &tokio::runtime::Runtime {
+        // only taken when dropped
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Whether the request may succeed when sent again: connection failures,
+/// timeouts, rate limiting and server errors.
+fn is_retryable(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout()
+        || error.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Result<Self, String> {
+        Self::with_config(url, RpcClientConfig::default())
+    }
+
+    pub fn with_config(url: &str, config: RpcClientConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|error| error.to_string())?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|error| error.to_string())?;
+        Ok(Self {
+            url: url.to_string(),
+            client,
+            config,
+            next_id: Arc::new(AtomicU64::new(1)),
+            runtime: Arc::new(BlockingRuntime(Some(runtime))),
+        })
+    }
+
+    async fn send(&self, request_body: &Value) -> Result<Value, reqwest::Error> {
+        self.client
+            .post(&self.url)
+            .json(request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await
+    }
+
+    /// Sends `method` and returns the raw response object, retrying with
+    /// exponential backoff when the HTTP request fails.
+    async fn send_with_retries(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request_body = json!({
+            "jsonrpc":"2.0",
+            "method":method,
+            "params":params,
+            "id":self.next_id.fetch_add(1, Ordering::Relaxed)
+        });
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.send(&request_body).await {
+                Ok(response) => return Ok(response),
+                Err(error) if attempt < self.config.max_retries && is_retryable(&error) => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(error) => return Err(format!("{}: {}", method, error)),
+            }
+        }
+    }
+
+    /// Sends `method` and decodes the result, retrying with exponential
+    /// backoff when the HTTP request fails.
+    pub async fn request<R: DeserializeOwned>(&self, method: &str, params: Value) -> Result<R, String> {
+        let response = self.send_with_retries(method, params).await?;
+        match serde_json::from_value::<JsonRpcResponse<R>>(response) {
+            Ok(JsonRpcResponse::Result(result)) => Ok(result.result),
+            Ok(JsonRpcResponse::Error(error)) => Err(error.to_string()),
+            Err(error) => Err(format!("{}: {}", method, error)),
+        }
+    }
+
+    /// `block` is a number or a tag such as `"latest"`.
+    pub async fn get_block_by_number(&self, block: &str) -> Result<BlockDetails, String> {
+        let block_details: Option<BlockDetails> = self.request(
+            "eth_getBlockByNumber", json!([block, false])
+        ).await?;
+        block_details.ok_or(format!("block {} not found", block))
+    }
+
+    pub async fn get_nonce(&self, address: Address, block: &str) -> Result<u64, String> {
+        let nonce: U64 = self.request("eth_getTransactionCount", json!([address, block])).await?;
+        Ok(nonce.to())
+    }
+
+    /// `debug_traceCall` with the `prestateTracer`.
+    pub async fn get_prestate_trace(
+        &self,
+        from: Address,
+        to: Address,
+        data: &Bytes,
+        value: U256,
+        gas_limit: u64,
+        block: &str
+    ) -> Result<HashMap<Address, AccountDetails>, String> {
+        self.request("debug_traceCall", json!([
+            {
+                "from": from,
+                "to": to,
+                "data": data,
+                "value": value,
+                "gas": U64::from(gas_limit)
+            },
+            block,
+            { "tracer": "prestateTracer" }
+        ])).await
+    }
+
+    /// The latest block, the sender nonce and the prestate of the call at
+    /// that block, fetched together so that they are consistent.
+    pub async fn fetch_trace_inputs(
+        &self,
+        from: Address,
+        to: Address,
+        data: &Bytes,
+        value: U256,
+        gas_limit: u64
+    ) -> Result<(BlockDetails, u64, HashMap<Address, AccountDetails>), String> {
+        let block_details = self.get_block_by_number("latest").await?;
+        let block = format!("0x{:x}", block_details.number);
+        let (from_nonce, prestate) = tokio::try_join!(
+            self.get_nonce(from, &block),
+            self.get_prestate_trace(from, to, data, value, gas_limit, &block)
+        )?;
+        Ok((block_details, from_nonce, prestate))
+    }
+}
+
+impl JsonRpcTransport for RpcClient {
+    /// Blocks until the response arrives. The request runs on the runtime of
+    /// the client, which makes this callable from async code as well.
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let client = self.clone();
+        let method = method.to_string();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.runtime.get().spawn(async move {
+            let response = client.send_with_retries(&method, params).await;
+            // the runtime must not be dropped by one of its own tasks
+            drop(client);
+            let _ = sender.send(response);
+        });
+        receiver.recv().map_err(|error| error.to_string())?
+    }
+}
+
+/// Fetches the latest block, the nonce of `from` and the prestate, then
+/// traces the transaction on them with [`trace_transaction`].
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_and_trace_transaction(
+    client: &RpcClient,
+    chain_id: u64,
+    from: Address,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128
+) -> Result<TraceOutput<HaltReason>, String> {
+    let (block_details, from_nonce, prestate) = client.fetch_trace_inputs(
+        from, to, &data, value, gas_limit
+    ).await?;
+    let latest_block = create_block_env_from_block_details(block_details)
+        .map_err(|error| error.to_string())?;
+    trace_transaction(
+        chain_id, from, from_nonce, to, data, value, gas_limit, gas_price, gas_priority_fee,
+        latest_block, prestate
+    )
+}
+
+/// [`fetch_and_trace_transaction`] with [`op_trace_transaction`].
+#[allow(clippy::too_many_arguments)]
+pub async fn op_fetch_and_trace_transaction(
+    client: &RpcClient,
+    chain_id: u64,
+    from: Address,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128
+) -> Result<TraceOutput<OpHaltReason>, String> {
+    let (block_details, from_nonce, prestate) = client.fetch_trace_inputs(
+        from, to, &data, value, gas_limit
+    ).await?;
+    let latest_block = create_block_env_from_block_details(block_details)
+        .map_err(|error| error.to_string())?;
+    op_trace_transaction(
+        chain_id, from, from_nonce, to, data, value, gas_limit, gas_price, gas_priority_fee,
+        latest_block, prestate
+    )
+}
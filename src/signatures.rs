@@ -0,0 +1,352 @@
+use std::fs;
+use std::path::Path;
+
+use alloy_json_abi::{Error, Event, Function};
+use revm::primitives::{hex, Log, HashMap, B256};
+use serde_json::Value;
+
+use crate::abi::{decode_event, decode_function_call, DecodedCall, DecodedEvent};
+use crate::revert::RevertDecoder;
+
+/// Signatures built into [`SignatureDatabase::embedded`].
+pub const COMMON_FUNCTIONS: &[&str] = &[
+    "name()",
+    "symbol()",
+    "decimals()",
+    "totalSupply()",
+    "balanceOf(address)",
+    "allowance(address,address)",
+    "transfer(address,uint256)",
+    "transferFrom(address,address,uint256)",
+    "approve(address,uint256)",
+    "ownerOf(uint256)",
+    "getApproved(uint256)",
+    "isApprovedForAll(address,address)",
+    "setApprovalForAll(address,bool)",
+    "safeTransferFrom(address,address,uint256)",
+    "safeTransferFrom(address,address,uint256,bytes)",
+    "safeTransferFrom(address,address,uint256,uint256,bytes)",
+    "safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+    "balanceOfBatch(address[],uint256[])",
+    "deposit()",
+    "withdraw(uint256)",
+    "multicall(bytes[])",
+    "owner()",
+    "transferOwnership(address)",
+    "execute(address,uint256,bytes)",
+    "executeBatch(address[],uint256[],bytes[])",
+    "handleOps((address,uint256,bytes,bytes,uint256,uint256,uint256,uint256,uint256,bytes,bytes)[],address)",
+    "handleOps((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address)",
+    "depositTo(address)",
+    "getNonce(address,uint192)",
+];
+
+pub const COMMON_EVENTS: &[&str] = &[
+    "Transfer(address,address,uint256)",
+    "Approval(address,address,uint256)",
+    "ApprovalForAll(address,address,bool)",
+    "TransferSingle(address,address,address,uint256,uint256)",
+    "TransferBatch(address,address,address,uint256[],uint256[])",
+    "Deposit(address,uint256)",
+    "Withdrawal(address,uint256)",
+    "OwnershipTransferred(address,address)",
+    "Upgraded(address)",
+    "UserOperationEvent(bytes32,address,address,uint256,bool,uint256,uint256)",
+    "BeforeExecution()",
+];
+
+pub const COMMON_ERRORS: &[&str] = &[
+    "ERC20InsufficientBalance(address,uint256,uint256)",
+    "ERC20InsufficientAllowance(address,uint256,uint256)",
+    "ERC20InvalidSender(address)",
+    "ERC20InvalidReceiver(address)",
+    "ERC721NonexistentToken(uint256)",
+    "ERC721IncorrectOwner(address,uint256,address)",
+    "OwnableUnauthorizedAccount(address)",
+    "ReentrancyGuardReentrantCall()",
+    "SafeERC20FailedOperation(address)",
+    "SignatureValidationFailed(address)",
+];
+
+/// Offline store of function selectors, event topics and error selectors
+/// used to guess what a call, log or revert is when no ABI is registered.
+/// Signatures carry no parameter names nor which event parameters are
+/// indexed.
+#[derive(Debug, Clone, Default)]
+pub struct SignatureDatabase {
+    functions: HashMap<[u8; 4], Vec<Function>>,
+    events: HashMap<B256, Vec<Event>>,
+    errors: HashMap<[u8; 4], Vec<Error>>,
+}
+
+impl SignatureDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Database of the common token, ownership and ERC-4337 signatures.
+    pub fn embedded() -> Self {
+        let mut signatures = Self::new();
+        for signature in COMMON_FUNCTIONS {
+            signatures.add_function(signature).unwrap();
+        }
+        for signature in COMMON_EVENTS {
+            signatures.add_event(signature).unwrap();
+        }
+        for signature in COMMON_ERRORS {
+            signatures.add_error(signature).unwrap();
+        }
+        signatures
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.values().map(Vec::len).sum::<usize>()
+            + self.events.values().map(Vec::len).sum::<usize>()
+            + self.errors.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn add_function(&mut self, signature: &str) -> Result<(), String> {
+        let function = Function::parse(signature).map_err(|error| error.to_string())?;
+        let candidates = self.functions.entry(function.selector().0).or_default();
+        if !candidates.contains(&function) {
+            candidates.push(function);
+        }
+        Ok(())
+    }
+
+    pub fn add_event(&mut self, signature: &str) -> Result<(), String> {
+        let event = Event::parse(signature).map_err(|error| error.to_string())?;
+        let candidates = self.events.entry(event.selector()).or_default();
+        if !candidates.contains(&event) {
+            candidates.push(event);
+        }
+        Ok(())
+    }
+
+    pub fn add_error(&mut self, signature: &str) -> Result<(), String> {
+        let error = Error::parse(signature).map_err(|error| error.to_string())?;
+        let candidates = self.errors.entry(error.selector().0).or_default();
+        if !candidates.contains(&error) {
+            candidates.push(error);
+        }
+        Ok(())
+    }
+
+    /// Adds a signature whose kind is given by its selector: 4 bytes for a
+    /// function, which also covers custom errors, 32 bytes for an event.
+    /// Entries whose selector does not hash from the signature are rejected.
+    fn add_entry(&mut self, selector: &[u8], signature: &str) -> Result<(), String> {
+        let matches = match selector.len() {
+            4 => {
+                let function = Function::parse(signature).map_err(|error| error.to_string())?;
+                function.selector()[..] == *selector
+            }
+            32 => {
+                let event = Event::parse(signature).map_err(|error| error.to_string())?;
+                event.selector()[..] == *selector
+            }
+            length => return Err(format!("selector of {} bytes", length)),
+        };
+        if !matches {
+            return Err(format!("{} does not hash to {}", signature, hex::encode_prefixed(selector)));
+        }
+        match selector.len() {
+            4 => {
+                self.add_function(signature)?;
+                self.add_error(signature)
+            }
+            _ => self.add_event(signature),
+        }
+    }
+
+    /// Imports an openchain export, `{"function": {"0x..": [{"name": ..}]},
+    /// "event": {..}}` optionally wrapped in the API `"result"`. Returns the
+    /// number of signatures read.
+    pub fn import_json(&mut self, json: &str) -> Result<usize, String> {
+        let value: Value = serde_json::from_str(json).map_err(|error| error.to_string())?;
+        let value = value.get("result").unwrap_or(&value);
+        let mut count = 0;
+        for kind in ["function", "event"] {
+            let Some(entries) = value.get(kind).and_then(Value::as_object) else {
+                continue;
+            };
+            for (selector, matches) in entries.iter() {
+                let selector = hex::decode(selector).map_err(|error| error.to_string())?;
+                for name in matches.as_array().into_iter().flatten()
+                    .filter_map(|entry| entry.get("name").and_then(Value::as_str)) {
+                    // dumps contain garbage, one bad entry should not abort the import
+                    if self.add_entry(&selector, name).is_ok() {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Imports a text dump with one signature per line, optionally with its
+    /// hex selector or topic, e.g. `0xa9059cbb transfer(address,uint256)` or
+    /// a 4byte.directory CSV export. Lines without a selector are added both
+    /// as a function and as an event. Returns the number of signatures read.
+    pub fn import_text(&mut self, text: &str) -> usize {
+        let mut count = 0;
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some(signature) = find_signature(line) else {
+                continue;
+            };
+            let rest = line.replacen(signature, "", 1);
+            let selector = rest
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .filter_map(|token| token.strip_prefix("0x"))
+                .filter_map(|token| hex::decode(token).ok())
+                .find(|selector| selector.len() == 4 || selector.len() == 32);
+            let added = match selector {
+                Some(selector) => self.add_entry(&selector, signature).is_ok(),
+                None => {
+                    let function = self.add_function(signature).is_ok();
+                    let event = self.add_event(signature).is_ok();
+                    function || event
+                }
+            };
+            if added {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Imports a JSON or text dump, see [`Self::import_json`] and
+    /// [`Self::import_text`].
+    pub fn import_file(&mut self, path: impl AsRef<Path>) -> Result<usize, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        if content.trim_start().starts_with('{') {
+            self.import_json(&content).map_err(|error| format!("{}: {}", path.display(), error))
+        } else {
+            Ok(self.import_text(&content))
+        }
+    }
+
+    /// Writes the database as a text dump that [`Self::import_text`] reads back.
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let mut lines: Vec<String> = Vec::with_capacity(self.len());
+        for (selector, functions) in self.functions.iter() {
+            for function in functions {
+                lines.push(format!("{} {}", hex::encode_prefixed(selector), function.signature()));
+            }
+        }
+        for (topic, events) in self.events.iter() {
+            for event in events {
+                lines.push(format!("{} {}", topic, event.signature()));
+            }
+        }
+        for (selector, errors) in self.errors.iter() {
+            for error in errors {
+                let line = format!("{} {}", hex::encode_prefixed(selector), error.signature());
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+        lines.sort();
+        let path = path.as_ref();
+        fs::write(path, lines.join("\n") + "\n")
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn functions(&self, selector: [u8; 4]) -> &[Function] {
+        self.functions.get(&selector).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn events(&self, topic: B256) -> &[Event] {
+        self.events.get(&topic).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn errors(&self, selector: [u8; 4]) -> &[Error] {
+        self.errors.get(&selector).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Revert decoder knowing every error of the database.
+    pub fn revert_decoder(&self) -> RevertDecoder {
+        let mut decoder = RevertDecoder::new();
+        for error in self.errors.values().flatten() {
+            decoder.add_error(error.clone());
+        }
+        decoder
+    }
+
+    /// Guesses the function called with `input`: the first candidate for
+    /// its selector that decodes it.
+    pub fn decode_call(&self, input: &[u8], output: Option<&[u8]>) -> Option<DecodedCall> {
+        let selector: [u8; 4] = input.get(..4)?.try_into().ok()?;
+        self.functions(selector).iter()
+            .find_map(|function| decode_function_call(function, input, output, true))
+    }
+
+    /// Guesses the event of `log`. As signatures do not tell which
+    /// parameters are indexed, the first placement of as many indexed
+    /// parameters as the log has topics that decodes it is used.
+    pub fn decode_log(&self, log: &Log) -> Option<DecodedEvent> {
+        let topic0 = *log.topics().first()?;
+        let indexed_count = log.topics().len() - 1;
+        self.events(topic0).iter().find_map(|event| {
+            let placements = indexed_placements(event.inputs.len(), indexed_count);
+            let candidates: Vec<Event> = placements.into_iter().map(|placement| {
+                let mut candidate = event.clone();
+                for (input, indexed) in candidate.inputs.iter_mut().zip(placement) {
+                    input.indexed = indexed;
+                }
+                candidate
+            }).collect();
+            decode_event(candidates.iter(), log, true)
+        })
+    }
+}
+
+/// Every way to mark `indexed` out of `count` parameters, leftmost first.
+fn indexed_placements(count: usize, indexed: usize) -> Vec<Vec<bool>> {
+    if indexed > count {
+        return Vec::new();
+    }
+    if indexed == 0 {
+        return vec![vec![false; count]];
+    }
+    if indexed == count {
+        return vec![vec![true; count]];
+    }
+    let mut placements = Vec::new();
+    for mut rest in indexed_placements(count - 1, indexed - 1) {
+        rest.insert(0, true);
+        placements.push(rest);
+    }
+    for mut rest in indexed_placements(count - 1, indexed) {
+        rest.insert(0, false);
+        placements.push(rest);
+    }
+    placements
+}
+
+/// The `name(types)` part of a dump line.
+fn find_signature(line: &str) -> Option<&str> {
+    let open = line.find('(')?;
+    let close = line.rfind(')')?;
+    if close < open {
+        return None;
+    }
+    let start = line[..open]
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    if start == open {
+        return None;
+    }
+    Some(&line[start..=close])
+}
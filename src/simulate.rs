@@ -0,0 +1,459 @@
+use revm::context::result::{ExecutionResult, HaltReason};
+use revm::context::{BlockEnv, CfgEnv, ContextTr};
+use revm::database::InMemoryDB;
+use revm::interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter, InterpreterTypes};
+use revm::primitives::{address, Address, Bytes, HashMap, Log, LogData, TxKind, B256, U256};
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::state::{AccountInfo, Bytecode};
+use revm::{Database, DatabaseCommit, Inspector};
+use serde::{Deserialize, Serialize};
+
+use crate::call::call_cfg_env;
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::trace::{inspect_mainnet_transaction, tx_env_build_error_to_string, tx_env_builder};
+use crate::transfers::TRANSFER_TOPIC;
+
+/// Emitter of the synthetic `Transfer` logs of native value transfers.
+pub const TRANSFER_LOG_ADDRESS: Address = address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee");
+/// Most blocks one simulation can produce, gaps included.
+pub const MAX_SIMULATED_BLOCKS: u64 = 256;
+/// Seconds between a simulated block and the previous one when not overridden.
+pub const DEFAULT_BLOCK_TIME: u64 = 12;
+
+/// Blocks to simulate and their options, with the field names of the
+/// `eth_simulateV1` parameters.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatePayload {
+    pub block_state_calls: Vec<SimulateBlock>,
+    #[serde(default)]
+    pub trace_transfers: bool,
+    #[serde(default)]
+    pub validation: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateBlock {
+    pub block_overrides: Option<BlockOverrides>,
+    pub state_overrides: Option<HashMap<Address, AccountOverride>>,
+    #[serde(default)]
+    pub calls: Vec<SimulateCall>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    pub number: Option<U64>,
+    pub time: Option<U64>,
+    pub gas_limit: Option<U64>,
+    pub fee_recipient: Option<Address>,
+    pub prev_randao: Option<B256>,
+    pub base_fee_per_gas: Option<U64>,
+    pub blob_base_fee: Option<U128>,
+}
+
+/// `state` replaces the whole storage of the account, `state_diff` only the
+/// slots it lists.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U64>,
+    pub code: Option<Bytes>,
+    pub state: Option<HashMap<B256, B256>>,
+    pub state_diff: Option<HashMap<B256, B256>>,
+    pub move_precompile_to_address: Option<Address>,
+}
+
+/// A call of a simulated block. `to` empty is a contract creation; the
+/// nonce defaults to the sender's and the gas to what is left in the block.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateCall {
+    #[serde(default)]
+    pub from: Address,
+    pub to: Option<Address>,
+    #[serde(default, alias = "data")]
+    pub input: Bytes,
+    #[serde(default)]
+    pub value: U256,
+    pub gas: Option<U64>,
+    pub gas_price: Option<U128>,
+    pub max_fee_per_gas: Option<U128>,
+    pub max_priority_fee_per_gas: Option<U128>,
+    pub nonce: Option<U64>,
+}
+
+/// Log of a simulated call, with its position in the simulated block. No
+/// block or transaction hash is computed, so unlike an RPC log it has no
+/// `blockHash` and `transactionHash`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+    pub block_number: U64,
+    pub transaction_index: U64,
+    pub log_index: U64,
+    pub removed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateCallError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Bytes>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCall {
+    pub return_data: Bytes,
+    pub logs: Vec<SimulatedLog>,
+    pub gas_used: U64,
+    /// `0x1` on success, `0x0` otherwise.
+    pub status: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SimulateCallError>,
+}
+
+/// Header fields of a simulated block and the results of its calls. This
+/// is not a full block: the hashes, `parentHash`, `stateRoot`, `logsBloom`
+/// and `transactions` are not computed and left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedBlock {
+    pub number: U64,
+    pub timestamp: U64,
+    pub gas_limit: U64,
+    pub gas_used: U64,
+    pub miner: Address,
+    pub base_fee_per_gas: U64,
+    pub mix_hash: B256,
+    pub calls: Vec<SimulatedCall>,
+}
+
+fn transfer_log(from: Address, to: Address, value: U256) -> Log {
+    Log {
+        address: TRANSFER_LOG_ADDRESS,
+        data: LogData::new_unchecked(
+            vec![TRANSFER_TOPIC, from.into_word(), to.into_word()],
+            Bytes::from(value.to_be_bytes::<32>())
+        ),
+    }
+}
+
+/// Collects the logs of a call in emission order, without those of the
+/// frames that failed. With `trace_transfers`, every native value transfer
+/// is logged as an ERC-20 `Transfer` from [`TRANSFER_LOG_ADDRESS`].
+struct SimulateLogInspector {
+    trace_transfers: bool,
+    logs: Vec<Log>,
+    // number of logs when every open frame started, and for creations the
+    // index of the transfer log whose recipient is not known yet
+    frames: Vec<(usize, Option<usize>)>,
+}
+
+impl SimulateLogInspector {
+    fn new(trace_transfers: bool) -> Self {
+        Self { trace_transfers, logs: Vec::new(), frames: Vec::new() }
+    }
+
+    fn exit(&mut self, success: bool) {
+        if let Some((start, _)) = self.frames.pop() && !success {
+            self.logs.truncate(start);
+        }
+    }
+}
+
+impl<CTX: ContextTr, INTR: InterpreterTypes> Inspector<CTX, INTR> for SimulateLogInspector {
+    fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.frames.push((self.logs.len(), None));
+        if self.trace_transfers && inputs.transfers_value() {
+            self.logs.push(transfer_log(
+                inputs.transfer_from(), inputs.transfer_to(), inputs.call_value()
+            ));
+        }
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.exit(outcome.result.is_ok());
+    }
+
+    fn create(&mut self, _context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let start = self.logs.len();
+        let mut pending = None;
+        if self.trace_transfers && inputs.value > U256::ZERO {
+            pending = Some(start);
+            self.logs.push(transfer_log(inputs.caller, Address::ZERO, inputs.value));
+        }
+        self.frames.push((start, pending));
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut CTX, _inputs: &CreateInputs, outcome: &mut CreateOutcome) {
+        if let Some((_, Some(index))) = self.frames.last()
+            && let Some(created) = outcome.address {
+            let log = &self.logs[*index];
+            self.logs[*index] = transfer_log(
+                Address::from_word(log.topics()[1]), created, U256::from_be_slice(&log.data.data)
+            );
+        }
+        self.exit(outcome.result.is_ok());
+    }
+
+    fn log(&mut self, _interp: &mut Interpreter<INTR>, _context: &mut CTX, log: Log) {
+        self.logs.push(log);
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if self.trace_transfers && value > U256::ZERO {
+            self.logs.push(transfer_log(contract, target, value));
+        }
+    }
+}
+
+fn apply_state_overrides(
+    db: &mut InMemoryDB,
+    overrides: &HashMap<Address, AccountOverride>
+) -> Result<(), String> {
+    for (address, account) in overrides.iter() {
+        if account.move_precompile_to_address.is_some() {
+            return Err(format!("{}: moving precompiles is not supported", address));
+        }
+        if account.state.is_some() && account.state_diff.is_some() {
+            return Err(format!("{}: both state and stateDiff overridden", address));
+        }
+        let mut info = db.basic(*address)
+            .map_err(|error| error.to_string())?
+            .unwrap_or_default();
+        if let Some(balance) = account.balance {
+            info.balance = balance;
+        }
+        if let Some(nonce) = account.nonce {
+            info.nonce = nonce.to();
+        }
+        if let Some(code) = &account.code {
+            let bytecode = Bytecode::new_raw(code.clone());
+            info = AccountInfo { code_hash: bytecode.hash_slow(), code: Some(bytecode), ..info };
+        }
+        db.insert_account_info(*address, info);
+
+        let words = |storage: &HashMap<B256, B256>| -> HashMap<U256, U256> {
+            storage.iter().map(|(slot, value)| ((*slot).into(), (*value).into())).collect()
+        };
+        if let Some(state) = &account.state {
+            db.replace_account_storage(*address, words(state))
+                .map_err(|error| error.to_string())?;
+        }
+        if let Some(state_diff) = &account.state_diff {
+            for (slot, value) in words(state_diff) {
+                db.insert_account_storage(*address, slot, value)
+                    .map_err(|error| error.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Environment of the block after `parent`, with `overrides` applied.
+/// Without validation the base fee is zero unless overridden, as gas is
+/// free then.
+fn simulated_block_env(
+    parent: &BlockEnv,
+    overrides: &BlockOverrides,
+    validation: bool
+) -> BlockEnv {
+    let mut block_env = parent.clone();
+    block_env.number = overrides.number.map(U256::from)
+        .unwrap_or(parent.number + U256::from(1));
+    block_env.timestamp = overrides.time.map(U256::from)
+        .unwrap_or(parent.timestamp + U256::from(DEFAULT_BLOCK_TIME));
+    if let Some(gas_limit) = overrides.gas_limit {
+        block_env.gas_limit = gas_limit.to();
+    }
+    if let Some(fee_recipient) = overrides.fee_recipient {
+        block_env.beneficiary = fee_recipient;
+    }
+    if let Some(prev_randao) = overrides.prev_randao {
+        block_env.prevrandao = Some(prev_randao);
+    }
+    block_env.basefee = match overrides.base_fee_per_gas {
+        Some(base_fee) => base_fee.to(),
+        None if validation => parent.basefee,
+        None => 0,
+    };
+    if let Some(blob_base_fee) = overrides.blob_base_fee
+        && let Some(blob) = block_env.blob_excess_gas_and_price.as_mut() {
+        blob.blob_gasprice = blob_base_fee.to();
+    }
+    block_env
+}
+
+fn simulated_call_error(result: &ExecutionResult<HaltReason>) -> Option<SimulateCallError> {
+    match result {
+        ExecutionResult::Success { .. } => None,
+        ExecutionResult::Revert { output, .. } => Some(SimulateCallError {
+            code: 3,
+            message: String::from("execution reverted"),
+            data: Some(output.clone()),
+        }),
+        ExecutionResult::Halt { reason, .. } => Some(SimulateCallError {
+            code: -32015,
+            message: format!("VM error: {:?}", reason),
+            data: None,
+        }),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn simulate_call(
+    chain_id: u64,
+    cfg_env: &CfgEnv,
+    block_env: &BlockEnv,
+    db: &mut InMemoryDB,
+    call: &SimulateCall,
+    gas_left: u64,
+    trace_transfers: bool
+) -> Result<(ExecutionResult<HaltReason>, Vec<Log>), String> {
+    let gas_limit = match call.gas {
+        Some(gas) if gas.to::<u64>() > gas_left => {
+            return Err(format!("gas {} above the {} left in the block", gas, gas_left));
+        }
+        Some(gas) => gas.to(),
+        None => gas_left,
+    };
+    let nonce = match call.nonce {
+        Some(nonce) => nonce.to(),
+        None => db.basic(call.from)
+            .map_err(|error| error.to_string())?
+            .map(|info| info.nonce)
+            .unwrap_or(0),
+    };
+    let gas_price = call.gas_price.or(call.max_fee_per_gas).unwrap_or_default().to();
+    let gas_priority_fee = call.max_priority_fee_per_gas.or(call.gas_price).unwrap_or_default().to();
+    let tx = tx_env_builder(
+        chain_id, call.from, nonce, call.to.unwrap_or_default(), call.input.clone(), call.value,
+        gas_limit, gas_price, gas_priority_fee
+    ).kind(call.to.map_or(TxKind::Create, TxKind::Call))
+        .build()
+        .map_err(tx_env_build_error_to_string)?;
+
+    let mut inspector = SimulateLogInspector::new(trace_transfers);
+    let (result, state_diff, _) = inspect_mainnet_transaction(
+        cfg_env.clone(), block_env.clone(), &mut *db, tx, &mut inspector
+    )?;
+    db.commit(state_diff);
+    Ok((result, inspector.logs))
+}
+
+/// Simulates `payload` on the prestate: the blocks run in order on one
+/// database, each after its state overrides, and the calls of a block one
+/// after the other like transactions. Blocks are numbered from the one
+/// after `latest_block_env`; a number gap is filled with empty blocks.
+///
+/// With `validation` the calls are checked like transactions (nonce,
+/// balance, fees), otherwise like `eth_call`. A call that reverts or halts
+/// is reported with an error; an invalid call fails the simulation. The
+/// results are reduced `eth_simulateV1` results, see [`SimulatedBlock`].
+pub fn simulate(
+    chain_id: u64,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    payload: &SimulatePayload
+) -> Result<Vec<SimulatedBlock>, String> {
+    let mut db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+    let cfg_env = match payload.validation {
+        true => CfgEnv::new().with_chain_id(chain_id),
+        false => call_cfg_env(chain_id),
+    };
+    let first_number = latest_block_env.number + U256::from(1);
+    let mut parent = latest_block_env;
+    let mut blocks = Vec::new();
+
+    for (block_index, block) in payload.block_state_calls.iter().enumerate() {
+        let overrides = block.block_overrides.clone().unwrap_or_default();
+        let number = overrides.number.map(U256::from).unwrap_or(parent.number + U256::from(1));
+        if number <= parent.number {
+            return Err(format!(
+                "block {}: number {} not above {}", block_index, number, parent.number
+            ));
+        }
+        if number - first_number >= U256::from(MAX_SIMULATED_BLOCKS) {
+            return Err(format!("block {}: more than {} blocks", block_index, MAX_SIMULATED_BLOCKS));
+        }
+        // empty blocks up to the requested number
+        while parent.number + U256::from(1) < number {
+            parent = simulated_block_env(&parent, &BlockOverrides::default(), payload.validation);
+            blocks.push(simulated_block(&parent, 0, Vec::new()));
+        }
+        let block_env = simulated_block_env(&parent, &overrides, payload.validation);
+        if block_env.timestamp <= parent.timestamp {
+            return Err(format!(
+                "block {}: timestamp {} not above {}", block_index, block_env.timestamp,
+                parent.timestamp
+            ));
+        }
+        if let Some(state_overrides) = &block.state_overrides {
+            apply_state_overrides(&mut db, state_overrides)
+                .map_err(|error| format!("block {}: {}", block_index, error))?;
+        }
+
+        let block_number = U64::from(block_env.number.saturating_to::<u64>());
+        let mut gas_used = 0u64;
+        let mut log_index = 0u64;
+        let mut calls = Vec::with_capacity(block.calls.len());
+        for (call_index, call) in block.calls.iter().enumerate() {
+            let (result, logs) = simulate_call(
+                chain_id, &cfg_env, &block_env, &mut db, call, block_env.gas_limit - gas_used,
+                payload.trace_transfers
+            ).map_err(|error| format!("block {} call {}: {}", block_index, call_index, error))?;
+            gas_used += result.gas_used();
+
+            let logs = match result.is_success() {
+                true => logs,
+                false => Vec::new(),
+            };
+            let logs = logs.into_iter().map(|log| {
+                log_index += 1;
+                SimulatedLog {
+                    address: log.address,
+                    topics: log.topics().to_vec(),
+                    data: log.data.data,
+                    block_number,
+                    transaction_index: U64::from(call_index),
+                    log_index: U64::from(log_index - 1),
+                    removed: false,
+                }
+            }).collect();
+            calls.push(SimulatedCall {
+                return_data: result.output().cloned().unwrap_or_default(),
+                logs,
+                gas_used: U64::from(result.gas_used()),
+                status: U64::from(result.is_success() as u64),
+                error: simulated_call_error(&result),
+            });
+        }
+        blocks.push(simulated_block(&block_env, gas_used, calls));
+        parent = block_env;
+    }
+    Ok(blocks)
+}
+
+fn simulated_block(block_env: &BlockEnv, gas_used: u64, calls: Vec<SimulatedCall>) -> SimulatedBlock {
+    SimulatedBlock {
+        number: U64::from(block_env.number.saturating_to::<u64>()),
+        timestamp: U64::from(block_env.timestamp.saturating_to::<u64>()),
+        gas_limit: U64::from(block_env.gas_limit),
+        gas_used: U64::from(gas_used),
+        miner: block_env.beneficiary,
+        base_fee_per_gas: U64::from(block_env.basefee),
+        mix_hash: block_env.prevrandao.unwrap_or_default(),
+        calls,
+    }
+}
@@ -0,0 +1,261 @@
+use std::ops::Deref;
+
+use revm::bytecode::opcode;
+use revm::context::result::HaltReason;
+use revm::context::{BlockEnv, CfgEnv, ContextTr, JournalEntry, JournalInner};
+use revm::interpreter::interpreter::EthInterpreter;
+use revm::interpreter::interpreter_types::{InputsTr, Jumps};
+use revm::interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter};
+use revm::primitives::{Address, Bytes, HashMap, StorageKey, StorageValue, U256};
+use revm::Inspector;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::database::{create_in_memory_database_from_prestate_trace, AccountDetails};
+use crate::inspector::MyInspector;
+use crate::trace::{
+    inspect_mainnet_transaction, pop_trace_result, tx_env_build_error_to_string, tx_env_builder,
+    TraceOutput
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StorageOp {
+    Sload,
+    Sstore,
+    Tload,
+    Tstore,
+}
+
+/// One storage access. `value` is the value read, or the value replaced by
+/// a write. `original_value` (the value at the start of the transaction)
+/// and `is_cold` are only known for persistent storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageAccess {
+    pub op: StorageOp,
+    pub address: Address,
+    pub slot: StorageKey,
+    pub value: StorageValue,
+    pub original_value: Option<StorageValue>,
+    pub new_value: Option<StorageValue>,
+    pub is_cold: Option<bool>,
+}
+
+/// Accesses made by one frame, in execution order. `frame` is the path of
+/// indexes into `calls` from the root frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameStorageAccesses {
+    pub frame: Vec<usize>,
+    pub accesses: Vec<StorageAccess>,
+}
+
+struct PendingAccess {
+    op: StorageOp,
+    address: Address,
+    slot: StorageKey,
+    // present value and coldness before the access, if the slot was loaded
+    before: Option<(StorageValue, bool)>,
+    new_value: Option<StorageValue>,
+}
+
+/// Inspector recording every SLOAD, SSTORE, TLOAD and TSTORE with the
+/// frame that made it.
+#[derive(Default)]
+pub struct StorageAccessTracer {
+    frames: Vec<FrameStorageAccesses>,
+    // index in `frames` and number of children of every open frame
+    open: Vec<(usize, usize)>,
+    pending: Option<PendingAccess>,
+}
+
+impl StorageAccessTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frames in the order they were entered.
+    pub fn frames(&self) -> &[FrameStorageAccesses] {
+        &self.frames
+    }
+
+    pub fn into_frames(self) -> Vec<FrameStorageAccesses> {
+        self.frames
+    }
+
+    fn enter(&mut self) {
+        let frame = match self.open.last_mut() {
+            Some((parent, children)) => {
+                let mut path = self.frames[*parent].frame.clone();
+                path.push(*children);
+                *children += 1;
+                path
+            }
+            None => Vec::new(),
+        };
+        self.frames.push(FrameStorageAccesses { frame, accesses: Vec::new() });
+        self.open.push((self.frames.len() - 1, 0));
+    }
+
+    fn exit(&mut self) {
+        self.pending = None;
+        self.open.pop();
+    }
+
+    fn push(&mut self, access: StorageAccess) {
+        if let Some((index, _)) = self.open.last() {
+            self.frames[*index].accesses.push(access);
+        }
+    }
+}
+
+fn storage_slot(
+    journal: &JournalInner<JournalEntry>,
+    address: Address,
+    slot: StorageKey
+) -> Option<(StorageValue, StorageValue, bool)> {
+    let slot_state = journal.state.get(&address)?.storage.get(&slot)?;
+    // a slot warmed by an earlier transaction of the journal is cold again
+    let is_cold = slot_state.is_cold || slot_state.transaction_id != journal.transaction_id;
+    Some((slot_state.original_value, slot_state.present_value, is_cold))
+}
+
+impl<CTX> Inspector<CTX, EthInterpreter> for StorageAccessTracer
+where
+    CTX: ContextTr,
+    CTX::Journal: Deref<Target = JournalInner<JournalEntry>>,
+{
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, context: &mut CTX) {
+        let op = match interp.bytecode.opcode() {
+            opcode::SLOAD => StorageOp::Sload,
+            opcode::SSTORE => StorageOp::Sstore,
+            opcode::TLOAD => StorageOp::Tload,
+            opcode::TSTORE => StorageOp::Tstore,
+            _ => return,
+        };
+        let Ok(slot) = interp.stack.peek(0) else {
+            return;
+        };
+        let new_value = match op {
+            StorageOp::Sstore | StorageOp::Tstore => match interp.stack.peek(1) {
+                Ok(value) => Some(value),
+                Err(_) => return,
+            },
+            _ => None,
+        };
+        let address = interp.input.target_address();
+        let journal: &JournalInner<JournalEntry> = context.journal_ref();
+        let before = match op {
+            StorageOp::Sload | StorageOp::Sstore => storage_slot(journal, address, slot)
+                .map(|(_, present, is_cold)| (present, is_cold)),
+            StorageOp::Tload | StorageOp::Tstore => Some((
+                journal.transient_storage.get(&(address, slot)).copied().unwrap_or_default(),
+                false
+            )),
+        };
+        self.pending = Some(PendingAccess { op, address, slot, before, new_value });
+    }
+
+    fn step_end(&mut self, _interp: &mut Interpreter<EthInterpreter>, context: &mut CTX) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let journal: &JournalInner<JournalEntry> = context.journal_ref();
+        let access = match pending.op {
+            StorageOp::Sload | StorageOp::Sstore => {
+                // not loaded means the opcode failed before touching the slot
+                let Some((original, present, _)) = storage_slot(journal, pending.address, pending.slot) else {
+                    return;
+                };
+                let (value, is_cold) = pending.before.unwrap_or((original, true));
+                StorageAccess {
+                    op: pending.op,
+                    address: pending.address,
+                    slot: pending.slot,
+                    value: if pending.op == StorageOp::Sload { present } else { value },
+                    original_value: Some(original),
+                    new_value: pending.new_value,
+                    is_cold: Some(is_cold),
+                }
+            }
+            StorageOp::Tload | StorageOp::Tstore => StorageAccess {
+                op: pending.op,
+                address: pending.address,
+                slot: pending.slot,
+                value: pending.before.map(|(value, _)| value).unwrap_or(U256::ZERO),
+                original_value: None,
+                new_value: pending.new_value,
+                is_cold: None,
+            },
+        };
+        self.push(access);
+    }
+
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.enter();
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, _outcome: &mut CallOutcome) {
+        self.exit();
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.enter();
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut CTX, _inputs: &CreateInputs, _outcome: &mut CreateOutcome) {
+        self.exit();
+    }
+}
+
+fn frame_at<'a>(trace: &'a mut Value, path: &[usize]) -> Option<&'a mut Value> {
+    match path.split_first() {
+        Some((index, rest)) => frame_at(trace.get_mut("calls")?.get_mut(*index)?, rest),
+        None => Some(trace),
+    }
+}
+
+/// Adds the accesses of every frame to the trace as `storageAccesses`.
+pub fn annotate_storage_accesses(trace: &mut Value, frames: &[FrameStorageAccesses]) {
+    for frame in frames.iter().filter(|frame| !frame.accesses.is_empty()) {
+        if let Some(trace_frame) = frame_at(trace, &frame.frame) {
+            trace_frame["storageAccesses"] = json!(frame.accesses);
+        }
+    }
+}
+
+/// [`trace_transaction`](crate::trace::trace_transaction) with the storage
+/// accesses of every frame added to the trace.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction_with_storage_accesses(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<TraceOutput<HaltReason>, String> {
+    let tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value, gas_limit, gas_price, gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+    let db = create_in_memory_database_from_prestate_trace(prestate_tracer_result);
+
+    let buffer = &mut Vec::new();
+    let mut storage_tracer = StorageAccessTracer::new();
+    let inspector = (MyInspector::new(buffer), &mut storage_tracer);
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (result, state, _) = inspect_mainnet_transaction(
+        cfg_env, latest_block_env, db, tx, inspector
+    )?;
+    let mut trace = pop_trace_result(buffer);
+    annotate_storage_accesses(&mut trace, storage_tracer.frames());
+    Ok((result, state, trace))
+}
@@ -7,13 +7,15 @@ use op_revm::OpSpecId;
 use op_revm::OpTransaction;
 use revm::context::result::ExecutionResult;
 use revm::context::result::HaltReason;
-use revm::context::tx::TxEnvBuildError;
+use revm::context::tx::{TxEnvBuildError, TxEnvBuilder};
 use revm::context::BlockEnv;
 use revm::context::CfgEnv;
 use revm::context::JournalTr;
 use revm::context::LocalContext;
 use revm::handler::instructions::EthInstructions;
 use revm::handler::EthPrecompiles;
+use revm::handler::MainnetContext;
+use revm::interpreter::interpreter::EthInterpreter;
 use revm::primitives::HashMap;
 use revm::primitives::TxKind;
 use revm::primitives::B256;
@@ -22,6 +24,9 @@ use revm::ExecuteEvm;
 use revm::Journal;
 use revm::MainnetEvm;
 use revm::InspectEvm;
+use revm::Inspector;
+use revm::Database;
+use revm::state::EvmState;
 
 use serde_json::Value;
 use std::str::FromStr;
@@ -33,10 +38,27 @@ use revm::{
 };
 
 use crate::database::create_in_memory_database_from_prestate_trace;
+use crate::database::create_strict_database_from_prestate_trace;
 use crate::database::AccountDetails;
+use crate::database::MissingState;
+use crate::database::StrictMode;
 use crate::inspector::MyInspector;
 
-pub fn trace_transaction(
+pub type TraceOutput<HaltReasonTy> = (
+    ExecutionResult<HaltReasonTy>,
+    HashMap<Address, revm::state::Account>, //state diff
+    Value  //tracer result
+);
+
+pub type StrictTraceOutput<HaltReasonTy> = (
+    ExecutionResult<HaltReasonTy>,
+    HashMap<Address, revm::state::Account>, //state diff
+    Value,  //tracer result
+    Vec<MissingState> //state read but not in the prestate
+);
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn tx_env_builder(
     chain_id: u64,
     from: Address,
     from_nonce: u64,
@@ -46,17 +68,8 @@ pub fn trace_transaction(
     gas_limit: u64,
     gas_price: u128,
     gas_priority_fee: u128,
-    latest_block_env: BlockEnv,
-    prestate_tracer_result: HashMap<Address, AccountDetails>
-) -> Result<
-        (
-            ExecutionResult<HaltReason>,
-            HashMap<Address, revm::state::Account>, //state diff
-            Value  //tracer result
-        ),
-        String // error
-> {
-    let tx_env_build = TxEnv::builder()
+) -> TxEnvBuilder {
+    TxEnv::builder()
         .chain_id(Some(chain_id))
         .caller(from)
         .kind(TxKind::Call(to))
@@ -66,62 +79,75 @@ pub fn trace_transaction(
         .gas_priority_fee(Some(gas_priority_fee))
         .data(data)
         .value(value)
-        .build();
+}
 
-    let tx: TxEnv;
-    match tx_env_build{
-        Ok(result) => {tx = result}
-        Err(error) => {
-            match error {
-                TxEnvBuildError::DeriveErr(_) => {
-                    return Err(String::from_str(
-                            "TxEnvBuildError: Derive Error"
-                        ).unwrap()
-                    )
-                }
-                TxEnvBuildError::MissingGasPriorityFeeForEip1559 => {
-                    return Err(
-                        String::from_str(
-                            "TxEnvBuildError: MissingGasPriorityFeeForEip1559"
-                        ).unwrap()
-                    )
-                }
-                TxEnvBuildError::MissingTargetForEip4844 => {
-                    return Err(
-                        String::from_str(
-                            "TxEnvBuildError: MissingTargetForEip4844"
-                        ).unwrap()
-                    )
-                }
-                TxEnvBuildError::MissingAuthorizationListForEip7702 => {
-                    return Err(
-                        String::from_str(
-                            "TxEnvBuildError: MissingAuthorizationListForEip7702"
-                        ).unwrap()
-                    )
-                }
-                TxEnvBuildError::MissingBlobHashesForEip4844 => {
-                    return Err(
-                        String::from_str(
-                            "TxEnvBuildError: MissingBlobHashesForEip4844"
-                        ).unwrap()
-                    )
-                }
-            };
+pub(crate) fn tx_env_build_error_to_string(error: TxEnvBuildError) -> String {
+    match error {
+        TxEnvBuildError::DeriveErr(_) => {
+            String::from_str("TxEnvBuildError: Derive Error").unwrap()
         }
-    };
-    
-    let buffer = &mut Vec::new();
-    let inspector = MyInspector::new(buffer);
+        TxEnvBuildError::MissingGasPriorityFeeForEip1559 => {
+            String::from_str(
+                "TxEnvBuildError: MissingGasPriorityFeeForEip1559"
+            ).unwrap()
+        }
+        TxEnvBuildError::MissingTargetForEip4844 => {
+            String::from_str(
+                "TxEnvBuildError: MissingTargetForEip4844"
+            ).unwrap()
+        }
+        TxEnvBuildError::MissingAuthorizationListForEip7702 => {
+            String::from_str(
+                "TxEnvBuildError: MissingAuthorizationListForEip7702"
+            ).unwrap()
+        }
+        TxEnvBuildError::MissingBlobHashesForEip4844 => {
+            String::from_str(
+                "TxEnvBuildError: MissingBlobHashesForEip4844"
+            ).unwrap()
+        }
+    }
+}
 
-    let db:InMemoryDB = create_in_memory_database_from_prestate_trace(
-        prestate_tracer_result
-    );
+pub(crate) fn pop_trace_result(buffer: &mut Vec<(u64, Value)>) -> Value {
+    match buffer.pop() {
+        Some(result) => {
+            assert!(
+                buffer.is_empty(),
+                "invalid stack buffer result. should only have one element."
+            );
+            assert!(
+                result.0 == 1,
+                "invalid stack buffer result. stack depth should be 1."
+            );
 
-    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+            result.1
+        }
+        None => {
+            //can happend with failed execution
+            Value::Null
+        }
+    }
+}
+
+/// Executes `tx` on top of `db` with the mainnet handler and the given inspector.
+/// Returns the execution result, the state diff and the database so callers
+/// can keep building on it.
+pub(crate) fn inspect_mainnet_transaction<DB, INSP>(
+    cfg_env: CfgEnv,
+    block_env: BlockEnv,
+    db: DB,
+    tx: TxEnv,
+    inspector: INSP
+) -> Result<(ExecutionResult<HaltReason>, EvmState, DB), String>
+where
+    DB: Database,
+    DB::Error: std::fmt::Display,
+    INSP: Inspector<MainnetContext<DB>, EthInterpreter>,
+{
     let context = Context::mainnet().with_db(db).
         with_cfg(cfg_env).
-        with_block(latest_block_env);
+        with_block(block_env);
 
     let mut my_evm = MainnetEvm::new_with_inspector(
         context,
@@ -134,29 +160,181 @@ pub fn trace_transaction(
         Err(error) => {return Err(error.to_string())}
     };
     let state_diff = my_evm.finalize();
-    let trace_result;
-    match buffer.pop() {
-        Some(result) => {
-            assert!(
-                buffer.len() == 0,
-                "invalid stack buffer result. should only have one element."
-            );
-            assert!(
-                result.0 == 1,
-                "invalid stack buffer result. stack depth should be 1."
-            );
+    Ok((execution_result, state_diff, my_evm.ctx.journaled_state.database))
+}
 
-            trace_result = result.1
+pub(crate) fn op_build_error_to_string(error: OpBuildError) -> String {
+    match error {
+        OpBuildError::Base(_) => {
+            String::from_str("OPTxEnvBuildError: Base").unwrap()
         }
-        None => {
-            //can happend with failed execution
-            trace_result = Value::Null;
+        OpBuildError::MissingEnvelopedTxBytes => {
+            String::from_str(
+                "OPTxEnvBuildError: MissingEnvelopedTxBytes"
+            ).unwrap()
+        }
+        OpBuildError::MissingSourceHashForDeposit => {
+            String::from_str(
+                "OPTxEnvBuildError: MissingSourceHashForDeposit"
+            ).unwrap()
         }
     }
+}
+
+/// Executes `tx` on top of `db` with the Optimism handler at `op_spec`. The
+/// L1 block info is read from the L1Block contract in `db`, the journal
+/// follows the spec of `cfg_env`.
+pub(crate) fn inspect_op_transaction<INSP>(
+    cfg_env: CfgEnv,
+    op_spec: OpSpecId,
+    block_env: BlockEnv,
+    db: InMemoryDB,
+    tx: OpTransaction<TxEnv>,
+    inspector: INSP
+) -> Result<(ExecutionResult<OpHaltReason>, EvmState), String>
+where
+    INSP: Inspector<OpContext<InMemoryDB>, EthInterpreter>,
+{
+    let mut chain = L1BlockInfo::default();
+    if op_spec == OpSpecId::ISTHMUS {
+        chain.operator_fee_constant = Some(U256::from(0));
+        chain.operator_fee_scalar = Some(U256::from(0));
+    }
+    let spec = cfg_env.spec;
+    let op_cfg = cfg_env.with_spec(op_spec);
+
+    let op_context = OpContext {
+        journaled_state: {
+            let mut journal = Journal::new(db);
+            // Converting SpecId into OpSpecId
+            journal.set_spec_id(spec);
+            journal
+        },
+        block: block_env,
+        cfg: op_cfg,
+        tx: OpTransaction::default(),
+        chain,
+        local: LocalContext::default(),
+        error: Ok(()),
+    };
+
+    let mut my_evm = OpEvm::new(op_context, inspector);
+    let execution_result = match my_evm.inspect_one_tx(tx){
+        Ok(result) => {result},
+        Err(error) => {return Err(error.to_string())}
+    };
+    let state_diff = my_evm.finalize();
+    Ok((execution_result, state_diff))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>
+) -> Result<
+        TraceOutput<HaltReason>,
+        String // error
+> {
+    let db:InMemoryDB = create_in_memory_database_from_prestate_trace(
+        prestate_tracer_result
+    );
+    trace_transaction_with_database(
+        chain_id, from, from_nonce, to, data, value,
+        gas_limit, gas_price, gas_priority_fee, latest_block_env, db
+    )
+}
+
+/// [`trace_transaction`] that also reports every account and storage slot
+/// read during execution but missing from `prestate_tracer_result`. With
+/// [`StrictMode::Fail`] the first such read aborts the trace with an error.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction_strict(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    prestate_tracer_result: HashMap<Address, AccountDetails>,
+    mode: StrictMode
+) -> Result<
+        StrictTraceOutput<HaltReason>,
+        String // error
+> {
+    let tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value,
+        gas_limit, gas_price, gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+
+    let buffer = &mut Vec::new();
+    let inspector = MyInspector::new(buffer);
+    let db = create_strict_database_from_prestate_trace(
+        prestate_tracer_result, mode
+    );
+
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (execution_result, state_diff, db) = inspect_mainnet_transaction(
+        cfg_env, latest_block_env, db, tx, inspector
+    )?;
+    let trace_result = pop_trace_result(buffer);
+
+    Ok((execution_result, state_diff, trace_result, db.db.missing()))
+}
+
+/// [`trace_transaction`] on any database, e.g. a
+/// [`LazyDatabase`](crate::lazy_database::LazyDatabase).
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction_with_database<DB>(
+    chain_id: u64,
+    from: Address,
+    from_nonce: u64,
+    to: Address,
+    data: Bytes,
+    value: U256,
+    gas_limit: u64,
+    gas_price: u128,
+    gas_priority_fee: u128,
+    latest_block_env: BlockEnv,
+    db: DB
+) -> Result<
+        TraceOutput<HaltReason>,
+        String // error
+>
+where
+    DB: Database,
+    DB::Error: std::fmt::Display,
+{
+    let tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value,
+        gas_limit, gas_price, gas_priority_fee
+    ).build().map_err(tx_env_build_error_to_string)?;
+    
+    let buffer = &mut Vec::new();
+    let inspector = MyInspector::new(buffer);
+
+    let cfg_env = CfgEnv::new().with_chain_id(chain_id);
+    let (execution_result, state_diff, _) = inspect_mainnet_transaction(
+        cfg_env, latest_block_env, db, tx, inspector
+    )?;
+    let trace_result = pop_trace_result(buffer);
 
     Ok((execution_result, state_diff, trace_result))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn op_trace_transaction(
     chain_id: u64,
     from: Address,
@@ -170,23 +348,13 @@ pub fn op_trace_transaction(
     latest_block_env: BlockEnv,
     prestate_tracer_result: HashMap<Address, AccountDetails>
 ) -> Result<
-        (
-            ExecutionResult<OpHaltReason>,
-            HashMap<Address, revm::state::Account>, //state diff
-            Value  //tracer result
-        ),
+        TraceOutput<OpHaltReason>,
         String // error
 > {
-    let base_tx = TxEnv::builder()
-        .chain_id(Some(chain_id))
-        .caller(from)
-        .kind(TxKind::Call(to))
-        .nonce(from_nonce)
-        .gas_limit(gas_limit)
-        .gas_price(gas_price)
-        .gas_priority_fee(Some(gas_priority_fee))
-        .data(data)
-        .value(value);
+    let base_tx = tx_env_builder(
+        chain_id, from, from_nonce, to, data, value,
+        gas_limit, gas_price, gas_priority_fee
+    );
     let op_tx_build = OpTransaction::builder()
         .base(base_tx)
         .enveloped_tx(None)
@@ -195,87 +363,18 @@ pub fn op_trace_transaction(
         .source_hash(B256::from([1u8; 32]))
         .build();
 
-    let op_tx; 
-    match op_tx_build{
-        Ok(result) => {op_tx = result}
-        Err(error) => {
-            match error {
-                OpBuildError::Base(_) => {
-                    return Err(String::from_str("OPTxEnvBuildError: Base").unwrap())
-                }
-                OpBuildError::MissingEnvelopedTxBytes => {
-                    return Err(
-                        String::from_str(
-                            "OPTxEnvBuildError: MissingEnvelopedTxBytes"
-                        ).unwrap()
-                    )
-                }
-                OpBuildError::MissingSourceHashForDeposit => {
-                    return Err(
-                        String::from_str(
-                            "OPTxEnvBuildError: MissingSourceHashForDeposit"
-                        ).unwrap()
-                    )
-                }
-            };
-        }
-    };
+    let op_tx = op_tx_build.map_err(op_build_error_to_string)?;
 
     let cfg_env = CfgEnv::new().with_chain_id(chain_id);
-    let op_spec = OpSpecId::default();
-    let mut chain = L1BlockInfo::default();
-    if op_spec == OpSpecId::ISTHMUS {
-        chain.operator_fee_constant = Some(U256::from(0));
-        chain.operator_fee_scalar = Some(U256::from(0));
-    }
-    let op_cfg = cfg_env.clone().with_spec(op_spec);
-
     let buffer = &mut Vec::new();
     let inspector = MyInspector::new(buffer);
     let db:InMemoryDB = create_in_memory_database_from_prestate_trace(
         prestate_tracer_result
     );
-
-    let op_context = OpContext {
-        journaled_state: {
-            let mut journal = Journal::new(db);
-            // Converting SpecId into OpSpecId
-            journal.set_spec_id(cfg_env.spec);
-            journal
-        },
-        block: latest_block_env,
-        cfg: op_cfg,
-        tx: OpTransaction::default(),
-        chain,
-        local: LocalContext::default(),
-        error: Ok(()),
-    };
-
-    let mut my_evm = OpEvm::new(op_context, inspector);
-    let execution_result = match my_evm.inspect_one_tx(op_tx){
-        Ok(result) => {result},
-        Err(error) => {return Err(error.to_string())}
-    };
-    let state_diff = my_evm.finalize();
-    let trace_result;
-    match buffer.pop() {
-        Some(result) => {
-            assert!(
-                buffer.len() == 0,
-                "invalid stack buffer result. should only have one element."
-            );
-            assert!(
-                result.0 == 1,
-                "invalid stack buffer result. stack depth should be 1."
-            );
-
-            trace_result = result.1
-        }
-        None => {
-            //can happend with failed execution
-            trace_result = Value::Null;
-        }
-    }
+    let (execution_result, state_diff) = inspect_op_transaction(
+        cfg_env, OpSpecId::default(), latest_block_env, db, op_tx, inspector
+    )?;
+    let trace_result = pop_trace_result(buffer);
 
     Ok((execution_result, state_diff, trace_result))
 }
@@ -0,0 +1,114 @@
+use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
+use op_revm::OpTransaction;
+use revm::context::transaction::{AccessList, SignedAuthorization};
+use revm::context::tx::TxEnvBuilder;
+use revm::context::TxEnv;
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::primitives::{Address, Bytes, TxKind, B256, U256};
+use serde::Deserialize;
+
+use crate::json_rpc::JsonRpcResponse;
+use crate::trace::{op_build_error_to_string, tx_env_build_error_to_string};
+
+/// Transaction object of `eth_getTransactionByHash`, also found in blocks
+/// fetched with full transactions. `gas_price` is the effective gas price
+/// for mined EIP-1559 transactions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionDetails {
+    pub hash: B256,
+    #[serde(rename = "type", default)]
+    pub tx_type: U64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub input: Bytes,
+    pub value: U256,
+    pub gas: U64,
+    pub nonce: U64,
+    pub chain_id: Option<U64>,
+    pub gas_price: Option<U128>,
+    pub max_fee_per_gas: Option<U128>,
+    pub max_priority_fee_per_gas: Option<U128>,
+    pub access_list: Option<AccessList>,
+    pub max_fee_per_blob_gas: Option<U128>,
+    pub blob_versioned_hashes: Option<Vec<B256>>,
+    pub authorization_list: Option<Vec<SignedAuthorization>>,
+    /// OP deposit transactions only.
+    pub source_hash: Option<B256>,
+    pub mint: Option<U128>,
+    pub is_system_tx: Option<bool>,
+    pub block_hash: Option<B256>,
+    pub block_number: Option<U64>,
+    pub transaction_index: Option<U64>,
+}
+pub type GetTransactionByHashResponse = JsonRpcResponse<TransactionDetails>;
+
+fn tx_env_builder_from_transaction_details(
+    transaction: &TransactionDetails
+) -> Result<TxEnvBuilder, String> {
+    let tx_type: u8 = transaction.tx_type.try_into()
+        .map_err(|_| format!("unsupported transaction type {}", transaction.tx_type))?;
+    let mut builder = TxEnv::builder()
+        .tx_type(Some(tx_type))
+        .chain_id(transaction.chain_id.map(|chain_id| chain_id.to()))
+        .caller(transaction.from)
+        .kind(transaction.to.map_or(TxKind::Create, TxKind::Call))
+        .nonce(transaction.nonce.to())
+        .gas_limit(transaction.gas.to())
+        .data(transaction.input.clone())
+        .value(transaction.value);
+
+    // from EIP-1559 on the fee cap is what the sender signed, deposits
+    // have no gas price
+    let gas_price = match tx_type {
+        DEPOSIT_TRANSACTION_TYPE => transaction.gas_price.unwrap_or_default(),
+        _ => transaction.max_fee_per_gas.or(transaction.gas_price)
+            .ok_or(String::from("transaction has no gas price"))?,
+    };
+    builder = builder.gas_price(gas_price.to())
+        .gas_priority_fee(transaction.max_priority_fee_per_gas.map(|fee| fee.to()));
+    if let Some(access_list) = &transaction.access_list {
+        builder = builder.access_list(access_list.clone());
+    }
+    if let Some(blob_hashes) = &transaction.blob_versioned_hashes {
+        builder = builder.blob_hashes(blob_hashes.clone());
+    }
+    if let Some(max_fee_per_blob_gas) = transaction.max_fee_per_blob_gas {
+        builder = builder.max_fee_per_blob_gas(max_fee_per_blob_gas.to());
+    }
+    if let Some(authorization_list) = &transaction.authorization_list {
+        builder = builder.authorization_list_signed(authorization_list.clone());
+    }
+    Ok(builder)
+}
+
+pub fn create_tx_env_from_transaction_details(
+    transaction: &TransactionDetails
+) -> Result<TxEnv, String> {
+    tx_env_builder_from_transaction_details(transaction)?
+        .build()
+        .map_err(tx_env_build_error_to_string)
+}
+
+/// OP Stack transaction. `enveloped_tx` is the raw signed transaction
+/// (`eth_getRawTransactionByHash`), which the L1 data fee is computed from;
+/// deposits have none.
+pub fn create_op_transaction_from_transaction_details(
+    transaction: &TransactionDetails,
+    enveloped_tx: Option<Bytes>
+) -> Result<OpTransaction<TxEnv>, String> {
+    let mut builder = OpTransaction::builder()
+        .base(tx_env_builder_from_transaction_details(transaction)?);
+    if transaction.tx_type == U64::from(DEPOSIT_TRANSACTION_TYPE) {
+        let source_hash = transaction.source_hash
+            .ok_or(String::from("deposit transaction has no source hash"))?;
+        builder = builder.source_hash(source_hash)
+            .mint(transaction.mint.unwrap_or_default().to());
+        if transaction.is_system_tx.unwrap_or(false) {
+            builder = builder.is_system_transaction();
+        }
+    } else {
+        builder = builder.enveloped_tx(enveloped_tx);
+    }
+    builder.build().map_err(op_build_error_to_string)
+}
@@ -0,0 +1,224 @@
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use revm::primitives::{b256, Address, Log, B256, U256};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::abi::frame_logs;
+use crate::revert::is_failed_frame;
+
+/// `Transfer(address,address,uint256)`, shared by ERC-20 and ERC-721.
+pub const TRANSFER_TOPIC: B256 =
+    b256!("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+/// `Approval(address,address,uint256)`, shared by ERC-20 and ERC-721.
+pub const APPROVAL_TOPIC: B256 =
+    b256!("0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925");
+/// `ApprovalForAll(address,address,bool)`, shared by ERC-721 and ERC-1155.
+pub const APPROVAL_FOR_ALL_TOPIC: B256 =
+    b256!("0x17307eab39ab6107e8899845ad3d59bd9653f200f220920489ca2b5937696c31");
+/// `TransferSingle(address,address,address,uint256,uint256)`
+pub const TRANSFER_SINGLE_TOPIC: B256 =
+    b256!("0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62");
+/// `TransferBatch(address,address,address,uint256[],uint256[])`
+pub const TRANSFER_BATCH_TOPIC: B256 =
+    b256!("0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStandard {
+    Erc20,
+    Erc721,
+    Erc1155,
+}
+
+/// A token movement or approval. ERC-721 transfers move an `amount` of one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AssetMovement {
+    Transfer {
+        token: Address,
+        standard: TokenStandard,
+        operator: Option<Address>,
+        from: Address,
+        to: Address,
+        id: Option<U256>,
+        amount: U256,
+    },
+    Approval {
+        token: Address,
+        standard: TokenStandard,
+        owner: Address,
+        spender: Address,
+        id: Option<U256>,
+        amount: Option<U256>,
+    },
+    ApprovalForAll {
+        token: Address,
+        owner: Address,
+        operator: Address,
+        approved: bool,
+    },
+}
+
+/// An [`AssetMovement`] with the frame that emitted it: `frame` is the path
+/// of indexes into `calls` from the root frame, `log_index` the position of
+/// the log in the frame `logs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameAssetMovement {
+    pub frame: Vec<usize>,
+    pub log_index: usize,
+    pub movement: AssetMovement,
+}
+
+fn topic_address(topic: &B256) -> Address {
+    Address::from_word(*topic)
+}
+
+fn word(data: &[u8], index: usize) -> Option<U256> {
+    data.get(index * 32..(index + 1) * 32).map(U256::from_be_slice)
+}
+
+/// Interprets a token event. A `TransferBatch` gives one transfer per id;
+/// logs that only share a topic with a token event (e.g. a `Transfer` with
+/// an unexpected layout) give nothing.
+pub fn decode_asset_movements(log: &Log) -> Vec<AssetMovement> {
+    let topics = log.topics();
+    let data = &log.data.data[..];
+    let token = log.address;
+    let Some(topic0) = topics.first() else {
+        return Vec::new();
+    };
+
+    let movement = match (*topic0, topics.len(), data.len()) {
+        (TRANSFER_TOPIC, 3, 32) => AssetMovement::Transfer {
+            token,
+            standard: TokenStandard::Erc20,
+            operator: None,
+            from: topic_address(&topics[1]),
+            to: topic_address(&topics[2]),
+            id: None,
+            amount: U256::from_be_slice(data),
+        },
+        (TRANSFER_TOPIC, 4, 0) => AssetMovement::Transfer {
+            token,
+            standard: TokenStandard::Erc721,
+            operator: None,
+            from: topic_address(&topics[1]),
+            to: topic_address(&topics[2]),
+            id: Some(topics[3].into()),
+            amount: U256::from(1),
+        },
+        (APPROVAL_TOPIC, 3, 32) => AssetMovement::Approval {
+            token,
+            standard: TokenStandard::Erc20,
+            owner: topic_address(&topics[1]),
+            spender: topic_address(&topics[2]),
+            id: None,
+            amount: Some(U256::from_be_slice(data)),
+        },
+        (APPROVAL_TOPIC, 4, 0) => AssetMovement::Approval {
+            token,
+            standard: TokenStandard::Erc721,
+            owner: topic_address(&topics[1]),
+            spender: topic_address(&topics[2]),
+            id: Some(topics[3].into()),
+            amount: None,
+        },
+        (APPROVAL_FOR_ALL_TOPIC, 3, 32) => AssetMovement::ApprovalForAll {
+            token,
+            owner: topic_address(&topics[1]),
+            operator: topic_address(&topics[2]),
+            approved: !U256::from_be_slice(data).is_zero(),
+        },
+        (TRANSFER_SINGLE_TOPIC, 4, 64) => AssetMovement::Transfer {
+            token,
+            standard: TokenStandard::Erc1155,
+            operator: Some(topic_address(&topics[1])),
+            from: topic_address(&topics[2]),
+            to: topic_address(&topics[3]),
+            id: word(data, 0),
+            amount: word(data, 1).unwrap_or_default(),
+        },
+        (TRANSFER_BATCH_TOPIC, 4, _) => {
+            return decode_transfer_batch(token, topics, data).unwrap_or_default();
+        }
+        _ => return Vec::new(),
+    };
+    vec![movement]
+}
+
+fn decode_transfer_batch(token: Address, topics: &[B256], data: &[u8]) -> Option<Vec<AssetMovement>> {
+    let ty = DynSolType::Tuple(vec![
+        DynSolType::Array(Box::new(DynSolType::Uint(256))),
+        DynSolType::Array(Box::new(DynSolType::Uint(256))),
+    ]);
+    let DynSolValue::Tuple(values) = ty.abi_decode_params(data).ok()? else {
+        return None;
+    };
+    let [ids, amounts] = values.as_slice() else {
+        return None;
+    };
+    let (ids, amounts) = (ids.as_array()?, amounts.as_array()?);
+    if ids.len() != amounts.len() {
+        return None;
+    }
+    ids.iter().zip(amounts.iter()).map(|(id, amount)| {
+        Some(AssetMovement::Transfer {
+            token,
+            standard: TokenStandard::Erc1155,
+            operator: Some(topic_address(&topics[1])),
+            from: topic_address(&topics[2]),
+            to: topic_address(&topics[3]),
+            id: Some(id.as_uint()?.0),
+            amount: amount.as_uint()?.0,
+        })
+    }).collect()
+}
+
+fn collect_frame(frame: &Value, path: &mut Vec<usize>, movements: &mut Vec<FrameAssetMovement>) {
+    // state changes of a failed frame are undone along with its subtree
+    if is_failed_frame(frame) {
+        return;
+    }
+    for (log_index, log) in frame_logs(frame).iter().enumerate() {
+        for movement in decode_asset_movements(log) {
+            movements.push(FrameAssetMovement { frame: path.clone(), log_index, movement });
+        }
+    }
+    if let Some(calls) = frame["calls"].as_array() {
+        for (index, call) in calls.iter().enumerate() {
+            path.push(index);
+            collect_frame(call, path, movements);
+            path.pop();
+        }
+    }
+}
+
+/// Token transfers and approvals of a trace, from the frames that did not
+/// revert or halt and whose callers did not either.
+pub fn extract_asset_movements(trace: &Value) -> Vec<FrameAssetMovement> {
+    let mut movements = Vec::new();
+    if trace.is_object() {
+        collect_frame(trace, &mut Vec::new(), &mut movements);
+    }
+    movements
+}
+
+/// Adds to every frame of a trace the `assetMovements` its logs describe.
+/// Reverted or halted frames and their subtrees get none.
+pub fn annotate_asset_movements(trace: &mut Value) {
+    if !trace.is_object() || is_failed_frame(trace) {
+        return;
+    }
+    let movements: Vec<AssetMovement> = frame_logs(trace).iter()
+        .flat_map(decode_asset_movements)
+        .collect();
+    if !movements.is_empty() {
+        trace["assetMovements"] = json!(movements);
+    }
+    if let Some(calls) = trace.get_mut("calls").and_then(Value::as_array_mut) {
+        for call in calls.iter_mut() {
+            annotate_asset_movements(call);
+        }
+    }
+}
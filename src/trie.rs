@@ -0,0 +1,157 @@
+use alloy_rlp::Header;
+use revm::primitives::{b256, keccak256, Bytes, HashMap, B256};
+
+/// Root of an empty Merkle Patricia trie, `keccak256(rlp(""))`.
+pub const EMPTY_ROOT_HASH: B256 =
+    b256!("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+pub(crate) enum NodeRef<'a> {
+    Empty,
+    Hash(B256),
+    Inline(&'a [u8]),
+}
+
+/// Splits an RLP list into the raw encodings of its items. The list must
+/// span the whole of `data`.
+pub(crate) fn rlp_list_items(data: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let mut buf = data;
+    let header = Header::decode(&mut buf).map_err(|error| error.to_string())?;
+    if !header.list {
+        return Err(String::from("expected an RLP list"));
+    }
+    if buf.len() < header.payload_length {
+        return Err(String::from("input too short"));
+    }
+    if buf.len() > header.payload_length {
+        return Err(String::from("trailing bytes after RLP list"));
+    }
+    let mut payload = buf;
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let item_start = payload;
+        let item_header = Header::decode(&mut payload).map_err(|error| error.to_string())?;
+        if payload.len() < item_header.payload_length {
+            return Err(String::from("input too short"));
+        }
+        payload = &payload[item_header.payload_length..];
+        items.push(&item_start[..item_start.len() - payload.len()]);
+    }
+    Ok(items)
+}
+
+/// Payload of an RLP string.
+pub(crate) fn rlp_string(item: &[u8]) -> Result<&[u8], String> {
+    let mut buf = item;
+    Header::decode_bytes(&mut buf, false).map_err(|error| error.to_string())
+}
+
+pub(crate) fn node_ref(item: &[u8]) -> Result<NodeRef<'_>, String> {
+    let mut buf = item;
+    let header = Header::decode(&mut buf).map_err(|error| error.to_string())?;
+    if header.list {
+        return Ok(NodeRef::Inline(item));
+    }
+    let payload = rlp_string(item)?;
+    match payload.len() {
+        0 => Ok(NodeRef::Empty),
+        32 => Ok(NodeRef::Hash(B256::from_slice(payload))),
+        length => Err(format!("invalid trie node reference of {} bytes", length)),
+    }
+}
+
+pub(crate) fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// Decodes a hex-prefix encoded path into `(is_leaf, nibbles)`.
+pub(crate) fn decode_hex_prefix(encoded: &[u8]) -> Result<(bool, Vec<u8>), String> {
+    let Some(first) = encoded.first() else {
+        return Err(String::from("empty hex-prefix path"));
+    };
+    let flag = first >> 4;
+    let mut path = Vec::with_capacity(encoded.len() * 2);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(nibbles(&encoded[1..]));
+    Ok((flag & 2 == 2, path))
+}
+
+pub(crate) enum TrieLookup {
+    Found(Vec<u8>),
+    Absent,
+    MissingNode(B256),
+}
+
+/// Looks `keccak256(key)` up in the trie rooted at `root`, resolving hashed
+/// nodes through `nodes`.
+pub(crate) fn lookup(
+    root: B256,
+    key: &[u8],
+    nodes: &HashMap<B256, &[u8]>
+) -> Result<TrieLookup, String> {
+    let path = nibbles(keccak256(key).as_slice());
+    let mut expected = NodeRef::Hash(root);
+    let mut depth = 0;
+    loop {
+        let node: &[u8] = match expected {
+            NodeRef::Empty => return Ok(TrieLookup::Absent),
+            NodeRef::Hash(hash) if hash == EMPTY_ROOT_HASH => return Ok(TrieLookup::Absent),
+            NodeRef::Hash(hash) => match nodes.get(&hash) {
+                Some(node) => node,
+                None => return Ok(TrieLookup::MissingNode(hash)),
+            },
+            NodeRef::Inline(node) => node,
+        };
+
+        let items = rlp_list_items(node)?;
+        match items.len() {
+            17 => {
+                if depth == path.len() {
+                    let value = rlp_string(items[16])?;
+                    if value.is_empty() {
+                        return Ok(TrieLookup::Absent);
+                    }
+                    return Ok(TrieLookup::Found(value.to_vec()));
+                }
+                expected = node_ref(items[path[depth] as usize])?;
+                depth += 1;
+            }
+            2 => {
+                let (is_leaf, segment) = decode_hex_prefix(rlp_string(items[0])?)?;
+                let rest = &path[depth..];
+                if is_leaf {
+                    if rest != segment.as_slice() {
+                        return Ok(TrieLookup::Absent);
+                    }
+                    return Ok(TrieLookup::Found(rlp_string(items[1])?.to_vec()));
+                }
+                if !rest.starts_with(&segment) {
+                    return Ok(TrieLookup::Absent);
+                }
+                depth += segment.len();
+                expected = node_ref(items[1])?;
+            }
+            count => return Err(format!("invalid trie node with {} items", count)),
+        }
+    }
+}
+
+/// Indexes trie nodes by their hash.
+pub(crate) fn node_map(nodes: &[Bytes]) -> HashMap<B256, &[u8]> {
+    nodes.iter().map(|node| (keccak256(node), &node[..])).collect()
+}
+
+/// Verifies `proof` from `root` along `keccak256(key)` and returns the value
+/// stored at the leaf, or `None` when the proof shows the key is absent.
+pub(crate) fn verify_proof(
+    root: B256,
+    key: &[u8],
+    proof: &[Bytes]
+) -> Result<Option<Vec<u8>>, String> {
+    match lookup(root, key, &node_map(proof))? {
+        TrieLookup::Found(value) => Ok(Some(value)),
+        TrieLookup::Absent => Ok(None),
+        TrieLookup::MissingNode(hash) => Err(format!("proof is missing node {}", hash)),
+    }
+}
@@ -0,0 +1,161 @@
+use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
+use op_revm::{L1BlockInfo, OpSpecId, OpTransaction};
+use revm::context::{Block, BlockEnv, Transaction, TxEnv};
+use revm::context::transaction::TransactionType;
+use revm::database_interface::WrapDatabaseRef;
+use revm::interpreter::gas::calculate_initial_tx_gas_for_tx;
+use revm::primitives::eip3860::MAX_INITCODE_SIZE;
+use revm::primitives::eip7825::TX_GAS_LIMIT_CAP;
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::{Address, TxKind, B256, U256};
+use revm::DatabaseRef;
+use serde::Serialize;
+
+/// A reason for a node to reject the transaction before executing it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ValidationFailure {
+    ChainIdMismatch { chain_id: u64, tx_chain_id: u64 },
+    NonceTooLow { account_nonce: u64, tx_nonce: u64 },
+    /// Would be queued by a mempool rather than rejected.
+    NonceTooHigh { account_nonce: u64, tx_nonce: u64 },
+    /// EIP-3607, EIP-7702 delegations excepted.
+    SenderHasCode { sender: Address, code_hash: B256 },
+    /// `required` is `gas_limit * max_fee + value`, plus the blob fee and the
+    /// L1 data and operator fees on OP Stack chains.
+    InsufficientBalance { balance: U256, required: U256 },
+    MaxFeeBelowBaseFee { max_fee_per_gas: u128, base_fee: u64 },
+    PriorityFeeAboveMaxFee { max_priority_fee_per_gas: u128, max_fee_per_gas: u128 },
+    BlobFeeBelowBlobBaseFee { max_fee_per_blob_gas: u128, blob_base_fee: u128 },
+    GasLimitAboveBlockGasLimit { gas_limit: u64, block_gas_limit: u64 },
+    /// EIP-7825, from Osaka on.
+    GasLimitAboveCap { gas_limit: u64, cap: u64 },
+    /// `intrinsic_gas` includes the EIP-7623 calldata floor from Prague on.
+    IntrinsicGasTooLow { gas_limit: u64, intrinsic_gas: u64 },
+    InitcodeTooLarge { size: usize, max_size: usize },
+}
+
+fn validate(
+    chain_id: u64,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    tx: &TxEnv,
+    db: &impl DatabaseRef,
+    additional_cost: U256
+) -> Result<Vec<ValidationFailure>, String> {
+    let mut failures = Vec::new();
+
+    if let Some(tx_chain_id) = tx.chain_id && tx_chain_id != chain_id {
+        failures.push(ValidationFailure::ChainIdMismatch { chain_id, tx_chain_id });
+    }
+
+    let sender = db.basic_ref(tx.caller)
+        .map_err(|error| error.to_string())?
+        .unwrap_or_default();
+    if tx.nonce < sender.nonce {
+        failures.push(ValidationFailure::NonceTooLow { account_nonce: sender.nonce, tx_nonce: tx.nonce });
+    } else if tx.nonce > sender.nonce {
+        failures.push(ValidationFailure::NonceTooHigh { account_nonce: sender.nonce, tx_nonce: tx.nonce });
+    }
+    if !sender.is_empty_code_hash() {
+        let code = match &sender.code {
+            Some(code) => code.clone(),
+            None => db.code_by_hash_ref(sender.code_hash).map_err(|error| error.to_string())?,
+        };
+        if !code.is_eip7702() {
+            failures.push(ValidationFailure::SenderHasCode { sender: tx.caller, code_hash: sender.code_hash });
+        }
+    }
+
+    let required = tx.max_balance_spending()
+        .unwrap_or(U256::MAX)
+        .saturating_add(additional_cost);
+    if sender.balance < required {
+        failures.push(ValidationFailure::InsufficientBalance { balance: sender.balance, required });
+    }
+
+    if spec_id.is_enabled_in(SpecId::LONDON) && tx.max_fee_per_gas() < block_env.basefee as u128 {
+        failures.push(ValidationFailure::MaxFeeBelowBaseFee {
+            max_fee_per_gas: tx.max_fee_per_gas(),
+            base_fee: block_env.basefee,
+        });
+    }
+    if let Some(max_priority_fee_per_gas) = tx.max_priority_fee_per_gas()
+        && max_priority_fee_per_gas > tx.max_fee_per_gas() {
+        failures.push(ValidationFailure::PriorityFeeAboveMaxFee {
+            max_priority_fee_per_gas,
+            max_fee_per_gas: tx.max_fee_per_gas(),
+        });
+    }
+    if tx.tx_type() == TransactionType::Eip4844
+        && let Some(blob_base_fee) = block_env.blob_gasprice()
+        && tx.max_fee_per_blob_gas < blob_base_fee {
+        failures.push(ValidationFailure::BlobFeeBelowBlobBaseFee {
+            max_fee_per_blob_gas: tx.max_fee_per_blob_gas,
+            blob_base_fee,
+        });
+    }
+
+    if tx.gas_limit > block_env.gas_limit {
+        failures.push(ValidationFailure::GasLimitAboveBlockGasLimit {
+            gas_limit: tx.gas_limit,
+            block_gas_limit: block_env.gas_limit,
+        });
+    }
+    if spec_id.is_enabled_in(SpecId::OSAKA) && tx.gas_limit > TX_GAS_LIMIT_CAP {
+        failures.push(ValidationFailure::GasLimitAboveCap { gas_limit: tx.gas_limit, cap: TX_GAS_LIMIT_CAP });
+    }
+    let initial_gas = calculate_initial_tx_gas_for_tx(tx, spec_id);
+    let intrinsic_gas = match spec_id.is_enabled_in(SpecId::PRAGUE) {
+        true => initial_gas.initial_gas.max(initial_gas.floor_gas),
+        false => initial_gas.initial_gas,
+    };
+    if tx.gas_limit < intrinsic_gas {
+        failures.push(ValidationFailure::IntrinsicGasTooLow { gas_limit: tx.gas_limit, intrinsic_gas });
+    }
+    if tx.kind == TxKind::Create && spec_id.is_enabled_in(SpecId::SHANGHAI)
+        && tx.data.len() > MAX_INITCODE_SIZE {
+        failures.push(ValidationFailure::InitcodeTooLarge { size: tx.data.len(), max_size: MAX_INITCODE_SIZE });
+    }
+    Ok(failures)
+}
+
+/// Mempool style checks of `tx` against the state in `db` and the block it
+/// would be included in. Unlike execution, which stops at the first
+/// invalid field, every failure is reported; none means the transaction
+/// is valid.
+pub fn validate_transaction(
+    chain_id: u64,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    tx: &TxEnv,
+    db: &impl DatabaseRef
+) -> Result<Vec<ValidationFailure>, String> {
+    validate(chain_id, spec_id, block_env, tx, db, U256::ZERO)
+}
+
+/// [`validate_transaction`] on an OP Stack chain, the L1 data fee being
+/// computed from the L1Block contract storage in `db`. Deposits are never
+/// rejected.
+pub fn op_validate_transaction(
+    chain_id: u64,
+    op_spec: OpSpecId,
+    block_env: &BlockEnv,
+    tx: &OpTransaction<TxEnv>,
+    db: &impl DatabaseRef
+) -> Result<Vec<ValidationFailure>, String> {
+    if tx.tx_type() == DEPOSIT_TRANSACTION_TYPE {
+        return Ok(Vec::new());
+    }
+    let enveloped_tx = tx.enveloped_tx.as_ref()
+        .ok_or(String::from("transaction has no enveloped transaction"))?;
+    let mut l1_block_info = L1BlockInfo::try_fetch(&mut WrapDatabaseRef(db), block_env.number, op_spec)
+        .map_err(|error| error.to_string())?;
+    let mut additional_cost = l1_block_info.calculate_tx_l1_cost(enveloped_tx, op_spec);
+    if op_spec.is_enabled_in(OpSpecId::ISTHMUS) {
+        additional_cost = additional_cost.saturating_add(
+            l1_block_info.operator_fee_charge(enveloped_tx, U256::from(tx.base.gas_limit))
+        );
+    }
+    validate(chain_id, op_spec.into_eth_spec(), block_env, &tx.base, db, additional_cost)
+}
@@ -0,0 +1,172 @@
+use alloy_rlp::Decodable;
+use serde::Deserialize;
+use revm::database::InMemoryDB;
+use revm::primitives::{keccak256, Address, Bytes, HashMap, B256, KECCAK_EMPTY, U256};
+use revm::state::{AccountInfo, Bytecode};
+
+use crate::json_rpc::JsonRpcResponse;
+use crate::proof::TrieAccount;
+use crate::trie::{lookup, node_map, rlp_list_items, TrieLookup, EMPTY_ROOT_HASH};
+
+/// `debug_executionWitness` result: trie nodes, bytecodes, preimages of the
+/// touched account addresses and storage slots, and RLP ancestor headers.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExecutionWitness {
+    pub state: Vec<Bytes>,
+    #[serde(default)]
+    pub codes: Vec<Bytes>,
+    #[serde(default)]
+    pub keys: Vec<Bytes>,
+    #[serde(default)]
+    pub headers: Vec<Bytes>,
+}
+
+pub type ExecutionWitnessResponse = JsonRpcResponse<ExecutionWitness>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitnessHeader {
+    pub hash: B256,
+    pub parent_hash: B256,
+    pub number: u64,
+    pub state_root: B256,
+}
+
+impl WitnessHeader {
+    pub fn decode_rlp(header_rlp: &[u8]) -> Result<Self, String> {
+        let items = rlp_list_items(header_rlp)?;
+        if items.len() < 9 {
+            return Err(format!("block header has {} fields", items.len()));
+        }
+        let error = |error: alloy_rlp::Error| error.to_string();
+        Ok(Self {
+            hash: keccak256(header_rlp),
+            parent_hash: B256::decode(&mut &items[0][..]).map_err(error)?,
+            number: u64::decode(&mut &items[8][..]).map_err(error)?,
+            state_root: B256::decode(&mut &items[3][..]).map_err(error)?,
+        })
+    }
+}
+
+impl ExecutionWitness {
+    /// Latest of the witness headers, i.e. the parent of the witnessed block.
+    pub fn parent_header(&self) -> Result<WitnessHeader, String> {
+        let mut parent: Option<WitnessHeader> = None;
+        for header_rlp in self.headers.iter() {
+            let header = WitnessHeader::decode_rlp(header_rlp)?;
+            if parent.is_none_or(|parent| header.number > parent.number) {
+                parent = Some(header);
+            }
+        }
+        parent.ok_or(String::from("execution witness has no headers"))
+    }
+
+    /// Checks that the headers form a chain of consecutive blocks, each one
+    /// the parent of the next, ending with the block of `parent_state_root`.
+    pub fn verify_headers(&self, parent_state_root: B256) -> Result<(), String> {
+        let mut headers = self.headers.iter()
+            .map(|header_rlp| WitnessHeader::decode_rlp(header_rlp))
+            .collect::<Result<Vec<_>, String>>()?;
+        headers.sort_by_key(|header| header.number);
+        for pair in headers.windows(2) {
+            if pair[1].number != pair[0].number + 1 || pair[1].parent_hash != pair[0].hash {
+                return Err(format!(
+                    "witness header {} is not the parent of header {}",
+                    pair[0].number, pair[1].number
+                ));
+            }
+        }
+        let parent = self.parent_header()?;
+        if parent.state_root != parent_state_root {
+            return Err(format!(
+                "latest witness header {} has state root {}, expected {}",
+                parent.number, parent.state_root, parent_state_root
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Alternative to
+/// [`create_in_memory_database_from_prestate_trace`](crate::database::create_in_memory_database_from_prestate_trace)
+/// that rebuilds accounts and storage from the witness trie nodes, anchored
+/// to `parent_state_root`. Every account and slot whose preimage is in
+/// `witness.keys` is loaded; a trie node missing from `witness.state` on the
+/// path of an account is an error. Slot preimages are not tied to an
+/// address, so every slot is looked up in every account with storage and a
+/// node missing on any of those paths is an error too. The ancestor headers
+/// must chain to `parent_state_root`, their hashes are loaded for
+/// `BLOCKHASH`.
+pub fn create_in_memory_database_from_execution_witness(
+    parent_state_root: B256,
+    witness: &ExecutionWitness
+) -> Result<InMemoryDB, String> {
+    witness.verify_headers(parent_state_root)?;
+    let nodes = node_map(&witness.state);
+    if !nodes.contains_key(&parent_state_root) {
+        return Err(format!(
+            "execution witness does not contain the state root node {}", parent_state_root
+        ));
+    }
+    let codes: HashMap<B256, &Bytes> = witness.codes.iter()
+        .map(|code| (keccak256(code), code))
+        .collect();
+    let slots: Vec<U256> = witness.keys.iter()
+        .filter(|key| key.len() == 32)
+        .map(|key| U256::from_be_slice(key))
+        .collect();
+
+    let mut database = InMemoryDB::default();
+    for key in witness.keys.iter().filter(|key| key.len() == 20) {
+        let address = Address::from_slice(key);
+        let account = match lookup(parent_state_root, address.as_slice(), &nodes)? {
+            TrieLookup::Found(leaf) => TrieAccount::decode_rlp(&leaf)
+                .map_err(|error| format!("account {}: {}", address, error))?,
+            TrieLookup::Absent => continue,
+            TrieLookup::MissingNode(hash) => {
+                return Err(format!("account {}: witness is missing trie node {}", address, hash));
+            }
+        };
+
+        // a contract that was only inspected (e.g. BALANCE) has no code in
+        // the witness, its hash is kept so EXTCODEHASH stays correct
+        let code = match account.code_hash {
+            KECCAK_EMPTY => None,
+            code_hash => codes.get(&code_hash)
+                .map(|code| Bytecode::new_raw((*code).clone())),
+        };
+        database.insert_account_info(
+            address,
+            AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce,
+                code_hash: account.code_hash,
+                code,
+            }
+        );
+
+        if account.storage_root == EMPTY_ROOT_HASH {
+            continue;
+        }
+        for slot in slots.iter() {
+            let slot_key = B256::from(*slot);
+            let value = match lookup(account.storage_root, slot_key.as_slice(), &nodes)? {
+                TrieLookup::Found(leaf) => U256::decode(&mut &leaf[..])
+                    .map_err(|error| error.to_string())?,
+                TrieLookup::Absent => U256::ZERO,
+                TrieLookup::MissingNode(hash) => {
+                    return Err(format!(
+                        "account {} storage slot {}: witness is missing trie node {}",
+                        address, slot, hash
+                    ));
+                }
+            };
+            database.insert_account_storage(address, *slot, value).unwrap();
+        }
+    }
+
+    for header_rlp in witness.headers.iter() {
+        let header = WitnessHeader::decode_rlp(header_rlp)?;
+        database.cache.block_hashes.insert(U256::from(header.number), header.hash);
+    }
+    Ok(database)
+}
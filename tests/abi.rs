@@ -0,0 +1,115 @@
+mod common;
+
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::{Event, Function};
+use revm::primitives::{address, Address, Bytes, U256};
+use serde_json::{json, Value};
+use trace_prestate::abi::{annotate_trace_with_abis, AbiRegistry};
+use trace_prestate::trace::trace_transaction;
+
+use common::{account, block_env, hex_string, prestate, revert_code, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+
+const ABI: &str = r#"{"abi": [
+    {"type": "function", "name": "store", "stateMutability": "nonpayable",
+     "inputs": [{"name": "value", "type": "uint256"}],
+     "outputs": [{"name": "stored", "type": "uint256"}]},
+    {"type": "event", "name": "Stored", "anonymous": false, "inputs": [
+        {"name": "who", "type": "address", "indexed": true},
+        {"name": "value", "type": "uint256", "indexed": false}]},
+    {"type": "error", "name": "TooLarge", "inputs": [{"name": "limit", "type": "uint256"}]}
+]}"#;
+
+/// Emits `Stored(msg.sender, value)` and returns `value`.
+fn store_code() -> String {
+    let topic = Event::parse("Stored(address indexed who, uint256 value)").unwrap().selector();
+    format!("0x600435600052337f{}60206000a260206000f3", hex_string(topic.as_slice()))
+}
+
+fn trace(code: &str, input: Vec<u8>) -> Value {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, account(0, 1, code)),
+    ]);
+    let (_, _, trace) = trace_transaction(
+        1, FROM, 0, TO, input.into(), U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    trace
+}
+
+fn store_calldata(value: u64) -> Vec<u8> {
+    Function::parse("store(uint256)").unwrap()
+        .abi_encode_input(&[DynSolValue::Uint(U256::from(value), 256)])
+        .unwrap()
+}
+
+fn registry() -> AbiRegistry {
+    let mut registry = AbiRegistry::new();
+    registry.add_abi_json(TO, ABI).unwrap();
+    registry
+}
+
+#[test]
+fn decodes_calls_outputs_and_events() {
+    let mut trace = trace(&store_code(), store_calldata(42));
+
+    annotate_trace_with_abis(&mut trace, &registry());
+
+    assert_eq!(trace["decoded"], json!({
+        "name": "store",
+        "signature": "store(uint256)",
+        "inputs": [{ "name": "value", "type": "uint256", "value": "42" }],
+        "outputs": [{ "name": "stored", "type": "uint256", "value": "42" }],
+        "guessed": false
+    }));
+    assert_eq!(trace["decodedLogs"], json!([{
+        "name": "Stored",
+        "signature": "Stored(address,uint256)",
+        "params": [
+            { "name": "who", "type": "address", "value": FROM.to_checksum(None) },
+            { "name": "value", "type": "uint256", "value": "42" }
+        ],
+        "guessed": false
+    }]));
+}
+
+#[test]
+fn unknown_contracts_and_selectors_are_left_as_is() {
+    let mut unknown_selector = trace(&store_code(), vec![0xde, 0xad, 0xbe, 0xef]);
+    annotate_trace_with_abis(&mut unknown_selector, &registry());
+    assert!(unknown_selector.get("decoded").is_none());
+
+    let mut unknown_contract = trace(&store_code(), store_calldata(1));
+    annotate_trace_with_abis(&mut unknown_contract, &AbiRegistry::new());
+    assert!(unknown_contract.get("decoded").is_none());
+    assert_eq!(unknown_contract["decodedLogs"], json!([null]));
+}
+
+#[test]
+fn decodes_custom_errors_of_registered_abis() {
+    let error = alloy_json_abi::Error::parse("TooLarge(uint256 limit)").unwrap()
+        .abi_encode_input(&[DynSolValue::Uint(U256::from(10), 256)])
+        .unwrap();
+    let mut trace = trace(&revert_code(&error), store_calldata(11));
+
+    annotate_trace_with_abis(&mut trace, &registry());
+
+    assert_eq!(trace["revertReason"]["name"], "TooLarge");
+    assert_eq!(trace["revertReason"]["args"][0]["value"], "10");
+    // reverted calls have no outputs to decode
+    assert_eq!(trace["decoded"]["outputs"], Value::Null);
+}
+
+#[test]
+fn merges_abis_registered_for_the_same_address() {
+    let mut registry = AbiRegistry::new();
+    registry.add_abi_json(TO, r#"[{"type": "function", "name": "a", "inputs": [], "outputs": []}]"#).unwrap();
+    registry.add_abi_json(TO, r#"[{"type": "function", "name": "b", "inputs": [], "outputs": []}]"#).unwrap();
+
+    let abi = registry.abi(&TO).unwrap();
+    assert!(abi.function("a").is_some() && abi.function("b").is_some());
+    assert!(registry.add_abi_json(TO, r#"{"bytecode": "0x"}"#).is_err());
+    assert!(registry.decode_call(&TO, &Bytes::from(vec![0x01]), None).is_none());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use revm::context::transaction::{AccessList, AccessListItem};
+use revm::primitives::{address, Address, Bytes, B256, U256};
+use trace_prestate::access_list::create_access_list;
+
+use common::{account, block_env, hex_string, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const OTHER: Address = address!("0x3000000000000000000000000000000000000003");
+const ECRECOVER: Address = address!("0x0000000000000000000000000000000000000001");
+
+#[test]
+fn lists_cold_accounts_and_slots() {
+    let balance = |address: Address| format!("73{}3150", hex_string(address.as_slice()));
+    let call = |address: Address| format!("6000600060006000600073{}5af150", hex_string(address.as_slice()));
+    // SLOAD 1, BALANCE of OTHER, FROM and TO, then calls ecrecover
+    let code = format!(
+        "0x60015450{}{}{}{}00", balance(OTHER), balance(FROM), balance(TO), call(ECRECOVER)
+    );
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, with_storage(account(0, 1, &code), &[(1, 5)])),
+        (OTHER, account(0, 0, "")),
+    ]);
+
+    let result = create_access_list(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+
+    assert_eq!(result.access_list, AccessList(vec![
+        AccessListItem { address: TO, storage_keys: vec![B256::from(U256::from(1))] },
+        AccessListItem { address: OTHER, storage_keys: vec![] },
+    ]));
+    assert!(result.result.is_success());
+    // the slot and OTHER cost 100 less than their cold access each, like
+    // geth the already warm TO is listed for its key at 2400
+    assert_eq!(result.gas_used, result.gas_used_without_access_list - 200 + 2400);
+}
+
+#[test]
+fn plain_transfers_need_no_access_list() {
+    let prestate = prestate(vec![(FROM, account(ETHER, 0, ""))]);
+
+    let result = create_access_list(
+        1, FROM, 0, TO, Bytes::new(), U256::from(1), 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+
+    assert!(result.access_list.0.is_empty());
+    assert_eq!(result.gas_used, 21000);
+    assert_eq!(result.gas_used_without_access_list, 21000);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use revm::context::BlockEnv;
+use revm::primitives::{address, Address, Bytes, HashMap, U256};
+use trace_prestate::balance_changes::{
+    compute_balance_changes, Asset, BalanceChangeReport, BalanceDelta, ValueTransferKind
+};
+use trace_prestate::database::AccountDetails;
+use trace_prestate::trace::trace_transaction;
+use trace_prestate::transfers::TRANSFER_TOPIC;
+
+use common::{account, block_env, hex_string, prestate, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const ALICE: Address = address!("0x3000000000000000000000000000000000000003");
+const COINBASE: Address = address!("0x9000000000000000000000000000000000000009");
+
+fn block() -> BlockEnv {
+    BlockEnv { basefee: 5, beneficiary: COINBASE, ..block_env() }
+}
+
+fn run(
+    to_code: &str,
+    value: u64,
+    extra: Vec<(Address, AccountDetails)>
+) -> Result<BalanceChangeReport, String> {
+    let mut accounts = vec![(FROM, account(ETHER, 0, "")), (TO, account(0, 1, to_code))];
+    accounts.extend(extra);
+    let prestate: HashMap<Address, AccountDetails> = prestate(accounts);
+    let (result, state_diff, trace) = trace_transaction(
+        1, FROM, 0, TO, Bytes::new(), U256::from(value), 100_000, 10, 1, block(), prestate.clone()
+    ).unwrap();
+    assert!(result.is_success(), "{:?}", result);
+    compute_balance_changes(FROM, 10, Some(1), &block(), &result, &state_diff, &prestate, &trace)
+}
+
+fn delta(report: &BalanceChangeReport, address: Address, asset: Asset) -> Option<BalanceDelta> {
+    report.changes.iter()
+        .find(|change| change.address == address && change.asset == asset)
+        .map(|change| change.delta)
+}
+
+/// Emits ERC-20 `Transfer(caller, ALICE, U256::MAX)` `count` times.
+fn max_transfers_code(count: usize) -> String {
+    let transfer = format!(
+        "7f{}60005273{}337f{}60206000a3",
+        "ff".repeat(32), hex_string(ALICE.as_slice()), hex_string(TRANSFER_TOPIC.as_slice())
+    );
+    format!("0x{}00", transfer.repeat(count))
+}
+
+#[test]
+fn reports_ether_changes_including_fees() {
+    let report = run("0x00", 1000, vec![]).unwrap();
+
+    let fees = &report.gas_fees;
+    assert_eq!(fees.effective_gas_price, 6);
+    assert_eq!(fees.total_fee, U256::from(21000 * 6));
+    assert_eq!(fees.burned_fee, U256::from(21000 * 5));
+    assert_eq!(fees.priority_fee, U256::from(21000));
+    assert_eq!(delta(&report, FROM, Asset::Native), Some(BalanceDelta::decrease(U256::from(1000 + 21000 * 6))));
+    assert_eq!(delta(&report, TO, Asset::Native), Some(BalanceDelta::increase(U256::from(1000))));
+    assert_eq!(delta(&report, COINBASE, Asset::Native), Some(BalanceDelta::increase(U256::from(21000))));
+    assert_eq!(report.value_transfers.len(), 1);
+    assert_eq!(report.value_transfers[0].kind, ValueTransferKind::Call);
+}
+
+#[test]
+fn reports_transfers_of_u256_max() {
+    let report = run(&max_transfers_code(1), 0, vec![]).unwrap();
+
+    let token = Asset::Erc20 { token: TO };
+    assert_eq!(delta(&report, FROM, token), Some(BalanceDelta::decrease(U256::MAX)));
+    assert_eq!(delta(&report, ALICE, token), Some(BalanceDelta::increase(U256::MAX)));
+}
+
+#[test]
+fn overflowing_token_changes_are_an_error() {
+    let error = run(&max_transfers_code(2), 0, vec![]).unwrap_err();
+
+    assert!(error.contains("overflows"), "{}", error);
+}
+
+#[test]
+fn skips_value_sent_by_reverted_calls() {
+    // sends 5 wei to ALICE, which reverts
+    let code = format!("0x6000600060006000600573{}5af15000", hex_string(ALICE.as_slice()));
+    let report = run(&code, 10, vec![(ALICE, account(0, 1, "0x60006000fd"))]).unwrap();
+
+    assert_eq!(report.value_transfers.len(), 1);
+    assert_eq!(report.value_transfers[0].to, TO);
+    assert_eq!(delta(&report, ALICE, Asset::Native), None);
+}
+
+#[test]
+fn deltas_of_opposite_signs_cancel_out() {
+    let up = BalanceDelta::increase(U256::from(3));
+    let down = BalanceDelta::decrease(U256::from(5));
+
+    assert_eq!(up.checked_add(down), Some(BalanceDelta::decrease(U256::from(2))));
+    assert_eq!(down.checked_add(BalanceDelta::increase(U256::from(5))), Some(BalanceDelta::default()));
+    assert_eq!(BalanceDelta::decrease(U256::MAX).checked_add(down), None);
+}
@@ -0,0 +1,36 @@
+use revm::primitives::{Address, B256, U256};
+use serde_json::{json, Value};
+use trace_prestate::block::{create_block_env_from_block_details, BlockDetails};
+
+/// A block with the required fields and `fields`.
+fn block_details(fields: Value) -> BlockDetails {
+    let mut block = json!({
+        "number": "0x64",
+        "miner": Address::ZERO,
+        "timestamp": "0x6553f100",
+        "gasLimit": "0x1c9c380",
+        "baseFeePerGas": "0x7",
+        "difficulty": "0x2a",
+        "excessBlobGas": "0x0"
+    });
+    block.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(block).unwrap()
+}
+
+#[test]
+fn the_state_root_is_optional() {
+    assert_eq!(block_details(json!({})).state_root, None);
+    let block = block_details(json!({ "stateRoot": B256::repeat_byte(1) }));
+    assert_eq!(block.state_root, Some(B256::repeat_byte(1)));
+}
+
+#[test]
+fn prevrandao_is_the_mix_hash() {
+    let block = block_details(json!({ "mixHash": B256::repeat_byte(7) }));
+    let block_env = create_block_env_from_block_details(block).unwrap();
+    assert_eq!(block_env.prevrandao, Some(B256::repeat_byte(7)));
+
+    // before the merge, and on nodes that leave it out, the difficulty
+    let block_env = create_block_env_from_block_details(block_details(json!({}))).unwrap();
+    assert_eq!(block_env.prevrandao, Some(B256::from(U256::from(42))));
+}
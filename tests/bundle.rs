@@ -0,0 +1,66 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, HashMap, U256};
+use trace_prestate::bundle::{trace_bundle, BundleTransaction};
+use trace_prestate::database::AccountDetails;
+
+use common::{account, block_env, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const COUNTER: Address = address!("0x2000000000000000000000000000000000000002");
+const REVERTER: Address = address!("0x3000000000000000000000000000000000000003");
+
+// increments slot 0
+const INCREMENT: &str = "0x60005460010160005500";
+
+fn transaction(to: Address, nonce: Option<u64>) -> BundleTransaction {
+    BundleTransaction {
+        from: FROM,
+        nonce,
+        to,
+        data: Bytes::new(),
+        value: U256::ZERO,
+        gas_limit: 100_000,
+        gas_price: 1,
+        gas_priority_fee: 1,
+    }
+}
+
+fn accounts() -> HashMap<Address, AccountDetails> {
+    prestate(vec![
+        (FROM, account(ETHER, 3, "")),
+        (COUNTER, with_storage(account(0, 1, INCREMENT), &[(0, 5)])),
+        (REVERTER, account(0, 1, "0x60006000fd")),
+    ])
+}
+
+#[test]
+fn transactions_see_the_state_of_earlier_ones() {
+    let bundle = trace_bundle(1, block_env(), accounts(), vec![
+        transaction(COUNTER, None),
+        transaction(REVERTER, None),
+        transaction(COUNTER, None),
+    ]).unwrap();
+
+    assert!(bundle.transactions[0].result.is_success());
+    assert!(!bundle.transactions[1].result.is_success());
+    assert!(bundle.transactions[2].result.is_success());
+    // the reverted transaction still used a nonce
+    assert_eq!(bundle.transactions[2].state_diff[&FROM].info.nonce, 6);
+    assert_eq!(bundle.transactions[2].state_diff[&COUNTER].storage[&U256::ZERO].original_value, U256::from(6));
+
+    let counter_slot = &bundle.state_diff[&COUNTER].storage[&U256::ZERO];
+    assert_eq!((counter_slot.original_value, counter_slot.present_value), (U256::from(5), U256::from(7)));
+    assert_eq!(bundle.state_diff[&FROM].info.nonce, 6);
+    assert!(bundle.transactions.iter().all(|transaction| transaction.trace.is_object()));
+}
+
+#[test]
+fn invalid_transactions_abort_the_bundle() {
+    let error = trace_bundle(1, block_env(), accounts(), vec![
+        transaction(COUNTER, Some(3)),
+        transaction(COUNTER, Some(3)),
+    ]).unwrap_err();
+
+    assert!(error.starts_with("transaction 1: "), "{}", error);
+}
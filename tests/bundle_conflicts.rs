@@ -0,0 +1,118 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use trace_prestate::bundle_conflicts::{
+    detect_user_operation_conflicts, StateAccess, UserOperationTransaction
+};
+
+use common::{account, block_env, prestate, ETHER};
+
+const BUNDLER: Address = address!("0x1000000000000000000000000000000000000001");
+const ENTRY_POINT: Address = address!("0x2000000000000000000000000000000000000002");
+
+// reverts when slot 0 is set, sets it otherwise
+const ONCE: &str = "0x600054600c57600160005500005b60006000fd";
+
+fn user_operation() -> UserOperationTransaction {
+    UserOperationTransaction {
+        from: BUNDLER,
+        to: ENTRY_POINT,
+        data: Bytes::new(),
+        value: U256::ZERO,
+        gas_limit: 100_000,
+        gas_price: 1,
+        gas_priority_fee: 1,
+    }
+}
+
+#[test]
+fn reports_reads_invalidated_by_earlier_ops() {
+    let prestate = prestate(vec![
+        (BUNDLER, account(ETHER, 0, "")),
+        (ENTRY_POINT, account(0, 1, ONCE)),
+    ]);
+
+    let reports = detect_user_operation_conflicts(
+        1, block_env(), prestate, vec![user_operation(), user_operation()]
+    ).unwrap();
+
+    let slot = StateAccess::Storage { address: ENTRY_POINT, slot: U256::ZERO };
+    assert!(reports[0].bundled_result.is_success());
+    assert!(reports[0].conflicts.is_empty());
+    assert!(reports[0].validation_reads.contains(&slot));
+    assert!(reports[0].writes.contains(&slot));
+    assert!(reports[0].writes.contains(&StateAccess::Balance { address: BUNDLER }));
+    assert!(!reports[0].fails_in_bundle());
+
+    assert!(reports[1].standalone_result.is_success());
+    assert!(!reports[1].bundled_result.is_success());
+    assert!(reports[1].fails_in_bundle());
+    assert_eq!(reports[1].conflicts.len(), 1);
+    assert_eq!(reports[1].conflicts[0].earlier_op, 0);
+    assert_eq!(reports[1].conflicts[0].access, slot);
+}
+
+#[test]
+fn independent_ops_do_not_conflict() {
+    let other_entry_point = address!("0x3000000000000000000000000000000000000003");
+    let prestate = prestate(vec![
+        (BUNDLER, account(ETHER, 0, "")),
+        (ENTRY_POINT, account(0, 1, ONCE)),
+        (other_entry_point, account(0, 1, ONCE)),
+    ]);
+    let second = UserOperationTransaction { to: other_entry_point, ..user_operation() };
+
+    let reports = detect_user_operation_conflicts(
+        1, block_env(), prestate, vec![user_operation(), second]
+    ).unwrap();
+
+    assert!(reports.iter().all(|report| report.bundled_result.is_success()));
+    assert!(reports.iter().all(|report| report.conflicts.is_empty()));
+}
+
+#[test]
+fn reads_after_inner_handle_op_are_not_validation_reads() {
+    // calls itself with innerHandleOp (v0.7) then reads slot 1
+    let code = "0x303314602057630042dc5360e01b60005260006000600460006000305af150005b60015400";
+    let prestate = prestate(vec![
+        (BUNDLER, account(ETHER, 0, "")),
+        (ENTRY_POINT, account(0, 1, code)),
+    ]);
+
+    let reports = detect_user_operation_conflicts(
+        1, block_env(), prestate, vec![user_operation()]
+    ).unwrap();
+
+    assert!(reports[0].bundled_result.is_success(), "{:?}", reports[0].bundled_result);
+    assert!(!reports[0].validation_reads.contains(
+        &StateAccess::Storage { address: ENTRY_POINT, slot: U256::from(1) }
+    ));
+}
+
+#[test]
+fn inner_handle_op_calls_of_other_contracts_do_not_end_validation() {
+    let wallet = address!("0x4000000000000000000000000000000000000004");
+    let other = address!("0x5000000000000000000000000000000000000005");
+    // calls the wallet, which calls another contract with innerHandleOp
+    // (v0.7) then reads slot 1
+    let entry_point_code = format!("0x6000600060006000600073{}5af15000", &wallet.to_string()[2..]);
+    let wallet_code = format!(
+        "0x630042dc5360e01b6000526000600060046000600073{}5af15060015400",
+        &other.to_string()[2..]
+    );
+    let prestate = prestate(vec![
+        (BUNDLER, account(ETHER, 0, "")),
+        (ENTRY_POINT, account(0, 1, &entry_point_code)),
+        (wallet, account(0, 1, &wallet_code)),
+        (other, account(0, 0, "")),
+    ]);
+
+    let reports = detect_user_operation_conflicts(
+        1, block_env(), prestate, vec![user_operation()]
+    ).unwrap();
+
+    assert!(reports[0].bundled_result.is_success(), "{:?}", reports[0].bundled_result);
+    assert!(reports[0].validation_reads.contains(
+        &StateAccess::Storage { address: wallet, slot: U256::from(1) }
+    ));
+}
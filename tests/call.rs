@@ -0,0 +1,60 @@
+mod common;
+
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::Error;
+use revm::context::result::HaltReason;
+use revm::context::BlockEnv;
+use revm::primitives::{address, Address, Bytes, U256};
+use trace_prestate::call::{call_transaction, CallResult};
+use trace_prestate::revert::RevertReason;
+
+use common::{account, block_env, prestate, revert_code};
+
+const CALLER: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+
+fn call(code: &str, value: u64) -> CallResult {
+    // a contract sender with no balance, nonce 7 and a base fee to pay
+    let prestate = prestate(vec![
+        (CALLER, account(0, 7, "0x00")),
+        (TO, account(0, 1, code)),
+    ]);
+    let block = BlockEnv { basefee: 1_000_000_000, ..block_env() };
+    call_transaction(1, CALLER, TO, Bytes::new(), U256::from(value), None, block, prestate).unwrap()
+}
+
+#[test]
+fn returns_the_output_from_any_sender() {
+    // returns 42
+    let result = call("0x602a60005260206000f3", 5);
+
+    assert!(result.success);
+    assert_eq!(result.output, Bytes::from(U256::from(42).to_be_bytes::<32>()));
+    assert_eq!(result.gas_used, 21000 + 3 + 3 + 3 + 3 + 3 + 3);
+    assert_eq!(result.state_diff[&TO].info.balance, U256::from(5));
+    assert_eq!(result.revert_reason, None);
+    assert!(result.trace.is_object());
+}
+
+#[test]
+fn decodes_revert_reasons() {
+    let data = Error::parse("Error(string)").unwrap()
+        .abi_encode_input(&[DynSolValue::String("denied".into())])
+        .unwrap();
+
+    let result = call(&revert_code(&data), 0);
+
+    assert!(!result.success);
+    assert_eq!(result.output, Bytes::from(data));
+    assert_eq!(result.revert_reason, Some(RevertReason::Error { message: "denied".into() }));
+    assert_eq!(result.halt_reason, None);
+}
+
+#[test]
+fn reports_halts() {
+    let result = call("0xfe", 0);
+
+    assert!(!result.success);
+    assert_eq!(result.halt_reason, Some(HaltReason::InvalidFEOpcode));
+    assert_eq!(result.gas_used, block_env().gas_limit);
+}
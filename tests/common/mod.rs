@@ -0,0 +1,248 @@
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+
+use alloy_rlp::{Encodable, Header};
+use revm::context::BlockEnv;
+use revm::primitives::{b256, keccak256, Address, Bytes, HashMap, B256, KECCAK_EMPTY, U256};
+use serde_json::{json, Value};
+use trace_prestate::database::AccountDetails;
+use trace_prestate::lazy_database::JsonRpcTransport;
+
+pub const ETHER: u128 = 1_000_000_000_000_000_000;
+
+pub fn account(balance: u128, nonce: u64, code: &str) -> AccountDetails {
+    AccountDetails {
+        balance: Some(U256::from(balance)),
+        nonce: Some(nonce),
+        code: match code {
+            "" => None,
+            code => Some(code.parse().unwrap()),
+        },
+        storage: None,
+    }
+}
+
+pub fn with_storage(mut account: AccountDetails, slots: &[(u64, u64)]) -> AccountDetails {
+    account.storage = Some(slots.iter()
+        .map(|(slot, value)| (U256::from(*slot), U256::from(*value)))
+        .collect());
+    account
+}
+
+pub fn prestate(accounts: Vec<(Address, AccountDetails)>) -> HashMap<Address, AccountDetails> {
+    accounts.into_iter().collect()
+}
+
+pub fn block_env() -> BlockEnv {
+    BlockEnv {
+        number: U256::from(100),
+        timestamp: U256::from(1_700_000_000),
+        gas_limit: 30_000_000,
+        ..Default::default()
+    }
+}
+
+/// Answers every request with `handler` and records the methods.
+pub struct MockTransport<F: Fn(&str, &Value) -> Value> {
+    pub handler: F,
+    pub calls: RefCell<Vec<String>>,
+}
+
+impl<F: Fn(&str, &Value) -> Value> MockTransport<F> {
+    pub fn new(handler: F) -> Self {
+        Self { handler, calls: RefCell::new(Vec::new()) }
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+}
+
+impl<F: Fn(&str, &Value) -> Value> JsonRpcTransport for MockTransport<F> {
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.calls.borrow_mut().push(method.to_string());
+        Ok((self.handler)(method, &params))
+    }
+}
+
+pub fn rpc_result(result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": 1, "result": result })
+}
+
+pub fn rpc_error(code: i16, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": code, "message": message } })
+}
+
+pub fn rlp<T: Encodable + ?Sized>(value: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.encode(&mut out);
+    out
+}
+
+/// RLP list of already encoded items.
+pub fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(|item| item.len()).sum();
+    let mut out = Vec::new();
+    Header { list: true, payload_length }.encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+pub fn rlp_account(nonce: u64, balance: U256, storage_root: B256, code_hash: B256) -> Vec<u8> {
+    rlp_list(&[rlp(&nonce), rlp(&balance), rlp(&storage_root), rlp(&code_hash)])
+}
+
+pub fn empty_account_rlp(balance: u64) -> Vec<u8> {
+    rlp_account(0, U256::from(balance), EMPTY_ROOT_HASH, KECCAK_EMPTY)
+}
+
+pub const EMPTY_ROOT_HASH: B256 =
+    b256!("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// Merkle Patricia trie built from scratch, keeping every hashed node.
+pub struct Trie {
+    pub root: B256,
+    pub nodes: Vec<Bytes>,
+}
+
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut encoded = Vec::new();
+    let rest = if path.len() % 2 == 1 {
+        encoded.push((flag << 4) | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag << 4);
+        path
+    };
+    encoded.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+fn node_reference(node: Vec<u8>, nodes: &mut Vec<Bytes>) -> Vec<u8> {
+    if node.len() < 32 {
+        return node;
+    }
+    let hash = keccak256(&node);
+    nodes.push(node.into());
+    rlp(&hash)
+}
+
+fn build_node(entries: &[(Vec<u8>, Vec<u8>)], depth: usize, nodes: &mut Vec<Bytes>) -> Vec<u8> {
+    if entries.len() == 1 {
+        let (path, value) = &entries[0];
+        return rlp_list(&[rlp(&hex_prefix(&path[depth..], true)[..]), rlp(&value[..])]);
+    }
+    let first = &entries[0].0;
+    let mut shared = 0;
+    while entries.iter().all(|(path, _)| path[depth + shared] == first[depth + shared]) {
+        shared += 1;
+    }
+    if shared > 0 {
+        let child = build_node(entries, depth + shared, nodes);
+        return rlp_list(&[
+            rlp(&hex_prefix(&first[depth..depth + shared], false)[..]),
+            node_reference(child, nodes),
+        ]);
+    }
+    let mut items = Vec::with_capacity(17);
+    for nibble in 0..16u8 {
+        let children: Vec<_> = entries.iter()
+            .filter(|(path, _)| path[depth] == nibble)
+            .cloned()
+            .collect();
+        items.push(match children.is_empty() {
+            true => vec![alloy_rlp::EMPTY_STRING_CODE],
+            false => node_reference(build_node(&children, depth + 1, nodes), nodes),
+        });
+    }
+    items.push(vec![alloy_rlp::EMPTY_STRING_CODE]);
+    rlp_list(&items)
+}
+
+impl Trie {
+    /// Trie of `keccak256(key) => value`, as the state and storage tries.
+    pub fn new(entries: &[(Vec<u8>, Vec<u8>)]) -> Self {
+        if entries.is_empty() {
+            return Self { root: EMPTY_ROOT_HASH, nodes: Vec::new() };
+        }
+        let entries: Vec<_> = entries.iter()
+            .map(|(key, value)| {
+                let path = keccak256(key).iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect();
+                (path, value.clone())
+            })
+            .collect();
+        let mut nodes = Vec::new();
+        let root_node = build_node(&entries, 0, &mut nodes);
+        let root = keccak256(&root_node);
+        nodes.push(root_node.into());
+        Self { root, nodes }
+    }
+
+    /// Nodes on the path of `key`, as in an `eth_getProof` proof.
+    pub fn proof(&self, key: &[u8]) -> Vec<Bytes> {
+        let path: Vec<u8> = keccak256(key).iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect();
+        let by_hash: HashMap<B256, &Bytes> = self.nodes.iter().map(|node| (keccak256(node), node)).collect();
+        let mut proof = Vec::new();
+        let mut next = Some(self.root);
+        let mut depth = 0;
+        while let Some(hash) = next.take() {
+            let Some(node) = by_hash.get(&hash) else { break };
+            proof.push((*node).clone());
+            let items = split_list(node);
+            if items.len() == 17 {
+                if depth < path.len() {
+                    next = child_hash(&items[path[depth] as usize]);
+                    depth += 1;
+                }
+            } else {
+                let encoded = rlp_payload(&items[0]);
+                let odd = encoded[0] >> 4 & 1 == 1;
+                let leaf = encoded[0] >> 4 & 2 == 2;
+                let length = (encoded.len() - 1) * 2 + odd as usize;
+                if !leaf {
+                    depth += length;
+                    next = child_hash(&items[1]);
+                }
+            }
+        }
+        proof
+    }
+}
+
+fn rlp_payload(item: &[u8]) -> Vec<u8> {
+    let mut buf = item;
+    Header::decode_bytes(&mut buf, false).unwrap().to_vec()
+}
+
+fn split_list(node: &[u8]) -> Vec<Vec<u8>> {
+    let mut buf = node;
+    let header = Header::decode(&mut buf).unwrap();
+    let mut payload = &buf[..header.payload_length];
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let start = payload;
+        let item = Header::decode(&mut payload).unwrap();
+        payload = &payload[item.payload_length..];
+        items.push(start[..start.len() - payload.len()].to_vec());
+    }
+    items
+}
+
+fn child_hash(item: &[u8]) -> Option<B256> {
+    let payload = rlp_payload(item);
+    (payload.len() == 32).then(|| B256::from_slice(&payload))
+}
+
+/// Code that reverts with `data`.
+pub fn revert_code(data: &[u8]) -> String {
+    let length = format!("{:04x}", data.len());
+    format!("0x61{length}600e60003961{length}6000fd{}", hex_string(data))
+}
+
+pub fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::Error;
+use revm::primitives::{address, Address, Bytes, HashMap, U256};
+use trace_prestate::database::AccountDetails;
+use trace_prestate::estimate_gas::{estimate_gas, GasEstimateFailure};
+use trace_prestate::revert::RevertReason;
+use trace_prestate::trace::trace_transaction;
+
+use common::{account, block_env, hex_string, prestate, revert_code, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const CHILD: Address = address!("0x3000000000000000000000000000000000000003");
+
+fn accounts(code: &str) -> HashMap<Address, AccountDetails> {
+    prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, with_storage(account(0, 1, code), &[(0, 1)])),
+        // sets slot 1
+        (CHILD, account(0, 1, "0x600160015500")),
+    ])
+}
+
+fn succeeds_with(code: &str, gas_limit: u64) -> bool {
+    trace_transaction(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, gas_limit, 1, 1, block_env(), accounts(code)
+    ).is_ok_and(|(result, _, _)| result.is_success())
+}
+
+/// Checks that the estimate is the lowest gas limit that succeeds.
+fn assert_lowest_limit(code: &str) -> u64 {
+    let estimate = estimate_gas(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 1, 1, block_env(), accounts(code), None
+    ).unwrap();
+    assert_eq!(estimate.failure, None);
+    assert!(succeeds_with(code, estimate.gas));
+    assert!(!succeeds_with(code, estimate.gas - 1));
+    estimate.gas
+}
+
+#[test]
+fn estimates_plain_calls_exactly() {
+    assert_eq!(assert_lowest_limit("0x00"), 21000);
+}
+
+#[test]
+fn covers_gas_refunded_after_execution() {
+    // clears slot 0 for a refund
+    assert_lowest_limit("0x600060005500");
+}
+
+#[test]
+fn covers_the_gas_kept_back_from_calls() {
+    // calls CHILD with all the gas left, fails unless the call succeeded
+    let code = format!("0x6000600060006000600073{}5af1602557fe5b00", hex_string(CHILD.as_slice()));
+    let gas = assert_lowest_limit(&code);
+    assert!(gas > 21000 + 22100 * 64 / 63);
+}
+
+#[test]
+fn reports_reverts_with_their_reason() {
+    let data = Error::parse("Error(string)").unwrap()
+        .abi_encode_input(&[DynSolValue::String("nope".into())])
+        .unwrap();
+
+    let estimate = estimate_gas(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 1, 1, block_env(), accounts(&revert_code(&data)),
+        Some(1_000_000)
+    ).unwrap();
+
+    assert_eq!(estimate.gas, 1_000_000);
+    assert_eq!(estimate.runs, 1);
+    assert!(matches!(
+        estimate.failure,
+        Some(GasEstimateFailure::Revert { reason: Some(RevertReason::Error { message }), .. }) if message == "nope"
+    ));
+}
+
+#[test]
+fn caps_the_limit_to_what_the_sender_can_pay() {
+    let prestate = prestate(vec![(FROM, account(20_000, 0, ""))]);
+
+    let estimate = estimate_gas(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 1, 1, block_env(), prestate, None
+    ).unwrap();
+
+    assert_eq!(estimate.gas, 20_000);
+    assert!(matches!(estimate.failure, Some(GasEstimateFailure::Invalid { .. })));
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use serde_json::{json, Value};
+use trace_prestate::gas::{gas_breakdown, GasBreakdown};
+use trace_prestate::trace::trace_transaction;
+
+use common::{account, block_env, hex_string, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const CHILD: Address = address!("0x3000000000000000000000000000000000000003");
+
+fn breakdown(code: &str, data: Bytes) -> GasBreakdown {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, with_storage(account(100, 1, code), &[(0, 1)])),
+        (CHILD, account(0, 1, "0x00")),
+    ]);
+    let (result, _, trace) = trace_transaction(
+        1, FROM, 0, TO, data, U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    assert!(result.is_success(), "{:?}", result);
+    gas_breakdown(&trace, &result).unwrap()
+}
+
+#[test]
+fn plain_call_only_pays_intrinsic_gas() {
+    let breakdown = breakdown("0x00", Bytes::new());
+
+    assert_eq!(breakdown.gas_limit, 100_000);
+    assert_eq!(breakdown.intrinsic_gas, 21000);
+    assert_eq!(breakdown.execution_gas, 0);
+    assert_eq!(breakdown.gas_used, 21000);
+    assert!(!breakdown.floor_applied);
+    assert_eq!(breakdown.frames.len(), 1);
+}
+
+#[test]
+fn applies_the_storage_clearing_refund() {
+    // clears slot 0
+    let breakdown = breakdown("0x600060005500", Bytes::new());
+
+    assert_eq!(breakdown.execution_gas, 3 + 3 + 5000);
+    assert_eq!(breakdown.refund_counter, 4800);
+    assert_eq!(breakdown.refund_applied, 4800);
+    assert_eq!(breakdown.gas_used, 21000 + 5006 - 4800);
+}
+
+#[test]
+fn splits_frame_gas_between_self_and_children() {
+    // sends 1 wei to CHILD
+    let code = format!("0x6000600060006000600173{}5af15000", hex_string(CHILD.as_slice()));
+    let breakdown = breakdown(&code, Bytes::new());
+
+    let root = breakdown.frames[0].gas;
+    let child = &breakdown.frames[1];
+    assert_eq!(child.frame, [0]);
+    assert_eq!(child.address, Some(CHILD));
+    assert_eq!(child.gas.stipend, 2300);
+    assert_eq!(root.children_used, child.gas.used);
+    assert_eq!(root.self_used + root.children_used, root.used + child.gas.stipend);
+}
+
+#[test]
+fn reports_the_calldata_floor() {
+    let data = Bytes::from(vec![0xff; 100]);
+
+    let breakdown = breakdown("0x00", data);
+
+    // EIP-7623: 10 gas per token, 4 tokens per non-zero byte
+    assert_eq!(breakdown.intrinsic_gas, 21000 + 16 * 100);
+    assert_eq!(breakdown.gas_used, 21000 + 10 * 4 * 100);
+    assert!(breakdown.floor_applied);
+    assert_eq!(breakdown.refund_applied, 0);
+}
+
+#[test]
+fn traces_without_gas_are_an_error() {
+    let prestate = prestate(vec![(FROM, account(ETHER, 0, ""))]);
+    let (result, _, _) = trace_transaction(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+
+    assert!(gas_breakdown(&json!({}), &result).is_err());
+    assert!(gas_breakdown(&Value::Null, &result).is_err());
+}
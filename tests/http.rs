@@ -0,0 +1,110 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use revm::primitives::{address, Address, B256, KECCAK_EMPTY, U256};
+use revm::Database;
+use serde_json::{json, Value};
+use trace_prestate::lazy_database::{
+    create_lazy_database_from_prestate_trace, HttpTransport, JsonRpcDatabaseError
+};
+
+use common::{prestate, rpc_error, rpc_result};
+
+const REMOTE: Address = address!("0x2000000000000000000000000000000000000002");
+
+/// Local HTTP server answering every JSON-RPC request with `handler`,
+/// after `delay`. Returns its URL and the requests it received.
+fn mock_server(
+    delay: Duration,
+    handler: impl Fn(&Value) -> (u16, Value) + Send + 'static
+) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            let body = loop {
+                let read = stream.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break None;
+                }
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                let Some(headers_end) = text.find("\r\n\r\n") else { continue };
+                let content_length = text[..headers_end].lines()
+                    .find_map(|line| line.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|length| length.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if request.len() >= headers_end + 4 + content_length {
+                    break Some(request[headers_end + 4..headers_end + 4 + content_length].to_vec());
+                }
+            };
+            let Some(body) = body else { continue };
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            received.lock().unwrap().push(request.clone());
+            thread::sleep(delay);
+            let (status, response) = handler(&request);
+            let response = response.to_string();
+            let _ = stream.write_all(format!(
+                "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status, response.len(), response
+            ).as_bytes());
+        }
+    });
+    (url, requests)
+}
+
+#[test]
+fn fetches_missing_state_over_http() {
+    let (url, requests) = mock_server(Duration::ZERO, |request| {
+        assert_eq!(request["params"], json!([REMOTE, [], "0xa"]));
+        (200, rpc_result(json!({
+            "address": REMOTE,
+            "balance": "0x9",
+            "nonce": "0x2",
+            "codeHash": KECCAK_EMPTY,
+            "storageHash": B256::ZERO,
+            "accountProof": [],
+            "storageProof": []
+        })))
+    });
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), HttpTransport::new(&url), 10);
+
+    let info = db.basic(REMOTE).unwrap().unwrap();
+
+    assert_eq!((info.balance, info.nonce), (U256::from(9), 2));
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["jsonrpc"], "2.0");
+    assert_eq!(requests[0]["method"], "eth_getProof");
+}
+
+#[test]
+fn rpc_errors_are_not_transport_errors() {
+    let (url, _) = mock_server(Duration::ZERO, |_| (200, rpc_error(-32000, "header not found")));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), HttpTransport::new(&url), 10);
+
+    assert!(matches!(db.basic(REMOTE), Err(JsonRpcDatabaseError::Rpc(_))));
+}
+
+#[test]
+fn http_failures_are_transport_errors() {
+    let (url, _) = mock_server(Duration::ZERO, |_| (503, json!("unavailable")));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), HttpTransport::new(&url), 10);
+    assert!(matches!(db.basic(REMOTE), Err(JsonRpcDatabaseError::Transport(_))));
+
+    let (url, _) = mock_server(Duration::from_secs(2), |_| (200, rpc_result(json!("0x5"))));
+    let transport = HttpTransport::with_timeout(&url, Duration::from_millis(200));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), transport, 10);
+    assert!(matches!(db.basic(REMOTE), Err(JsonRpcDatabaseError::Transport(_))));
+}
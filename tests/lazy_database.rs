@@ -0,0 +1,110 @@
+mod common;
+
+use revm::primitives::{address, Address, B256, KECCAK_EMPTY, U256};
+use revm::Database;
+use serde_json::{json, Value};
+use trace_prestate::lazy_database::{create_lazy_database_from_prestate_trace, JsonRpcDatabaseError};
+
+use common::{account, prestate, rpc_error, rpc_result, with_storage, MockTransport};
+
+const KNOWN: Address = address!("0x1000000000000000000000000000000000000001");
+const REMOTE: Address = address!("0x2000000000000000000000000000000000000002");
+
+fn proof(balance: u64, nonce: u64, code_hash: B256) -> Value {
+    json!({
+        "address": REMOTE,
+        "balance": U256::from(balance),
+        "nonce": format!("0x{:x}", nonce),
+        "codeHash": code_hash,
+        "storageHash": B256::ZERO,
+        "accountProof": [],
+        "storageProof": []
+    })
+}
+
+#[test]
+fn prestate_accounts_and_slots_are_never_fetched() {
+    let transport = MockTransport::new(|method, _: &Value| panic!("unexpected {}", method));
+    let prestate = prestate(vec![(KNOWN, with_storage(account(5, 1, ""), &[(1, 7)]))]);
+    let mut db = create_lazy_database_from_prestate_trace(prestate, &transport, 10);
+
+    assert_eq!(db.basic(KNOWN).unwrap().unwrap().balance, U256::from(5));
+    assert_eq!(db.storage(KNOWN, U256::from(1)).unwrap(), U256::from(7));
+    assert!(transport.calls().is_empty());
+}
+
+#[test]
+fn missing_state_is_fetched_once_at_the_block() {
+    let transport = MockTransport::new(|method, params: &Value| {
+        assert_eq!(params.as_array().unwrap().last().unwrap(), "0xa");
+        match method {
+            "eth_getProof" => rpc_result(proof(9, 2, B256::repeat_byte(1))),
+            "eth_getCode" => rpc_result(json!("0x6000")),
+            "eth_getStorageAt" => rpc_result(json!(B256::from(U256::from(3)))),
+            method => panic!("unexpected {}", method),
+        }
+    });
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), &transport, 10);
+
+    let info = db.basic(REMOTE).unwrap().unwrap();
+    assert_eq!((info.balance, info.nonce), (U256::from(9), 2));
+    assert_eq!(info.code.unwrap().original_bytes(), "0x6000".parse::<revm::primitives::Bytes>().unwrap());
+    assert_eq!(db.storage(REMOTE, U256::from(4)).unwrap(), U256::from(3));
+    db.basic(REMOTE).unwrap();
+    db.storage(REMOTE, U256::from(4)).unwrap();
+
+    assert_eq!(transport.calls(), ["eth_getProof", "eth_getCode", "eth_getStorageAt"]);
+}
+
+#[test]
+fn empty_accounts_are_missing() {
+    let transport = MockTransport::new(|_, _: &Value| rpc_result(proof(0, 0, KECCAK_EMPTY)));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), &transport, 10);
+
+    assert!(db.basic(REMOTE).unwrap().is_none());
+    assert_eq!(transport.calls(), ["eth_getProof"]);
+}
+
+#[test]
+fn falls_back_to_legacy_calls_without_eth_get_proof() {
+    let transport = MockTransport::new(|method, _: &Value| match method {
+        "eth_getProof" => rpc_error(-32601, "method not found"),
+        "eth_getBalance" => rpc_result(json!("0x64")),
+        "eth_getTransactionCount" => rpc_result(json!("0x1")),
+        "eth_getCode" => rpc_result(json!("0x")),
+        method => panic!("unexpected {}", method),
+    });
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), &transport, 10);
+
+    let info = db.basic(REMOTE).unwrap().unwrap();
+
+    assert_eq!((info.balance, info.nonce, info.code_hash), (U256::from(100), 1, KECCAK_EMPTY));
+    assert_eq!(
+        transport.calls(),
+        ["eth_getProof", "eth_getBalance", "eth_getTransactionCount", "eth_getCode"]
+    );
+}
+
+#[test]
+fn rpc_errors_are_returned() {
+    let transport = MockTransport::new(|_, _: &Value| rpc_error(-32000, "header not found"));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), &transport, 10);
+
+    match db.storage(REMOTE, U256::ZERO) {
+        Err(JsonRpcDatabaseError::Rpc(error)) => {
+            assert_eq!(error.error.message.as_deref(), Some("header not found"))
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn invalid_responses_are_returned() {
+    let transport = MockTransport::new(|_, _: &Value| rpc_result(json!("not a word")));
+    let mut db = create_lazy_database_from_prestate_trace(prestate(vec![]), &transport, 10);
+
+    assert!(matches!(
+        db.storage(REMOTE, U256::ZERO),
+        Err(JsonRpcDatabaseError::InvalidResponse(_))
+    ));
+}
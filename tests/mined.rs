@@ -0,0 +1,151 @@
+mod common;
+
+use op_revm::OpSpecId;
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::{address, Address, Bytes, B256, U256};
+use serde_json::{json, Value};
+use trace_prestate::mined::{op_trace_transaction_by_hash, trace_transaction_by_hash};
+
+use common::{rpc_result, MockTransport, ETHER};
+
+const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const MINER: Address = address!("0x3000000000000000000000000000000000000003");
+const HASH: B256 = B256::repeat_byte(0xaa);
+const BLOCK_HASH: B256 = B256::repeat_byte(0xbb);
+
+// returns the block number and the base fee
+const NUMBER_AND_BASE_FEE: &str = "0x436000524860205260406000f3";
+
+fn transaction() -> Value {
+    json!({
+        "hash": HASH,
+        "type": "0x2",
+        "from": SENDER,
+        "to": TO,
+        "input": "0x",
+        "value": "0x0",
+        "gas": "0x186a0",
+        "nonce": "0x0",
+        "chainId": "0x1",
+        "maxFeePerGas": "0x64",
+        "maxPriorityFeePerGas": "0x2",
+        "accessList": [],
+        "blockHash": BLOCK_HASH,
+        "blockNumber": "0x64",
+        "transactionIndex": "0x0"
+    })
+}
+
+fn deposit() -> Value {
+    json!({
+        "hash": HASH,
+        "type": "0x7e",
+        "from": SENDER,
+        "to": TO,
+        "input": "0x",
+        "value": "0x0",
+        "gas": "0x186a0",
+        "nonce": "0x0",
+        "sourceHash": B256::repeat_byte(0xcc),
+        "mint": "0x0",
+        "isSystemTx": false,
+        "blockHash": BLOCK_HASH,
+        "blockNumber": "0x64",
+        "transactionIndex": "0x0"
+    })
+}
+
+/// Answers like a node where `transaction` was mined in block 100.
+fn node(transaction: Value) -> impl Fn(&str, &Value) -> Value {
+    move |method, params| match method {
+        "eth_getTransactionByHash" => {
+            assert_eq!(params[0], json!(HASH));
+            rpc_result(transaction.clone())
+        }
+        "eth_getBlockByHash" => {
+            assert_eq!(params, &json!([BLOCK_HASH, false]));
+            rpc_result(json!({
+                "hash": BLOCK_HASH,
+                "number": "0x64",
+                "miner": MINER,
+                "timestamp": "0x6553f100",
+                "stateRoot": B256::ZERO,
+                "gasLimit": "0x1c9c380",
+                "baseFeePerGas": "0x7",
+                "difficulty": "0x0",
+                "mixHash": B256::ZERO
+            }))
+        }
+        "debug_traceTransaction" => {
+            assert_eq!(params[1]["tracer"], "prestateTracer");
+            // the tracer omits the zero nonce of the sender
+            rpc_result(json!({
+                SENDER.to_string(): { "balance": U256::from(ETHER) },
+                TO.to_string(): { "balance": "0x0", "nonce": 1, "code": NUMBER_AND_BASE_FEE },
+                MINER.to_string(): { "balance": "0x0" }
+            }))
+        }
+        "eth_getRawTransactionByHash" => rpc_result(json!("0x02c0")),
+        method => panic!("unexpected {}", method),
+    }
+}
+
+fn number_and_base_fee(number: u64, base_fee: u64) -> Bytes {
+    [U256::from(number).to_be_bytes::<32>(), U256::from(base_fee).to_be_bytes::<32>()]
+        .concat()
+        .into()
+}
+
+#[test]
+fn replays_in_the_block_environment() {
+    let transport = MockTransport::new(node(transaction()));
+
+    let (result, state_diff, trace) =
+        trace_transaction_by_hash(&transport, 1, SpecId::PRAGUE, HASH).unwrap();
+
+    assert_eq!(
+        transport.calls(),
+        ["eth_getTransactionByHash", "eth_getBlockByHash", "debug_traceTransaction"]
+    );
+    assert!(result.is_success());
+    assert_eq!(result.output(), Some(&number_and_base_fee(100, 7)));
+    assert_eq!(state_diff[&SENDER].info.nonce, 1);
+    // the priority fee of 2 goes to the miner of the block
+    assert_eq!(state_diff[&MINER].info.balance, U256::from(result.gas_used() * 2));
+    assert_eq!(trace["inputs"]["target_address"], json!(TO));
+}
+
+#[test]
+fn unknown_and_pending_transactions_are_rejected() {
+    let transport = MockTransport::new(|_, _| rpc_result(json!(null)));
+    let error = trace_transaction_by_hash(&transport, 1, SpecId::PRAGUE, HASH).unwrap_err();
+    assert_eq!(error, format!("transaction {} not found", HASH));
+
+    let mut pending = transaction();
+    pending["blockHash"] = json!(null);
+    let transport = MockTransport::new(node(pending));
+    let error = trace_transaction_by_hash(&transport, 1, SpecId::PRAGUE, HASH).unwrap_err();
+    assert_eq!(error, format!("transaction {} is not mined", HASH));
+    assert_eq!(transport.calls(), ["eth_getTransactionByHash"]);
+}
+
+#[test]
+fn op_transactions_fetch_their_raw_bytes() {
+    let mut transaction = transaction();
+    transaction["chainId"] = json!("0xa");
+    let transport = MockTransport::new(node(transaction));
+    let (result, _, _) =
+        op_trace_transaction_by_hash(&transport, 10, OpSpecId::ISTHMUS, HASH).unwrap();
+    assert!(result.is_success());
+    assert_eq!(transport.calls()[1], "eth_getRawTransactionByHash");
+
+    // deposits are traced without their raw bytes and pay no fees
+    let transport = MockTransport::new(node(deposit()));
+    let (result, state_diff, _) =
+        op_trace_transaction_by_hash(&transport, 10, OpSpecId::ISTHMUS, HASH).unwrap();
+    assert!(result.is_success());
+    assert_eq!(result.output(), Some(&number_and_base_fee(100, 7)));
+    assert!(!transport.calls().contains(&String::from("eth_getRawTransactionByHash")));
+    assert_eq!(state_diff[&SENDER].info.balance, U256::from(ETHER));
+}
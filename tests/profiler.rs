@@ -0,0 +1,56 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use trace_prestate::profiler::profile_transaction;
+
+use common::{account, block_env, hex_string, prestate, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const PROXY: Address = address!("0x2000000000000000000000000000000000000002");
+const LIBRARY: Address = address!("0x3000000000000000000000000000000000000003");
+
+fn profile(data: Bytes) -> (u64, trace_prestate::profiler::GasProfiler) {
+    // DELEGATECALL to LIBRARY without calldata
+    let proxy = format!("0x600060006000600073{}5af45000", hex_string(LIBRARY.as_slice()));
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (PROXY, account(0, 1, &proxy)),
+        // ADD
+        (LIBRARY, account(0, 1, "0x600160010150")),
+    ]);
+    let (result, profiler) = profile_transaction(
+        1, FROM, 0, PROXY, data, U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    assert!(result.is_success(), "{:?}", result);
+    (result.gas_used(), profiler)
+}
+
+#[test]
+fn labels_delegate_calls_by_their_code() {
+    let (_, profiler) = profile(Bytes::from(vec![0x12, 0x34, 0x56, 0x78, 0x9a]));
+
+    let root = format!("{}:0x12345678", PROXY);
+    let library = format!("{};{}:fallback", root, LIBRARY);
+    let paths = profiler.call_paths();
+    assert_eq!(paths.iter().map(|path| path.path.as_str()).collect::<Vec<_>>(), [&root, &library]);
+    assert_eq!(paths[1].self_gas, 3 + 3 + 3 + 2);
+    assert!(profiler.folded_stacks().lines().any(|line| line == format!("{};ADD 3", library)));
+}
+
+#[test]
+fn call_path_totals_include_sub_paths() {
+    let (gas_used, profiler) = profile(Bytes::new());
+
+    let paths = profiler.call_paths();
+    assert_eq!(paths[0].total_gas, paths[0].self_gas + paths[1].total_gas);
+
+    let summary = profiler.summary(1);
+    assert_eq!(summary.intrinsic_gas, 21000);
+    assert_eq!(summary.intrinsic_gas + summary.execution_gas, gas_used);
+    assert_eq!(summary.top_call_paths.len(), 1);
+    assert_eq!(summary.top_call_paths[0].path, format!("{}:fallback", PROXY));
+    assert_eq!(summary.top_opcodes[0].opcode, "DELEGATECALL");
+    let opcodes = profiler.opcodes();
+    let push1 = opcodes.iter().find(|opcode| opcode.opcode == "PUSH1").unwrap();
+    assert_eq!((push1.count, push1.gas), (6, 18));
+}
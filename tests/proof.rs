@@ -0,0 +1,179 @@
+mod common;
+
+use revm::primitives::ruint::aliases::U64;
+use revm::primitives::{address, keccak256, Address, Bytes, HashMap, B256, KECCAK_EMPTY, U256};
+use trace_prestate::database::AccountDetails;
+use trace_prestate::lazy_database::{AccountProof, StorageProof};
+use trace_prestate::proof::{
+    create_verified_in_memory_database_from_prestate_trace, verify_account_proof,
+    verify_prestate_trace, TrieAccount
+};
+
+use common::{account, prestate, rlp, rlp_account, with_storage, Trie, EMPTY_ROOT_HASH};
+
+const CONTRACT: Address = address!("0x1000000000000000000000000000000000000001");
+const EOA: Address = address!("0x2000000000000000000000000000000000000002");
+const OTHER: Address = address!("0x3000000000000000000000000000000000000003");
+const ABSENT: Address = address!("0x4000000000000000000000000000000000000004");
+const CODE: &str = "0x6001600055";
+
+struct State {
+    state: Trie,
+    storage: Trie,
+}
+
+fn slot_key(slot: u64) -> Vec<u8> {
+    B256::from(U256::from(slot)).to_vec()
+}
+
+fn state() -> State {
+    let storage = Trie::new(&[
+        (slot_key(1), rlp(&U256::from(7))),
+        (slot_key(2), rlp(&U256::from(300))),
+    ]);
+    let code_hash = keccak256(CODE.parse::<Bytes>().unwrap());
+    let state = Trie::new(&[
+        (CONTRACT.to_vec(), rlp_account(1, U256::from(5), storage.root, code_hash)),
+        (EOA.to_vec(), rlp_account(3, U256::from(100), EMPTY_ROOT_HASH, KECCAK_EMPTY)),
+        (OTHER.to_vec(), rlp_account(0, U256::from(1), EMPTY_ROOT_HASH, KECCAK_EMPTY)),
+    ]);
+    State { state, storage }
+}
+
+fn proof_of(state: &State, address: Address, slots: &[(u64, u64)]) -> AccountProof {
+    let (balance, nonce, code_hash, storage_hash) = match address {
+        CONTRACT => (5, 1, keccak256(CODE.parse::<Bytes>().unwrap()), state.storage.root),
+        EOA => (100, 3, KECCAK_EMPTY, EMPTY_ROOT_HASH),
+        OTHER => (1, 0, KECCAK_EMPTY, EMPTY_ROOT_HASH),
+        _ => (0, 0, B256::ZERO, B256::ZERO),
+    };
+    AccountProof {
+        address,
+        balance: U256::from(balance),
+        nonce: U64::from(nonce),
+        code_hash,
+        storage_hash,
+        account_proof: state.state.proof(address.as_slice()),
+        storage_proof: slots.iter().map(|(slot, value)| StorageProof {
+            key: U256::from(*slot),
+            value: U256::from(*value),
+            proof: state.storage.proof(&slot_key(*slot)),
+        }).collect(),
+    }
+}
+
+fn prestate_and_proofs(
+    state: &State
+) -> (HashMap<Address, AccountDetails>, HashMap<Address, AccountProof>) {
+    let prestate = prestate(vec![
+        (CONTRACT, with_storage(account(5, 1, CODE), &[(1, 7), (9, 0)])),
+        (EOA, account(100, 3, "")),
+    ]);
+    let proofs = [
+        (CONTRACT, proof_of(state, CONTRACT, &[(1, 7), (9, 0)])),
+        (EOA, proof_of(state, EOA, &[])),
+    ].into_iter().collect();
+    (prestate, proofs)
+}
+
+#[test]
+fn verifies_a_valid_prestate() {
+    let state = state();
+    let (prestate, proofs) = prestate_and_proofs(&state);
+
+    assert_eq!(verify_prestate_trace(state.state.root, &prestate, &proofs), Ok(()));
+    assert!(create_verified_in_memory_database_from_prestate_trace(
+        state.state.root, prestate, &proofs
+    ).is_ok());
+}
+
+#[test]
+fn rejects_a_prestate_that_differs_from_the_proof() {
+    let state = state();
+    let (mut prestate, proofs) = prestate_and_proofs(&state);
+    prestate.get_mut(&EOA).unwrap().balance = Some(U256::from(101));
+
+    let error = verify_prestate_trace(state.state.root, &prestate, &proofs).unwrap_err();
+
+    assert!(error.contains("balance 101 but proven 100"), "{}", error);
+}
+
+#[test]
+fn rejects_a_tampered_node() {
+    let state = state();
+    let (prestate, mut proofs) = prestate_and_proofs(&state);
+    let account_proof = &mut proofs.get_mut(&EOA).unwrap().account_proof;
+    let leaf = account_proof.last_mut().unwrap();
+    let mut tampered = leaf.to_vec();
+    *tampered.last_mut().unwrap() ^= 1;
+    *leaf = tampered.into();
+
+    let error = verify_prestate_trace(state.state.root, &prestate, &proofs).unwrap_err();
+
+    assert!(error.contains("proof is missing node"), "{}", error);
+}
+
+#[test]
+fn rejects_a_wrong_state_root() {
+    let state = state();
+    let (prestate, proofs) = prestate_and_proofs(&state);
+
+    assert!(verify_prestate_trace(B256::repeat_byte(1), &prestate, &proofs).is_err());
+}
+
+#[test]
+fn proves_absent_accounts() {
+    let state = state();
+
+    let account = verify_account_proof(state.state.root, &proof_of(&state, ABSENT, &[])).unwrap();
+
+    assert_eq!(account, TrieAccount::default());
+}
+
+#[test]
+fn rejects_an_absence_claim_for_an_existing_account() {
+    let state = state();
+    let mut account_proof = proof_of(&state, EOA, &[]);
+    account_proof.balance = U256::ZERO;
+    account_proof.nonce = U64::ZERO;
+
+    assert!(verify_account_proof(state.state.root, &account_proof).is_err());
+}
+
+fn proof_with_root_node(node: &[u8]) -> (B256, AccountProof) {
+    let account_proof = AccountProof {
+        address: ABSENT,
+        balance: U256::ZERO,
+        nonce: U64::ZERO,
+        code_hash: B256::ZERO,
+        storage_hash: B256::ZERO,
+        account_proof: vec![Bytes::copy_from_slice(node)],
+        storage_proof: Vec::new(),
+    };
+    (keccak256(node), account_proof)
+}
+
+#[test]
+fn rejects_truncated_nodes() {
+    let state = state();
+    let mut root_node = state.state.proof(EOA.as_slice())[0].to_vec();
+    root_node.truncate(root_node.len() - 10);
+
+    for node in [&root_node[..], &[0xc2], &[0xc2, 0x81]] {
+        let (root, account_proof) = proof_with_root_node(node);
+        let error = verify_account_proof(root, &account_proof).unwrap_err();
+        assert!(error.contains("input too short"), "{}", error);
+    }
+}
+
+#[test]
+fn rejects_trailing_bytes_after_a_node() {
+    let state = state();
+    let mut root_node = state.state.proof(EOA.as_slice())[0].to_vec();
+    root_node.push(0x80);
+
+    let (root, account_proof) = proof_with_root_node(&root_node);
+    let error = verify_account_proof(root, &account_proof).unwrap_err();
+
+    assert!(error.contains("trailing bytes"), "{}", error);
+}
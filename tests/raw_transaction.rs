@@ -0,0 +1,232 @@
+mod common;
+
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::primitives::{address, b256, hex, keccak256, Address, Bytes, B256, U256};
+use op_revm::constants::{
+    ECOTONE_L1_BLOB_BASE_FEE_SLOT, ECOTONE_L1_FEE_SCALARS_SLOT, L1_BASE_FEE_SLOT, L1_BLOCK_CONTRACT
+};
+use trace_prestate::raw_transaction::{
+    decode_raw_transaction, op_trace_raw_transaction, trace_raw_transaction
+};
+use trace_prestate::transaction::TransactionDetails;
+
+use common::{account, block_env, prestate, rlp, rlp_list, ETHER};
+
+/// Signer of every vector, the key `0x4646..46`.
+const SENDER: Address = address!("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+const TO: Address = address!("0x3535353535353535353535353535353535353535");
+
+// nonce 7, gas 21000, 5 wei to TO; legacy ones carry 0xabcd, gas price 10
+const LEGACY: &str = "0xf861070a8252089435353535353535353535353535353535353535350582abcd1ba07ff9f1288fcd9090cb379ce167bcccf440d561e5b1d65efa1bcd1d3837bb6089a050f8784bc3cd38f73a090a2caabe95f2e3ac736e0ffb86c42e6316fbf3ca1b5d";
+const LEGACY_EIP155: &str = "0xf861070a8252089435353535353535353535353535353535353535350582abcd26a020d60d05e50a9adcc7035e1941175154570411ba2d6d0beb63205c20f95be75ca03118beb5183e81f7be523462860c72336e36783484f91906823f0c6d7bbca18f";
+// gas price 10, TO and slot 0x0202..02 in the access list
+const EIP2930: &str = "0x01f89a01070a8252089435353535353535353535353535353535353535350580f838f7943535353535353535353535353535353535353535e1a0020202020202020202020202020202020202020202020202020202020202020280a014de19f84d00ccf9c5942a10d3d31d6109799bb3c89dc849cbf5229a7c063137a05b07cc2105f8f8bbbdcb2966d070cf3521df0f5e2760cf5e1cdcebaf7f7f1499";
+// priority fee 1, max fee 10
+const EIP1559: &str = "0x02f8620107010a8252089435353535353535353535353535353535353535350580c001a04ef23d8f732bc290412bda8184cf17c86c6dd77ec56c24a3ea7f4f54810fb450a05da16090e99b792eda5906ee7efc5dc7d627af07059d6e9a7be21430ceadbc44";
+// blob fee 3 and the versioned hash 0x0101..01
+const EIP4844: &str = "0x03f8850107010a8252089435353535353535353535353535353535353535350580c003e1a0010101010101010101010101010101010101010101010101010101010101010180a04c783b6e7b96f0ec454ff2ba41aff973850aa5adcdc7a2f3acd981899f98b8faa0473ef43f9c5d3ce75b5bee0d4c69498a54251847712774de0554a50d5a79653e";
+// EIP4844 with an empty sidecar
+const EIP4844_NETWORK: &str = "0x03f88af8850107010a8252089435353535353535353535353535353535353535350580c003e1a0010101010101010101010101010101010101010101010101010101010101010180a04c783b6e7b96f0ec454ff2ba41aff973850aa5adcdc7a2f3acd981899f98b8faa0473ef43f9c5d3ce75b5bee0d4c69498a54251847712774de0554a50d5a79653ec0c0c0";
+// one authorization of TO on chain 1 at nonce 0
+const EIP7702: &str = "0x04f87e0107010a8252089435353535353535353535353535353535353535350580c0dbda019435353535353535353535353535353535353535358080010101a0bda592e1f330ed522d4d9346e50f06fa5502a4647a64bc6646a92de5bc63ea2ca00183fe1e26352c73df51e4322a8fd137b4b82ca25a51389112d4a860f8a8d135";
+
+fn decode(raw: &str) -> TransactionDetails {
+    decode_raw_transaction(&hex::decode(raw).unwrap()).unwrap()
+}
+
+fn assert_common_fields(transaction: &TransactionDetails, raw: &str) {
+    assert_eq!(transaction.hash, keccak256(hex::decode(raw).unwrap()));
+    assert_eq!(transaction.from, SENDER);
+    assert_eq!(transaction.to, Some(TO));
+    assert_eq!(transaction.nonce, U64::from(7));
+    assert_eq!(transaction.gas, U64::from(21000));
+    assert_eq!(transaction.value, U256::from(5));
+}
+
+#[test]
+fn decodes_legacy_transactions() {
+    let transaction = decode(LEGACY);
+    assert_common_fields(&transaction, LEGACY);
+    assert_eq!(transaction.tx_type, U64::ZERO);
+    assert_eq!(transaction.chain_id, None);
+    assert_eq!(transaction.gas_price, Some(U128::from(10)));
+    assert_eq!(transaction.input, Bytes::from(vec![0xab, 0xcd]));
+
+    let transaction = decode(LEGACY_EIP155);
+    assert_common_fields(&transaction, LEGACY_EIP155);
+    assert_eq!(transaction.chain_id, Some(U64::from(1)));
+}
+
+#[test]
+fn decodes_typed_transactions() {
+    let transaction = decode(EIP2930);
+    assert_common_fields(&transaction, EIP2930);
+    assert_eq!(transaction.tx_type, U64::from(1));
+    assert_eq!(transaction.chain_id, Some(U64::from(1)));
+    assert_eq!(transaction.gas_price, Some(U128::from(10)));
+    let access_list = transaction.access_list.unwrap();
+    assert_eq!(access_list.0.len(), 1);
+    assert_eq!(access_list.0[0].address, TO);
+    assert_eq!(access_list.0[0].storage_keys, [B256::repeat_byte(2)]);
+
+    let transaction = decode(EIP1559);
+    assert_common_fields(&transaction, EIP1559);
+    assert_eq!(transaction.tx_type, U64::from(2));
+    assert_eq!(transaction.max_priority_fee_per_gas, Some(U128::from(1)));
+    assert_eq!(transaction.max_fee_per_gas, Some(U128::from(10)));
+    assert_eq!(transaction.gas_price, None);
+    assert!(transaction.access_list.unwrap().0.is_empty());
+
+    let transaction = decode(EIP7702);
+    assert_common_fields(&transaction, EIP7702);
+    assert_eq!(transaction.tx_type, U64::from(4));
+    let authorizations = transaction.authorization_list.unwrap();
+    assert_eq!(authorizations.len(), 1);
+    assert_eq!(*authorizations[0].address(), TO);
+    assert_eq!(*authorizations[0].chain_id(), U256::from(1));
+}
+
+#[test]
+fn blob_transactions_decode_with_or_without_their_sidecar() {
+    let canonical = decode(EIP4844);
+    assert_common_fields(&canonical, EIP4844);
+    assert_eq!(canonical.tx_type, U64::from(3));
+    assert_eq!(canonical.max_fee_per_blob_gas, Some(U128::from(3)));
+    assert_eq!(canonical.blob_versioned_hashes, Some(vec![B256::repeat_byte(1)]));
+
+    // the hash leaves the sidecar out
+    let network = decode(EIP4844_NETWORK);
+    assert_eq!(network.hash, canonical.hash);
+    assert_eq!(network.from, SENDER);
+    assert_eq!(network.blob_versioned_hashes, canonical.blob_versioned_hashes);
+}
+
+#[test]
+fn decodes_deposit_transactions() {
+    let source_hash = b256!("0x1111111111111111111111111111111111111111111111111111111111111111");
+    let fields = [
+        rlp(&source_hash), rlp(&SENDER), rlp(&TO), rlp(&U256::from(ETHER)), rlp(&U256::from(5)),
+        rlp(&21000u64), rlp(&false), rlp(&Bytes::from(vec![0xab])),
+    ];
+    let raw = [&[0x7e][..], &rlp_list(&fields)].concat();
+
+    let transaction = decode_raw_transaction(&raw).unwrap();
+
+    assert_eq!(transaction.hash, keccak256(&raw));
+    assert_eq!(transaction.tx_type, U64::from(0x7e));
+    assert_eq!(transaction.source_hash, Some(source_hash));
+    assert_eq!(transaction.from, SENDER);
+    assert_eq!(transaction.to, Some(TO));
+    assert_eq!(transaction.mint, Some(U128::from(ETHER)));
+    assert_eq!(transaction.value, U256::from(5));
+    assert_eq!(transaction.gas, U64::from(21000));
+    assert_eq!(transaction.is_system_tx, Some(false));
+    assert_eq!(transaction.input, Bytes::from(vec![0xab]));
+
+    assert_eq!(decode_raw_transaction(&raw[..raw.len() - 1]).unwrap_err(), "input too short");
+    let error = decode_raw_transaction(&[&raw[..], &[0x80]].concat()).unwrap_err();
+    assert_eq!(error, "trailing bytes after RLP list");
+}
+
+#[test]
+fn rejects_truncated_payloads_and_trailing_bytes() {
+    for raw in [LEGACY, LEGACY_EIP155, EIP2930, EIP1559, EIP4844, EIP4844_NETWORK, EIP7702] {
+        let raw = hex::decode(raw).unwrap();
+
+        let error = decode_raw_transaction(&raw[..raw.len() - 1]).unwrap_err();
+        assert_eq!(error, "input too short");
+
+        let error = decode_raw_transaction(&[&raw[..], &[0x80]].concat()).unwrap_err();
+        assert_eq!(error, "trailing bytes after RLP list");
+    }
+    assert_eq!(decode_raw_transaction(&[]).unwrap_err(), "empty transaction");
+    assert_eq!(decode_raw_transaction(&[0x05, 0xc0]).unwrap_err(), "unsupported transaction type 5");
+}
+
+#[test]
+fn rejects_altered_signatures() {
+    // a signature over other fields recovers another sender
+    let mut raw = hex::decode(EIP1559).unwrap();
+    raw[5] = 8;
+    let transaction = decode_raw_transaction(&raw).unwrap();
+    assert_ne!(transaction.from, SENDER);
+
+    // y parity 2
+    let mut raw = hex::decode(EIP1559).unwrap();
+    let parity = raw.len() - 67;
+    raw[parity] = 2;
+    assert_eq!(decode_raw_transaction(&raw).unwrap_err(), "invalid signature parity 2");
+}
+
+#[test]
+fn traces_raw_transactions() {
+    let prestate = prestate(vec![
+        (SENDER, account(ETHER, 7, "")),
+        (TO, account(0, 0, "")),
+        (Address::ZERO, account(0, 0, "")),
+    ]);
+
+    let (result, state_diff, _) = trace_raw_transaction(
+        1, &hex::decode(EIP1559).unwrap(), block_env(), prestate
+    ).unwrap();
+
+    assert!(result.is_success());
+    assert_eq!(state_diff[&SENDER].info.nonce, 8);
+    assert_eq!(state_diff[&TO].info.balance, U256::from(5));
+}
+
+#[test]
+fn blob_and_set_code_transactions_need_a_recipient() {
+    // the checks come before the signature, which can stay empty
+    for tx_type in [3u8, 4] {
+        let mut fields = vec![
+            rlp(&1u64), rlp(&7u64), rlp(&1u64), rlp(&10u64), rlp(&21000u64), rlp(&Bytes::new()),
+            rlp(&5u64), rlp(&Bytes::new()), rlp_list(&[]),
+        ];
+        if tx_type == 3 {
+            fields.extend([rlp(&3u64), rlp_list(&[rlp(&B256::repeat_byte(1))])]);
+        } else {
+            fields.push(rlp_list(&[]));
+        }
+        fields.extend([rlp(&0u64), rlp(&0u64), rlp(&0u64)]);
+        let raw = [&[tx_type][..], &rlp_list(&fields)].concat();
+
+        let error = decode_raw_transaction(&raw).unwrap_err();
+        assert_eq!(error, format!("type {} transaction without recipient", tx_type));
+    }
+}
+
+#[test]
+fn op_l1_fees_leave_the_blob_sidecar_out() {
+    // a sidecar with an incompressible blob
+    let blob: Vec<u8> = (0..32u8).flat_map(|i| keccak256([i]).0).collect();
+    let canonical = hex::decode(EIP4844).unwrap();
+    let sidecar = [rlp_list(&[rlp(&Bytes::from(blob))]), rlp_list(&[]), rlp_list(&[])];
+    let network = [
+        &[0x03][..], &rlp_list(&[&[canonical[1..].to_vec()][..], &sidecar].concat())
+    ].concat();
+
+    let mut l1_block = account(0, 0, "");
+    l1_block.storage = Some([
+        (L1_BASE_FEE_SLOT, U256::from(1_000_000_000)),
+        // base fee scalar 1000 and blob base fee scalar 1000
+        (ECOTONE_L1_FEE_SCALARS_SLOT, U256::from(1000) << 96 | U256::from(1000) << 64),
+        (ECOTONE_L1_BLOB_BASE_FEE_SLOT, U256::from(1)),
+    ].into_iter().collect());
+    let spent = |raw: &[u8]| {
+        let prestate = prestate(vec![
+            (SENDER, account(ETHER, 7, "")),
+            (TO, account(0, 0, "")),
+            (Address::ZERO, account(0, 0, "")),
+            (L1_BLOCK_CONTRACT, l1_block.clone()),
+        ]);
+        let (result, state_diff, _) = op_trace_raw_transaction(1, raw, block_env(), prestate).unwrap();
+        assert!(result.is_success());
+        U256::from(ETHER) - state_diff[&SENDER].info.balance
+    };
+
+    // 21000 gas at a price of 1 and the 5 wei sent
+    let l1_fee = spent(&canonical) - U256::from(21005);
+    assert!(l1_fee > U256::ZERO);
+    assert_eq!(spent(&network), spent(&canonical));
+}
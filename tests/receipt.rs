@@ -0,0 +1,131 @@
+mod common;
+
+use revm::context::result::{ExecutionResult, HaltReason, Output, SuccessReason};
+use revm::context::TxEnv;
+use revm::primitives::alloy_primitives::{Bloom, BloomInput};
+use revm::primitives::ruint::aliases::{U128, U64};
+use revm::primitives::{address, Address, Bytes, Log, TxKind, B256};
+use serde_json::json;
+use trace_prestate::receipt::{compare_receipts, create_receipt, verify_receipt, Receipt};
+
+use common::{rpc_result, MockTransport};
+
+const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+const TOKEN: Address = address!("0x2000000000000000000000000000000000000002");
+
+fn tx(kind: TxKind) -> TxEnv {
+    TxEnv::builder()
+        .tx_type(Some(2))
+        .caller(SENDER)
+        .nonce(4)
+        .kind(kind)
+        .gas_limit(100_000)
+        .gas_price(30)
+        .gas_priority_fee(Some(5))
+        .build()
+        .unwrap()
+}
+
+fn success(logs: Vec<Log>) -> ExecutionResult<HaltReason> {
+    ExecutionResult::Success {
+        reason: SuccessReason::Stop,
+        gas_used: 50_000,
+        gas_refunded: 0,
+        logs,
+        output: Output::Call(Bytes::new()),
+    }
+}
+
+fn log(topic: u8, data: &[u8]) -> Log {
+    Log::new_unchecked(TOKEN, vec![B256::repeat_byte(topic)], Bytes::copy_from_slice(data))
+}
+
+fn receipt() -> Receipt {
+    let result = success(vec![log(1, &[1]), log(2, &[2])]);
+    create_receipt(B256::repeat_byte(0xaa), 3, &tx(TxKind::Call(TOKEN)), &result, 120_000, 7, 20)
+}
+
+#[test]
+fn receipts_index_logs_in_the_block() {
+    let receipt = receipt();
+
+    assert_eq!(receipt.transaction_index, U64::from(3));
+    assert_eq!(receipt.tx_type, U64::from(2));
+    assert_eq!(receipt.status, U64::from(1));
+    assert_eq!(receipt.gas_used, U64::from(50_000));
+    assert_eq!(receipt.cumulative_gas_used, U64::from(120_000));
+    assert_eq!(receipt.contract_address, None);
+    // base fee 20 plus the priority fee of 5, under the fee cap of 30
+    assert_eq!(receipt.effective_gas_price, U128::from(25));
+
+    let indexes: Vec<(U64, U64)> = receipt.logs.iter()
+        .map(|log| (log.log_index, log.transaction_index))
+        .collect();
+    assert_eq!(indexes, [(U64::from(7), U64::from(3)), (U64::from(8), U64::from(3))]);
+
+    let bloom = receipt.logs_bloom;
+    assert!(bloom.contains_input(BloomInput::Raw(TOKEN.as_slice())));
+    assert!(bloom.contains_input(BloomInput::Raw(B256::repeat_byte(2).as_slice())));
+    assert!(!bloom.contains_input(BloomInput::Raw(SENDER.as_slice())));
+}
+
+#[test]
+fn failed_creations_have_a_contract_address() {
+    let result = ExecutionResult::Revert { gas_used: 60_000, output: Bytes::new() };
+
+    let receipt = create_receipt(B256::ZERO, 0, &tx(TxKind::Create), &result, 60_000, 0, 40);
+
+    assert_eq!(receipt.status, U64::ZERO);
+    assert_eq!(receipt.contract_address, Some(SENDER.create(4)));
+    assert!(receipt.logs.is_empty());
+    assert_eq!(receipt.logs_bloom, Bloom::ZERO);
+    // capped by the fee cap
+    assert_eq!(receipt.effective_gas_price, U128::from(30));
+}
+
+#[test]
+fn comparison_reports_the_paths_that_differ() {
+    let local = receipt();
+    assert!(compare_receipts(&local, &local.clone()).is_empty());
+
+    let mut rpc = local.clone();
+    rpc.status = U64::ZERO;
+    rpc.logs[1].data = Bytes::from(vec![3]);
+    let mut paths: Vec<String> = compare_receipts(&local, &rpc).into_iter()
+        .map(|mismatch| mismatch.path)
+        .collect();
+    paths.sort();
+    assert_eq!(paths, ["logs[1].data", "status"]);
+
+    // logs are compared as a whole when their number differs
+    let mut rpc = local.clone();
+    rpc.logs.pop();
+    let mismatches = compare_receipts(&local, &rpc);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].path, "logs");
+    assert_eq!(mismatches[0].rpc, json!(rpc.logs));
+}
+
+#[test]
+fn verification_fetches_the_node_receipt() {
+    let local = receipt();
+    let mut on_chain = json!(local);
+    on_chain["gasUsed"] = json!("0xc351");
+    // fields the local receipt does not have are ignored
+    on_chain["blockNumber"] = json!("0x64");
+    let transport = MockTransport::new(|_, params| {
+        assert_eq!(params[0], json!(B256::repeat_byte(0xaa)));
+        rpc_result(on_chain.clone())
+    });
+
+    let mismatches = verify_receipt(&transport, &local).unwrap();
+
+    assert_eq!(transport.calls(), ["eth_getTransactionReceipt"]);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].path, "gasUsed");
+    assert_eq!(mismatches[0].local, json!("0xc350"));
+
+    let transport = MockTransport::new(|_, _| rpc_result(json!(null)));
+    let error = verify_receipt(&transport, &local).unwrap_err();
+    assert!(error.starts_with("no receipt"), "{}", error);
+}
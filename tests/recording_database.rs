@@ -0,0 +1,52 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use trace_prestate::database::create_in_memory_database_from_prestate_trace;
+use trace_prestate::recording_database::RecordingDatabase;
+use trace_prestate::trace::{trace_transaction, trace_transaction_with_database};
+
+use common::{account, block_env, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const UNUSED: Address = address!("0x3000000000000000000000000000000000000003");
+
+// reads slot 0 then sets it to 1
+const WRITER: &str = "0x60005450600160005500";
+
+#[test]
+fn records_the_minimal_prestate_of_a_run() {
+    let full_prestate = prestate(vec![
+        (FROM, account(ETHER, 2, "")),
+        (TO, with_storage(account(0, 1, WRITER), &[(0, 5), (7, 9)])),
+        (UNUSED, account(ETHER, 0, "")),
+    ]);
+    let mut db = RecordingDatabase::new(create_in_memory_database_from_prestate_trace(full_prestate));
+
+    let (result, state_diff, _) = trace_transaction_with_database(
+        1, FROM, 2, TO, Bytes::new(), U256::from(3), 100_000, 1, 1, block_env(), &mut db
+    ).unwrap();
+    let recorded = db.prestate();
+
+    assert!(result.is_success());
+    assert!(!recorded.contains_key(&UNUSED));
+    assert_eq!(recorded[&FROM].balance, Some(U256::from(ETHER)));
+    assert_eq!(recorded[&FROM].nonce, Some(2));
+    assert_eq!(recorded[&TO].code, Some(WRITER.parse().unwrap()));
+    // the value before execution, without the slot that was never read
+    assert_eq!(recorded[&TO].storage, Some([(U256::ZERO, U256::from(5))].into_iter().collect()));
+    // geth reports the untouched coinbase with a zero balance only
+    assert_eq!(recorded[&Address::ZERO].balance, Some(U256::ZERO));
+    assert_eq!(recorded[&Address::ZERO].nonce, None);
+
+    let (replayed, replayed_state_diff, _) = trace_transaction(
+        1, FROM, 2, TO, Bytes::new(), U256::from(3), 100_000, 1, 1, block_env(), recorded
+    ).unwrap();
+    assert_eq!(replayed, result);
+    for (address, account) in state_diff.iter() {
+        let replayed_account = &replayed_state_diff[address];
+        assert_eq!(replayed_account.info.balance, account.info.balance);
+        assert_eq!(replayed_account.info.nonce, account.info.nonce);
+        assert_eq!(replayed_account.storage, account.storage);
+    }
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::{address, b256, Address, HashMap, B256, U256};
+use revm::primitives::ruint::aliases::U64;
+use serde_json::{json, Value};
+use trace_prestate::block::BlockWithTransactions;
+use trace_prestate::database::AccountDetails;
+use trace_prestate::replay::{
+    merge_prestate_traces, replay_block_from_prestate, BEACON_ROOTS_ADDRESS,
+    WITHDRAWAL_REQUEST_ADDRESS
+};
+
+use common::{account, prestate, with_storage, ETHER};
+
+const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+const RECIPIENT: Address = address!("0x2000000000000000000000000000000000000002");
+const MINER: Address = address!("0x3000000000000000000000000000000000000003");
+const BEACON_ROOT: B256 = b256!("0x1111111111111111111111111111111111111111111111111111111111111111");
+
+// stores the first calldata word, or the block number, at slot 0
+const STORE_CALLDATA: &str = "0x60003560005500";
+const STORE_NUMBER: &str = "0x4360005500";
+
+fn transfer(nonce: u64) -> Value {
+    json!({
+        "hash": B256::with_last_byte(nonce as u8 + 1),
+        "type": "0x2",
+        "from": SENDER,
+        "to": RECIPIENT,
+        "input": "0x",
+        "value": "0x1",
+        "gas": "0x5208",
+        "nonce": U64::from(nonce),
+        "chainId": "0x1",
+        "maxFeePerGas": "0x14",
+        "maxPriorityFeePerGas": "0x2",
+        "accessList": []
+    })
+}
+
+fn block(gas_limit: u64, transactions: Vec<Value>) -> BlockWithTransactions {
+    serde_json::from_value(json!({
+        "number": "0x64",
+        "parentHash": B256::repeat_byte(0x22),
+        "miner": MINER,
+        "timestamp": "0x6553f100",
+        "stateRoot": B256::ZERO,
+        "gasLimit": U64::from(gas_limit),
+        "baseFeePerGas": "0xa",
+        "difficulty": "0x0",
+        "mixHash": B256::ZERO,
+        "excessBlobGas": "0x0",
+        "parentBeaconBlockRoot": BEACON_ROOT,
+        "transactions": transactions,
+        "withdrawals": [
+            { "index": "0x0", "validatorIndex": "0x5", "address": RECIPIENT, "amount": "0x2" },
+            { "index": "0x1", "validatorIndex": "0x6", "address": MINER, "amount": "0x0" }
+        ]
+    })).unwrap()
+}
+
+/// The `prestateTracer` results of the two transfers of a sender with
+/// nonce 0, which the tracer omits.
+fn traces() -> Vec<HashMap<Address, AccountDetails>> {
+    let mut first_sender = account(ETHER, 0, "");
+    first_sender.nonce = None;
+    let spent = 21000 * 12 + 1;
+    vec![
+        prestate(vec![
+            (SENDER, first_sender),
+            (RECIPIENT, account(0, 0, "")),
+            (MINER, account(0, 0, "")),
+        ]),
+        prestate(vec![
+            (SENDER, account(ETHER - spent, 1, "")),
+            (RECIPIENT, account(1, 0, "")),
+            (MINER, account(21000 * 2, 0, "")),
+        ]),
+    ]
+}
+
+fn parent_state() -> HashMap<Address, AccountDetails> {
+    let mut parent = merge_prestate_traces(traces());
+    parent.insert(BEACON_ROOTS_ADDRESS, account(0, 1, STORE_CALLDATA));
+    parent.insert(WITHDRAWAL_REQUEST_ADDRESS, account(0, 1, STORE_NUMBER));
+    parent
+}
+
+#[test]
+fn merging_keeps_the_first_transaction_accounts() {
+    let mut traces = traces();
+    traces[0].insert(RECIPIENT, with_storage(account(0, 0, ""), &[(1, 10)]));
+    traces[1].insert(RECIPIENT, with_storage(account(1, 0, ""), &[(1, 11), (2, 20)]));
+
+    let merged = merge_prestate_traces(traces);
+
+    // a later nonce must not fill the one the tracer omitted
+    assert_eq!(merged[&SENDER].nonce, None);
+    assert_eq!(merged[&SENDER].balance, Some(U256::from(ETHER)));
+    assert_eq!(merged[&RECIPIENT].balance, Some(U256::ZERO));
+    let storage = merged[&RECIPIENT].storage.as_ref().unwrap();
+    assert_eq!(storage[&U256::from(1)], U256::from(10));
+    assert_eq!(storage[&U256::from(2)], U256::from(20));
+}
+
+#[test]
+fn replays_a_block_like_a_node() {
+    let block = block(30_000_000, vec![transfer(0), transfer(1)]);
+
+    let replay = replay_block_from_prestate(1, SpecId::PRAGUE, &block, parent_state()).unwrap();
+
+    // the history and consolidation contracts have no code and are skipped
+    let system_calls: Vec<Address> = replay.system_calls.iter().map(|call| call.address).collect();
+    assert_eq!(system_calls, [BEACON_ROOTS_ADDRESS]);
+    let beacon_roots = &replay.state_diff[&BEACON_ROOTS_ADDRESS];
+    assert_eq!(beacon_roots.storage[&U256::ZERO].present_value, U256::from_be_bytes(BEACON_ROOT.0));
+    let post_block: Vec<Address> = replay.post_block_system_calls.iter().map(|call| call.address).collect();
+    assert_eq!(post_block, [WITHDRAWAL_REQUEST_ADDRESS]);
+    let withdrawal_requests = &replay.state_diff[&WITHDRAWAL_REQUEST_ADDRESS];
+    assert_eq!(withdrawal_requests.storage[&U256::ZERO].present_value, U256::from(100));
+
+    assert_eq!(replay.gas_used, 42000);
+    let cumulative: Vec<U64> = replay.transactions.iter()
+        .map(|transaction| transaction.receipt.cumulative_gas_used)
+        .collect();
+    assert_eq!(cumulative, [U64::from(21000), U64::from(42000)]);
+    assert_eq!(replay.transactions[1].receipt.transaction_index, U64::from(1));
+    assert_eq!(replay.burned_fees, U256::from(42000 * 10));
+    assert_eq!(replay.priority_fees, U256::from(42000 * 2));
+    assert_eq!(replay.withdrawn, U256::from(2_000_000_000u64));
+
+    assert_eq!(replay.state_diff[&SENDER].info.nonce, 2);
+    assert_eq!(replay.state_diff[&SENDER].info.balance, U256::from(ETHER - 2 * (21000 * 12 + 1)));
+    assert_eq!(replay.state_diff[&MINER].info.balance, U256::from(42000 * 2));
+    assert_eq!(replay.state_diff[&RECIPIENT].info.balance, U256::from(2_000_000_002u64));
+}
+
+#[test]
+fn system_calls_follow_the_spec() {
+    let block = block(30_000_000, Vec::new());
+
+    let replay = replay_block_from_prestate(1, SpecId::CANCUN, &block, parent_state()).unwrap();
+
+    assert_eq!(replay.system_calls.len(), 1);
+    assert!(replay.post_block_system_calls.is_empty());
+    assert!(!replay.state_diff.contains_key(&WITHDRAWAL_REQUEST_ADDRESS));
+}
+
+#[test]
+fn invalid_blocks_abort_the_replay() {
+    let error = replay_block_from_prestate(
+        1, SpecId::PRAGUE, &block(30_000, vec![transfer(0), transfer(1)]), parent_state()
+    ).unwrap_err();
+    assert!(error.starts_with("transaction 1 ") && error.contains("gas limit"), "{}", error);
+
+    let error = replay_block_from_prestate(
+        1, SpecId::PRAGUE, &block(30_000_000, vec![transfer(1)]), parent_state()
+    ).unwrap_err();
+    assert!(error.starts_with("transaction 0 "), "{}", error);
+}
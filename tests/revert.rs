@@ -0,0 +1,121 @@
+mod common;
+
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::Error;
+use revm::context::result::ExecutionResult;
+use revm::primitives::{address, Address, Bytes, U256};
+use serde_json::json;
+use trace_prestate::revert::{
+    annotate_revert_reasons, decode_execution_revert, RevertDecoder, RevertReason
+};
+use trace_prestate::trace::trace_transaction;
+
+use common::{account, block_env, prestate, revert_code, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+
+fn encode(signature: &str, values: &[DynSolValue]) -> Vec<u8> {
+    Error::parse(signature).unwrap().abi_encode_input(values).unwrap()
+}
+
+fn trace_revert(data: &[u8]) -> (ExecutionResult, serde_json::Value) {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, account(0, 1, &revert_code(data))),
+    ]);
+    let (result, _, trace) = trace_transaction(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    (result, trace)
+}
+
+#[test]
+fn decodes_error_strings_of_traced_reverts() {
+    let data = encode("Error(string)", &[DynSolValue::String("boom".into())]);
+
+    let (result, trace) = trace_revert(&data);
+
+    assert!(matches!(&result, ExecutionResult::Revert { output, .. } if output[..] == data[..]));
+    assert_eq!(
+        decode_execution_revert(&result, &RevertDecoder::new()),
+        Some(RevertReason::Error { message: "boom".into() })
+    );
+    assert_eq!(trace["revertReason"], json!({ "kind": "error", "message": "boom" }));
+}
+
+#[test]
+fn decodes_panics() {
+    let data = encode("Panic(uint256)", &[DynSolValue::Uint(U256::from(0x11), 256)]);
+
+    let reason = RevertDecoder::new().decode(&data).unwrap();
+
+    assert_eq!(reason, RevertReason::Panic {
+        code: U256::from(0x11),
+        meaning: "arithmetic overflow or underflow".into(),
+    });
+    assert_eq!(reason.to_string(), "Panic(0x11): arithmetic overflow or underflow");
+}
+
+#[test]
+fn decodes_registered_custom_errors_only() {
+    let data = encode(
+        "InsufficientBalance(uint256,address)",
+        &[DynSolValue::Uint(U256::from(5), 256), DynSolValue::Address(FROM)]
+    );
+    let mut decoder = RevertDecoder::new();
+    assert_eq!(decoder.decode(&data), None);
+
+    decoder.add_error_signature("InsufficientBalance(uint256 needed, address account)").unwrap();
+    let reason = decoder.decode(&data).unwrap();
+
+    let RevertReason::Custom { name, signature, args } = &reason else { panic!("{:?}", reason) };
+    assert_eq!(name, "InsufficientBalance");
+    assert_eq!(signature, "InsufficientBalance(uint256,address)");
+    assert_eq!(args.len(), 2);
+    assert_eq!(args[0].name, "needed");
+    assert_eq!(
+        reason.to_string(),
+        format!("InsufficientBalance(uint256,address) [5, {}]", FROM)
+    );
+}
+
+#[test]
+fn decodes_nested_entry_point_errors() {
+    let inner = encode("Error(string)", &[DynSolValue::String("AA23 reverted".into())]);
+    let data = encode("FailedOpWithRevert(uint256,string,bytes)", &[
+        DynSolValue::Uint(U256::from(1), 256),
+        DynSolValue::String("AA23".into()),
+        DynSolValue::Bytes(inner.clone()),
+    ]);
+
+    let reason = RevertDecoder::new().decode(&data).unwrap();
+
+    assert_eq!(reason, RevertReason::FailedOpWithRevert {
+        op_index: U256::from(1),
+        reason: "AA23".into(),
+        inner: inner.into(),
+        inner_reason: Some(Box::new(RevertReason::Error { message: "AA23 reverted".into() })),
+    });
+}
+
+#[test]
+fn short_or_malformed_data_is_not_decoded() {
+    let decoder = RevertDecoder::new();
+
+    assert_eq!(decoder.decode(&[0x08, 0xc3, 0x79]), None);
+    assert_eq!(decoder.decode(&[0x08, 0xc3, 0x79, 0xa0, 0x01]), None);
+}
+
+#[test]
+fn annotates_traces_with_errors_known_later() {
+    let data = encode("Unauthorized()", &[]);
+    let (_, mut trace) = trace_revert(&data);
+    assert_eq!(trace["revertReason"], json!(null));
+
+    let mut decoder = RevertDecoder::new();
+    decoder.add_error_signature("Unauthorized()").unwrap();
+    annotate_revert_reasons(&mut trace, &decoder);
+
+    assert_eq!(trace["revertReason"]["name"], "Unauthorized");
+}
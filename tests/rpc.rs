@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use revm::context::result::ExecutionResult;
+use revm::primitives::{address, Address, Bytes, B256, KECCAK_EMPTY, U256};
+use revm::Database;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use trace_prestate::lazy_database::create_lazy_database_from_prestate_trace;
+use trace_prestate::rpc::{fetch_and_trace_transaction, RpcClient, RpcClientConfig};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+
+type Handler = dyn Fn(&Value) -> (u16, Value) + Send + Sync;
+
+/// Local HTTP server answering every JSON-RPC request with `handler`,
+/// after `delay`. Returns its URL and the methods it received.
+async fn mock_server(
+    delay: Duration,
+    handler: impl Fn(&Value) -> (u16, Value) + Send + Sync + 'static
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let handler: Arc<Handler> = Arc::new(handler);
+    let received = methods.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let handler = handler.clone();
+            let received = received.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                let body = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        return;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    let Some(headers_end) = text.find("\r\n\r\n") else { continue };
+                    let content_length = text[..headers_end].lines()
+                        .find_map(|line| line.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|length| length.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= headers_end + 4 + content_length {
+                        break request[headers_end + 4..headers_end + 4 + content_length].to_vec();
+                    }
+                };
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                received.lock().unwrap().push(request["method"].as_str().unwrap().to_string());
+                tokio::time::sleep(delay).await;
+                let (status, response) = handler(&request);
+                let response = response.to_string();
+                let _ = stream.write_all(format!(
+                    "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status, response.len(), response
+                ).as_bytes()).await;
+            });
+        }
+    });
+    (url, methods)
+}
+
+fn result(request: &Value, result: Value) -> (u16, Value) {
+    (200, json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+}
+
+fn config(max_retries: u32) -> RpcClientConfig {
+    RpcClientConfig {
+        timeout: Duration::from_millis(500),
+        max_retries,
+        initial_backoff: Duration::from_millis(10),
+    }
+}
+
+fn latest_block() -> Value {
+    json!({
+        "number": "0x10",
+        "miner": "0x0000000000000000000000000000000000000000",
+        "timestamp": "0x6500",
+        "stateRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "gasLimit": "0x1c9c380",
+        "baseFeePerGas": "0x7",
+        "difficulty": "0x0",
+        "mixHash": "0x0000000000000000000000000000000000000000000000000000000000000001"
+    })
+}
+
+#[tokio::test]
+async fn fetches_inputs_and_traces() {
+    let (url, methods) = mock_server(Duration::ZERO, |request| match request["method"].as_str().unwrap() {
+        "eth_getBlockByNumber" => result(request, latest_block()),
+        "eth_getTransactionCount" => {
+            assert_eq!(request["params"][1], "0x10");
+            result(request, json!("0x3"))
+        }
+        "debug_traceCall" => {
+            assert_eq!(request["params"][1], "0x10");
+            assert_eq!(request["params"][2]["tracer"], "prestateTracer");
+            result(request, json!({
+                "0x1000000000000000000000000000000000000001": { "balance": "0xde0b6b3a7640000", "nonce": 3 },
+                "0x2000000000000000000000000000000000000002": { "balance": "0x0" }
+            }))
+        }
+        method => panic!("unexpected {}", method),
+    }).await;
+    let client = RpcClient::with_config(&url, config(0)).unwrap();
+
+    let (result, state_diff, trace) = fetch_and_trace_transaction(
+        &client, 1, FROM, TO, Bytes::new(), U256::from(5), 100_000, 10, 1
+    ).await.unwrap();
+
+    assert!(matches!(result, ExecutionResult::Success { gas_used: 21000, .. }));
+    assert_eq!(state_diff[&TO].info.balance, U256::from(5));
+    assert_eq!(state_diff[&FROM].info.nonce, 4);
+    assert!(trace.is_object());
+    let mut methods = methods.lock().unwrap().clone();
+    methods.sort();
+    assert_eq!(methods, ["debug_traceCall", "eth_getBlockByNumber", "eth_getTransactionCount"]);
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let attempts = Arc::new(Mutex::new(0));
+    let counter = attempts.clone();
+    let (url, methods) = mock_server(Duration::ZERO, move |request| {
+        let mut attempts = counter.lock().unwrap();
+        *attempts += 1;
+        match *attempts {
+            1 => (503, json!("unavailable")),
+            2 => (429, json!("rate limited")),
+            _ => result(request, json!("0x2a")),
+        }
+    }).await;
+    let client = RpcClient::with_config(&url, config(2)).unwrap();
+
+    assert_eq!(client.get_nonce(FROM, "latest").await, Ok(42));
+    assert_eq!(methods.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let (url, methods) = mock_server(Duration::ZERO, |_| (500, json!("error"))).await;
+    let client = RpcClient::with_config(&url, config(2)).unwrap();
+
+    let error = client.get_nonce(FROM, "latest").await.unwrap_err();
+
+    assert!(error.starts_with("eth_getTransactionCount"), "{}", error);
+    assert_eq!(methods.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn does_not_retry_json_rpc_errors() {
+    let (url, methods) = mock_server(Duration::ZERO, |request| (200, json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "error": { "code": -32601, "message": "the method debug_traceCall does not exist" }
+    }))).await;
+    let client = RpcClient::with_config(&url, config(2)).unwrap();
+
+    let error = client.get_prestate_trace(FROM, TO, &Bytes::new(), U256::ZERO, 21000, "latest")
+        .await
+        .unwrap_err();
+
+    assert!(error.contains("does not exist"), "{}", error);
+    assert_eq!(methods.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn times_out() {
+    let (url, _) = mock_server(Duration::from_secs(2), |request| result(request, json!("0x1"))).await;
+    let client = RpcClient::with_config(&url, config(0)).unwrap();
+
+    assert!(client.get_nonce(FROM, "latest").await.is_err());
+}
+
+#[tokio::test]
+async fn missing_block_is_an_error() {
+    let (url, _) = mock_server(Duration::ZERO, |request| result(request, Value::Null)).await;
+    let client = RpcClient::new(&url).unwrap();
+
+    assert_eq!(
+        client.get_block_by_number("0x100").await.unwrap_err(),
+        "block 0x100 not found"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serves_as_a_blocking_transport_with_retries() {
+    let attempts = Arc::new(Mutex::new(0));
+    let counter = attempts.clone();
+    let (url, methods) = mock_server(Duration::ZERO, move |request| {
+        let mut attempts = counter.lock().unwrap();
+        *attempts += 1;
+        match *attempts {
+            1 => (503, json!("unavailable")),
+            _ => result(request, json!({
+                "address": TO,
+                "balance": "0x9",
+                "nonce": "0x2",
+                "codeHash": KECCAK_EMPTY,
+                "storageHash": B256::ZERO,
+                "accountProof": [],
+                "storageProof": []
+            })),
+        }
+    }).await;
+    let client = RpcClient::with_config(&url, config(1)).unwrap();
+    let mut db = create_lazy_database_from_prestate_trace(Default::default(), client, 16);
+
+    let info = db.basic(TO).unwrap().unwrap();
+
+    assert_eq!((info.balance, info.nonce), (U256::from(9), 2));
+    assert_eq!(*methods.lock().unwrap(), ["eth_getProof", "eth_getProof"]);
+}
@@ -0,0 +1,139 @@
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::{Error, Event, Function};
+use revm::primitives::{address, Address, Log, B256, U256};
+use serde_json::json;
+use trace_prestate::revert::RevertReason;
+use trace_prestate::signatures::{
+    SignatureDatabase, COMMON_ERRORS, COMMON_EVENTS, COMMON_FUNCTIONS
+};
+
+const ALICE: Address = address!("0x1000000000000000000000000000000000000001");
+const BOB: Address = address!("0x2000000000000000000000000000000000000002");
+
+fn transfer_log(topics: usize) -> Log {
+    let event = Event::parse("Transfer(address,address,uint256)").unwrap();
+    let mut words = vec![
+        B256::left_padding_from(ALICE.as_slice()),
+        B256::left_padding_from(BOB.as_slice()),
+        B256::from(U256::from(5)),
+    ];
+    let data: Vec<u8> = words.split_off(topics - 1).concat();
+    let topics = [event.selector()].into_iter().chain(words).collect();
+    Log::new_unchecked(ALICE, topics, data.into())
+}
+
+#[test]
+fn guesses_calls_with_embedded_signatures() {
+    let input = Function::parse("transfer(address,uint256)").unwrap()
+        .abi_encode_input(&[DynSolValue::Address(BOB), DynSolValue::Uint(U256::from(7), 256)])
+        .unwrap();
+
+    let decoded = SignatureDatabase::embedded().decode_call(&input, Some(&[0; 32])).unwrap();
+
+    assert_eq!(decoded.signature, "transfer(address,uint256)");
+    assert!(decoded.guessed);
+    assert_eq!(decoded.inputs[1].value, json!("7"));
+    // signatures have no return types
+    assert_eq!(decoded.outputs, None);
+    assert!(SignatureDatabase::new().decode_call(&input, None).is_none());
+}
+
+#[test]
+fn guesses_which_event_parameters_are_indexed() {
+    let signatures = SignatureDatabase::embedded();
+
+    // ERC-20 indexes both addresses, ERC-721 the token id too
+    let erc20 = signatures.decode_log(&transfer_log(3)).unwrap();
+    let erc721 = signatures.decode_log(&transfer_log(4)).unwrap();
+
+    assert_eq!(erc20.params[2].value, json!("5"));
+    assert_eq!(erc721.params[2].value, json!("5"));
+    assert_eq!(erc20.params[1].value, json!(BOB.to_checksum(None)));
+    assert!(erc20.guessed && erc721.guessed);
+}
+
+#[test]
+fn decodes_embedded_errors() {
+    let data = Error::parse("ERC20InsufficientBalance(address,uint256,uint256)").unwrap()
+        .abi_encode_input(&[
+            DynSolValue::Address(ALICE),
+            DynSolValue::Uint(U256::from(1), 256),
+            DynSolValue::Uint(U256::from(2), 256),
+        ])
+        .unwrap();
+
+    let reason = SignatureDatabase::embedded().revert_decoder().decode(&data);
+
+    assert!(matches!(reason, Some(RevertReason::Custom { name, .. }) if name == "ERC20InsufficientBalance"));
+}
+
+#[test]
+fn imports_openchain_json_skipping_bad_entries() {
+    let mut signatures = SignatureDatabase::new();
+    let transfer_topic = Event::parse("Transfer(address,address,uint256)").unwrap().selector();
+
+    let count = signatures.import_json(&json!({
+        "result": {
+            "function": {
+                "0xa9059cbb": [{ "name": "transfer(address,uint256)" }, { "name": "garbage(" }],
+                "0x12345678": [{ "name": "transfer(address,uint256)" }]
+            },
+            "event": { transfer_topic.to_string(): [{ "name": "Transfer(address,address,uint256)" }] }
+        }
+    }).to_string()).unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(signatures.functions([0xa9, 0x05, 0x9c, 0xbb]).len(), 1);
+    assert_eq!(signatures.errors([0xa9, 0x05, 0x9c, 0xbb]).len(), 1);
+    assert_eq!(signatures.events(transfer_topic).len(), 1);
+    assert!(signatures.import_json("not json").is_err());
+}
+
+#[test]
+fn imports_text_dumps() {
+    let mut signatures = SignatureDatabase::new();
+
+    let count = signatures.import_text("\
+        # comment\n\
+        0xa9059cbb transfer(address,uint256)\n\
+        1,\"approve(address,uint256)\",0x095ea7b3\n\
+        Deposit(address,uint256)\n\
+        no signature here\n\
+        0xdeadbeef transfer(address,uint256)\n");
+
+    assert_eq!(count, 3);
+    assert_eq!(signatures.functions([0x09, 0x5e, 0xa7, 0xb3]).len(), 1);
+    // without a selector a line is both a function and an event
+    let deposit = Event::parse("Deposit(address,uint256)").unwrap().selector();
+    assert_eq!(signatures.events(deposit).len(), 1);
+    assert_eq!(signatures.len(), 6);
+}
+
+#[test]
+fn saved_files_import_back() {
+    let path = std::env::temp_dir().join(format!("signatures-{}.txt", std::process::id()));
+    let embedded = SignatureDatabase::embedded();
+
+    embedded.save_file(&path).unwrap();
+    let mut imported = SignatureDatabase::new();
+    imported.import_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let imported_line = |selector: &str, signature: &str| {
+        let selector = revm::primitives::hex::decode(selector).unwrap();
+        match selector.len() {
+            4 => imported.functions(selector[..].try_into().unwrap()).iter()
+                .any(|function| function.signature() == signature),
+            _ => imported.events(B256::from_slice(&selector)).iter()
+                .any(|event| event.signature() == signature),
+        }
+    };
+    for signature in COMMON_FUNCTIONS.iter().chain(COMMON_ERRORS) {
+        let selector = Function::parse(signature).unwrap().selector();
+        assert!(imported_line(&selector.to_string(), &Function::parse(signature).unwrap().signature()));
+    }
+    for signature in COMMON_EVENTS {
+        let event = Event::parse(signature).unwrap();
+        assert!(imported_line(&event.selector().to_string(), &event.signature()));
+    }
+}
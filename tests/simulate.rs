@@ -0,0 +1,187 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, HashMap, B256, U256};
+use revm::primitives::ruint::aliases::U64;
+use serde_json::json;
+use trace_prestate::database::AccountDetails;
+use trace_prestate::simulate::{simulate, SimulatePayload, SimulatedBlock, TRANSFER_LOG_ADDRESS};
+use trace_prestate::transfers::TRANSFER_TOPIC;
+
+use common::{account, block_env, prestate, revert_code, ETHER};
+
+const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+const COUNTER: Address = address!("0x2000000000000000000000000000000000000002");
+const RECIPIENT: Address = address!("0x3000000000000000000000000000000000000003");
+
+// returns slot 0
+const READ_SLOT: &str = "0x60005460005260206000f3";
+// adds one to slot 0 and emits an empty LOG0
+const INCREMENT: &str = "0x600160005401600055600060006000a000";
+
+fn accounts() -> HashMap<Address, AccountDetails> {
+    prestate(vec![
+        (SENDER, account(ETHER, 3, "")),
+        (COUNTER, account(0, 1, INCREMENT)),
+        (RECIPIENT, account(0, 0, "")),
+        (Address::ZERO, account(0, 0, "")),
+    ])
+}
+
+fn run(payload: serde_json::Value) -> Result<Vec<SimulatedBlock>, String> {
+    let payload: SimulatePayload = serde_json::from_value(payload).unwrap();
+    simulate(1, block_env(), accounts(), &payload)
+}
+
+fn word(value: u64) -> Bytes {
+    Bytes::from(U256::from(value).to_be_bytes::<32>())
+}
+
+#[test]
+fn blocks_run_in_sequence_on_one_state() {
+    let increment = json!({ "from": SENDER, "to": COUNTER });
+    let blocks = run(json!({
+        "blockStateCalls": [
+            { "calls": [increment, increment] },
+            {
+                "blockOverrides": { "number": "0x67", "time": "0x6553f200" },
+                "stateOverrides": { COUNTER.to_string(): { "code": READ_SLOT } },
+                "calls": [{ "from": SENDER, "to": COUNTER }]
+            }
+        ]
+    })).unwrap();
+
+    // block 102 fills the gap up to the overridden number
+    let numbers: Vec<u64> = blocks.iter().map(|block| block.number.to()).collect();
+    assert_eq!(numbers, [101, 102, 103]);
+    assert_eq!(blocks[0].timestamp, U64::from(1_700_000_012));
+    assert_eq!(blocks[1].timestamp, U64::from(1_700_000_024));
+    assert_eq!(blocks[2].timestamp, U64::from(1_700_000_256));
+    assert!(blocks[1].calls.is_empty());
+    assert_eq!(blocks[1].gas_used, U64::ZERO);
+
+    let first = &blocks[0];
+    assert_eq!(first.calls.len(), 2);
+    assert_eq!(first.gas_used, first.calls[0].gas_used + first.calls[1].gas_used);
+    assert_eq!(first.calls[1].logs[0].transaction_index, U64::from(1));
+    assert_eq!(first.calls[1].logs[0].log_index, U64::from(1));
+    assert_eq!(first.calls[1].logs[0].block_number, U64::from(101));
+
+    // the code override keeps the storage written by the first block
+    assert_eq!(blocks[2].calls[0].return_data, word(2));
+}
+
+#[test]
+fn state_overrides_replace_or_patch_the_storage() {
+    let slot = |slot: u64| B256::from(U256::from(slot));
+    let read = json!({ "from": SENDER, "to": COUNTER });
+    let blocks = run(json!({
+        "blockStateCalls": [
+            {
+                "stateOverrides": { COUNTER.to_string(): {
+                    "code": READ_SLOT,
+                    "stateDiff": { slot(0).to_string(): slot(7) }
+                }},
+                "calls": [read]
+            },
+            {
+                "stateOverrides": { COUNTER.to_string(): {
+                    "state": { slot(1).to_string(): slot(9) }
+                }},
+                "calls": [read]
+            }
+        ]
+    })).unwrap();
+
+    assert_eq!(blocks[0].calls[0].return_data, word(7));
+    assert_eq!(blocks[1].calls[0].return_data, word(0));
+
+    let error = run(json!({
+        "blockStateCalls": [{
+            "stateOverrides": { COUNTER.to_string(): { "state": {}, "stateDiff": {} } }
+        }]
+    })).unwrap_err();
+    assert!(error.starts_with("block 0:"), "{}", error);
+}
+
+#[test]
+fn traced_transfers_are_logged() {
+    let blocks = run(json!({
+        "traceTransfers": true,
+        "blockStateCalls": [{
+            "calls": [{ "from": SENDER, "to": RECIPIENT, "value": "0x64" }]
+        }]
+    })).unwrap();
+
+    let logs = &blocks[0].calls[0].logs;
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].address, TRANSFER_LOG_ADDRESS);
+    assert_eq!(logs[0].topics, [TRANSFER_TOPIC, SENDER.into_word(), RECIPIENT.into_word()]);
+    assert_eq!(logs[0].data, word(100));
+}
+
+#[test]
+fn failed_calls_are_reported_without_logs() {
+    let reverter = address!("0x4000000000000000000000000000000000000004");
+    let blocks = run(json!({
+        "traceTransfers": true,
+        "blockStateCalls": [{
+            "stateOverrides": { reverter.to_string(): { "code": revert_code(&[0xab]) } },
+            "calls": [
+                { "from": SENDER, "to": reverter, "value": "0x1" },
+                { "from": SENDER, "to": COUNTER }
+            ]
+        }]
+    })).unwrap();
+
+    let reverted = &blocks[0].calls[0];
+    assert_eq!(reverted.status, U64::ZERO);
+    assert!(reverted.logs.is_empty());
+    let error = reverted.error.as_ref().unwrap();
+    assert_eq!(error.code, 3);
+    assert_eq!(error.data, Some(Bytes::from(vec![0xab])));
+
+    // the block goes on, and log indexes skip nothing
+    let next = &blocks[0].calls[1];
+    assert_eq!(next.status, U64::from(1));
+    assert_eq!(next.logs[0].log_index, U64::ZERO);
+}
+
+#[test]
+fn validation_checks_calls_like_transactions() {
+    let poor = address!("0x5000000000000000000000000000000000000005");
+    let call = json!({ "from": poor, "to": RECIPIENT, "value": "0x1", "maxFeePerGas": "0x1" });
+
+    // like eth_call, a sender without funds can still call
+    let blocks = run(json!({ "blockStateCalls": [{ "calls": [call] }] })).unwrap();
+    assert_eq!(blocks[0].calls[0].status, U64::from(1));
+
+    let error = run(json!({
+        "validation": true,
+        "blockStateCalls": [{ "calls": [call] }]
+    })).unwrap_err();
+    assert!(error.starts_with("block 0 call 0:") && error.contains("lack of funds"), "{}", error);
+
+    let error = run(json!({
+        "validation": true,
+        "blockStateCalls": [{ "calls": [{ "from": SENDER, "to": RECIPIENT, "nonce": "0x0" }] }]
+    })).unwrap_err();
+    assert!(error.starts_with("block 0 call 0:") && error.contains("too low"), "{}", error);
+}
+
+#[test]
+fn block_numbers_and_timestamps_must_increase() {
+    let error = run(json!({
+        "blockStateCalls": [{ "blockOverrides": { "number": "0x64" } }]
+    })).unwrap_err();
+    assert!(error.contains("not above"), "{}", error);
+
+    let error = run(json!({
+        "blockStateCalls": [{ "blockOverrides": { "time": "0x1" } }]
+    })).unwrap_err();
+    assert!(error.contains("timestamp"), "{}", error);
+
+    let error = run(json!({
+        "blockStateCalls": [{ "blockOverrides": { "number": "0x1000" } }]
+    })).unwrap_err();
+    assert!(error.contains("more than"), "{}", error);
+}
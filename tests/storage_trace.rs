@@ -0,0 +1,79 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use serde_json::json;
+use trace_prestate::storage_trace::{trace_transaction_with_storage_accesses, StorageAccess, StorageOp};
+
+use common::{account, block_env, hex_string, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const CHILD: Address = address!("0x3000000000000000000000000000000000000003");
+
+fn access(
+    op: StorageOp,
+    address: Address,
+    slot: u64,
+    value: u64,
+    original_value: Option<u64>,
+    new_value: Option<u64>,
+    is_cold: Option<bool>
+) -> serde_json::Value {
+    json!(StorageAccess {
+        op,
+        address,
+        slot: U256::from(slot),
+        value: U256::from(value),
+        original_value: original_value.map(U256::from),
+        new_value: new_value.map(U256::from),
+        is_cold,
+    })
+}
+
+#[test]
+fn records_storage_accesses_per_frame() {
+    let call_child = format!("6000600060006000600073{}5af150", hex_string(CHILD.as_slice()));
+    // SLOAD 1, SSTORE 1 = 7, TSTORE 3 = 9, TLOAD 3, then calls CHILD twice
+    let code = format!("0x600154506007600155600960035d60035c50{}{}00", call_child, call_child);
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, with_storage(account(0, 1, &code), &[(1, 5)])),
+        // SLOAD 0 twice
+        (CHILD, with_storage(account(0, 1, "0x600054506000545000"), &[(0, 2)])),
+    ]);
+
+    let (result, _, trace) = trace_transaction_with_storage_accesses(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 200_000, 1, 1, block_env(), prestate
+    ).unwrap();
+
+    assert!(result.is_success(), "{:?}", result);
+    assert_eq!(trace["storageAccesses"], json!([
+        access(StorageOp::Sload, TO, 1, 5, Some(5), None, Some(true)),
+        access(StorageOp::Sstore, TO, 1, 5, Some(5), Some(7), Some(false)),
+        access(StorageOp::Tstore, TO, 3, 0, None, Some(9), None),
+        access(StorageOp::Tload, TO, 3, 9, None, None, None),
+    ]));
+    // the slot stays warm for the rest of the transaction
+    assert_eq!(trace["calls"][0]["storageAccesses"], json!([
+        access(StorageOp::Sload, CHILD, 0, 2, Some(2), None, Some(true)),
+        access(StorageOp::Sload, CHILD, 0, 2, Some(2), None, Some(false)),
+    ]));
+    assert_eq!(trace["calls"][1]["storageAccesses"], json!([
+        access(StorageOp::Sload, CHILD, 0, 2, Some(2), None, Some(false)),
+        access(StorageOp::Sload, CHILD, 0, 2, Some(2), None, Some(false)),
+    ]));
+}
+
+#[test]
+fn frames_without_accesses_are_left_as_is() {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, account(0, 1, "0x00")),
+    ]);
+
+    let (_, _, trace) = trace_transaction_with_storage_accesses(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate
+    ).unwrap();
+
+    assert!(trace.get("storageAccesses").is_none());
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use trace_prestate::database::{MissingState, StrictMode};
+use trace_prestate::trace::trace_transaction_strict;
+
+use common::{account, block_env, prestate, with_storage, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const UNKNOWN: Address = address!("0x3000000000000000000000000000000000000003");
+
+// reads slot 0, slot 1 and the balance of UNKNOWN
+const READER: &str = "0x600054506001545073300000000000000000000000000000000000000331500000";
+
+fn trace(mode: StrictMode) -> Result<Vec<MissingState>, String> {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, with_storage(account(0, 1, READER), &[(0, 1)])),
+        (Address::ZERO, account(0, 0, "")), // coinbase
+    ]);
+    trace_transaction_strict(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate, mode
+    ).map(|(result, _, _, missing)| {
+        assert!(result.is_success(), "{:?}", result);
+        missing
+    })
+}
+
+#[test]
+fn records_reads_missing_from_the_prestate() {
+    let missing = trace(StrictMode::Record).unwrap();
+
+    assert_eq!(missing, [
+        MissingState::Storage { address: TO, slot: U256::from(1) },
+        MissingState::Account { address: UNKNOWN },
+    ]);
+}
+
+#[test]
+fn complete_prestate_has_nothing_missing() {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, account(0, 1, "0x00")),
+        (Address::ZERO, account(0, 0, "")), // coinbase
+    ]);
+
+    let (result, _, _, missing) = trace_transaction_strict(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 100_000, 1, 1, block_env(), prestate,
+        StrictMode::Fail
+    ).unwrap();
+
+    assert!(result.is_success());
+    assert!(missing.is_empty());
+}
+
+#[test]
+fn fails_on_the_first_missing_read() {
+    let error = trace(StrictMode::Fail).unwrap_err();
+
+    assert!(error.contains(&MissingState::Storage { address: TO, slot: U256::from(1) }.to_string()), "{}", error);
+}
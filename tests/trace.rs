@@ -0,0 +1,51 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, U256};
+use serde_json::json;
+use trace_prestate::trace::trace_transaction;
+
+use common::{account, block_env, hex_string, prestate, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+const FIRST: Address = address!("0x3000000000000000000000000000000000000003");
+const SECOND: Address = address!("0x4000000000000000000000000000000000000004");
+
+fn call(address: Address) -> String {
+    format!("6000600060006000600073{}5af150", hex_string(address.as_slice()))
+}
+
+fn trace(code: &str) -> serde_json::Value {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (TO, account(0, 1, code)),
+        (FIRST, account(0, 1, "0x00")),
+        (SECOND, account(0, 1, "0x00")),
+        (Address::ZERO, account(0, 0, "")),
+    ]);
+    let (result, _, trace) = trace_transaction(
+        1, FROM, 0, TO, Bytes::new(), U256::ZERO, 200_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    assert!(result.is_success(), "{:?}", result);
+    trace
+}
+
+#[test]
+fn calls_are_in_call_order() {
+    let trace = trace(&format!("0x{}{}00", call(FIRST), call(SECOND)));
+
+    assert_eq!(trace["calls"][0]["inputs"]["target_address"], json!(FIRST));
+    assert_eq!(trace["calls"][1]["inputs"]["target_address"], json!(SECOND));
+}
+
+#[test]
+fn calls_of_created_contracts_are_in_call_order() {
+    // CREATE with the init code after the first 16 bytes, which calls FIRST
+    // then SECOND
+    let init_code = format!("{}{}00", call(FIRST), call(SECOND));
+    let trace = trace(&format!("0x60456010600039604560006000f05000{}", init_code));
+
+    let calls = &trace["calls"][0]["calls"];
+    assert_eq!(calls[0]["inputs"]["target_address"], json!(FIRST));
+    assert_eq!(calls[1]["inputs"]["target_address"], json!(SECOND));
+}
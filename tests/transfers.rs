@@ -0,0 +1,150 @@
+mod common;
+
+use revm::primitives::{address, Address, Bytes, Log, B256, U256};
+use serde_json::Value;
+use trace_prestate::trace::trace_transaction;
+use trace_prestate::transfers::{
+    annotate_asset_movements, decode_asset_movements, extract_asset_movements, AssetMovement,
+    TokenStandard, APPROVAL_FOR_ALL_TOPIC, TRANSFER_BATCH_TOPIC, TRANSFER_SINGLE_TOPIC,
+    TRANSFER_TOPIC
+};
+
+use common::{account, block_env, hex_string, prestate, ETHER};
+
+const FROM: Address = address!("0x1000000000000000000000000000000000000001");
+const ROUTER: Address = address!("0x2000000000000000000000000000000000000002");
+const TOKEN_A: Address = address!("0x3000000000000000000000000000000000000003");
+const TOKEN_B: Address = address!("0x4000000000000000000000000000000000000004");
+const ALICE: Address = address!("0x5000000000000000000000000000000000000005");
+
+fn topic(address: Address) -> B256 {
+    B256::left_padding_from(address.as_slice())
+}
+
+fn word(value: u64) -> Vec<u8> {
+    B256::from(U256::from(value)).to_vec()
+}
+
+fn log(topics: Vec<B256>, data: Vec<u8>) -> Log {
+    Log::new_unchecked(TOKEN_A, topics, data.into())
+}
+
+/// Emits an ERC-20 `Transfer(caller, ALICE, amount)`, then runs `end`.
+fn token_code(amount: u8, end: &str) -> String {
+    format!(
+        "0x60{:02x}60005273{}337f{}60206000a3{}",
+        amount, hex_string(ALICE.as_slice()), hex_string(TRANSFER_TOPIC.as_slice()), end
+    )
+}
+
+/// Calls TOKEN_A then TOKEN_B.
+fn router_code() -> String {
+    let call = |to: Address| format!("60006000600060006000 73{} 5af150", hex_string(to.as_slice()))
+        .replace(' ', "");
+    format!("0x{}{}00", call(TOKEN_A), call(TOKEN_B))
+}
+
+fn trace(token_b_end: &str) -> Value {
+    let prestate = prestate(vec![
+        (FROM, account(ETHER, 0, "")),
+        (ROUTER, account(0, 1, &router_code())),
+        (TOKEN_A, account(0, 1, &token_code(1, "00"))),
+        (TOKEN_B, account(0, 1, &token_code(2, token_b_end))),
+    ]);
+    let (result, _, trace) = trace_transaction(
+        1, FROM, 0, ROUTER, Bytes::new(), U256::ZERO, 200_000, 1, 1, block_env(), prestate
+    ).unwrap();
+    assert!(result.is_success(), "{:?}", result);
+    trace
+}
+
+fn erc20_transfer(token: Address, amount: u64) -> AssetMovement {
+    AssetMovement::Transfer {
+        token,
+        standard: TokenStandard::Erc20,
+        operator: None,
+        from: ROUTER,
+        to: ALICE,
+        id: None,
+        amount: U256::from(amount),
+    }
+}
+
+#[test]
+fn keeps_the_order_of_the_frames() {
+    let movements = extract_asset_movements(&trace("00"));
+
+    assert_eq!(movements.len(), 2);
+    assert_eq!((movements[0].frame.clone(), &movements[0].movement), (vec![0], &erc20_transfer(TOKEN_A, 1)));
+    assert_eq!((movements[1].frame.clone(), &movements[1].movement), (vec![1], &erc20_transfer(TOKEN_B, 2)));
+}
+
+#[test]
+fn skips_reverted_frames() {
+    // REVERT(0, 0)
+    let movements = extract_asset_movements(&trace("60006000fd"));
+
+    assert_eq!(movements.len(), 1);
+    assert_eq!(movements[0].movement, erc20_transfer(TOKEN_A, 1));
+}
+
+#[test]
+fn skips_halted_frames() {
+    // INVALID
+    let mut trace = trace("fe");
+
+    let movements = extract_asset_movements(&trace);
+    annotate_asset_movements(&mut trace);
+
+    assert_eq!(movements.len(), 1);
+    assert_eq!(movements[0].movement, erc20_transfer(TOKEN_A, 1));
+    assert!(trace["calls"][0].get("assetMovements").is_some());
+    assert!(trace["calls"][1].get("assetMovements").is_none());
+}
+
+#[test]
+fn decodes_token_events() {
+    let erc721 = log(vec![TRANSFER_TOPIC, topic(ROUTER), topic(ALICE), B256::from(U256::from(9))], vec![]);
+    assert_eq!(decode_asset_movements(&erc721), [AssetMovement::Transfer {
+        token: TOKEN_A,
+        standard: TokenStandard::Erc721,
+        operator: None,
+        from: ROUTER,
+        to: ALICE,
+        id: Some(U256::from(9)),
+        amount: U256::from(1),
+    }]);
+
+    let approval_for_all = log(vec![APPROVAL_FOR_ALL_TOPIC, topic(ROUTER), topic(ALICE)], word(1));
+    assert_eq!(decode_asset_movements(&approval_for_all), [AssetMovement::ApprovalForAll {
+        token: TOKEN_A, owner: ROUTER, operator: ALICE, approved: true
+    }]);
+
+    let single = log(
+        vec![TRANSFER_SINGLE_TOPIC, topic(FROM), topic(ROUTER), topic(ALICE)],
+        [word(7), word(3)].concat()
+    );
+    assert!(matches!(
+        &decode_asset_movements(&single)[..],
+        [AssetMovement::Transfer { standard: TokenStandard::Erc1155, operator: Some(FROM), id: Some(id), amount, .. }]
+            if *id == U256::from(7) && *amount == U256::from(3)
+    ));
+
+    // ids [1, 2], amounts [10, 20]
+    let batch_data = [word(0x40), word(0xa0), word(2), word(1), word(2), word(2), word(10), word(20)].concat();
+    let batch = log(vec![TRANSFER_BATCH_TOPIC, topic(FROM), topic(ROUTER), topic(ALICE)], batch_data);
+    let amounts: Vec<U256> = decode_asset_movements(&batch).into_iter().map(|movement| match movement {
+        AssetMovement::Transfer { amount, .. } => amount,
+        movement => panic!("{:?}", movement),
+    }).collect();
+    assert_eq!(amounts, [U256::from(10), U256::from(20)]);
+}
+
+#[test]
+fn ignores_lookalike_events() {
+    // a Transfer with every parameter indexed is neither ERC-20 nor ERC-721
+    let lookalike = log(vec![TRANSFER_TOPIC, topic(ROUTER), topic(ALICE)], vec![]);
+
+    assert!(decode_asset_movements(&lookalike).is_empty());
+    assert!(decode_asset_movements(&log(vec![], vec![])).is_empty());
+}
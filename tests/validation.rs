@@ -0,0 +1,167 @@
+mod common;
+
+use revm::context::tx::TxEnvBuilder;
+use revm::context::{BlockEnv, TxEnv};
+use revm::primitives::eip3860::MAX_INITCODE_SIZE;
+use revm::primitives::eip7825::TX_GAS_LIMIT_CAP;
+use revm::primitives::hardfork::SpecId;
+use revm::primitives::{address, keccak256, Address, Bytes, TxKind, U256};
+use trace_prestate::database::create_in_memory_database_from_prestate_trace;
+use trace_prestate::validation::{validate_transaction, ValidationFailure};
+
+use common::{account, block_env, prestate, ETHER};
+
+const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+const TO: Address = address!("0x2000000000000000000000000000000000000002");
+
+const CONTRACT_CODE: &str = "0x6000";
+// EIP-7702 delegation to TO
+const DELEGATION: &str = "0xef01002000000000000000000000000000000000000002";
+
+/// A transfer of 1 wei from SENDER at nonce 3, paying 10 with a 1 tip.
+fn tx() -> TxEnvBuilder {
+    TxEnv::builder()
+        .tx_type(Some(2))
+        .chain_id(Some(1))
+        .caller(SENDER)
+        .nonce(3)
+        .kind(TxKind::Call(TO))
+        .value(U256::from(1))
+        .gas_limit(21000)
+        .gas_price(10)
+        .gas_priority_fee(Some(1))
+}
+
+fn block() -> BlockEnv {
+    BlockEnv { basefee: 5, ..block_env() }
+}
+
+fn validate_with(
+    spec_id: SpecId,
+    sender_balance: u128,
+    sender_code: &str,
+    tx: TxEnvBuilder
+) -> Vec<ValidationFailure> {
+    let db = create_in_memory_database_from_prestate_trace(prestate(vec![
+        (SENDER, account(sender_balance, 3, sender_code)),
+        (TO, account(0, 0, "")),
+    ]));
+    validate_transaction(1, spec_id, &block(), &tx.build().unwrap(), &db).unwrap()
+}
+
+fn validate(tx: TxEnvBuilder) -> Vec<ValidationFailure> {
+    validate_with(SpecId::PRAGUE, ETHER, "", tx)
+}
+
+#[test]
+fn valid_transactions_have_no_failures() {
+    assert_eq!(validate(tx()), []);
+    // exactly enough to pay
+    assert_eq!(validate_with(SpecId::PRAGUE, 21000 * 10 + 1, "", tx()), []);
+}
+
+#[test]
+fn checks_the_chain_id_and_nonce() {
+    assert_eq!(validate(tx().chain_id(Some(5))), [
+        ValidationFailure::ChainIdMismatch { chain_id: 1, tx_chain_id: 5 },
+    ]);
+    // legacy transactions without EIP-155 are valid on any chain
+    assert_eq!(validate(tx().tx_type(Some(0)).chain_id(None).gas_priority_fee(None)), []);
+
+    assert_eq!(validate(tx().nonce(2)), [
+        ValidationFailure::NonceTooLow { account_nonce: 3, tx_nonce: 2 },
+    ]);
+    assert_eq!(validate(tx().nonce(4)), [
+        ValidationFailure::NonceTooHigh { account_nonce: 3, tx_nonce: 4 },
+    ]);
+}
+
+#[test]
+fn senders_with_code_are_rejected_unless_delegated() {
+    let code = CONTRACT_CODE.parse::<Bytes>().unwrap();
+    assert_eq!(validate_with(SpecId::PRAGUE, ETHER, CONTRACT_CODE, tx()), [
+        ValidationFailure::SenderHasCode { sender: SENDER, code_hash: keccak256(&code) },
+    ]);
+
+    assert_eq!(validate_with(SpecId::PRAGUE, ETHER, DELEGATION, tx()), []);
+}
+
+#[test]
+fn checks_the_balance_and_fees() {
+    assert_eq!(validate_with(SpecId::PRAGUE, 21000 * 10, "", tx()), [
+        ValidationFailure::InsufficientBalance {
+            balance: U256::from(21000 * 10),
+            required: U256::from(21000 * 10 + 1),
+        },
+    ]);
+
+    assert_eq!(validate(tx().gas_price(4)), [
+        ValidationFailure::MaxFeeBelowBaseFee { max_fee_per_gas: 4, base_fee: 5 },
+    ]);
+    // no base fee before London
+    let legacy = tx().tx_type(Some(0)).gas_price(4).gas_priority_fee(None);
+    assert_eq!(validate_with(SpecId::BERLIN, ETHER, "", legacy), []);
+
+    assert_eq!(validate(tx().gas_priority_fee(Some(11))), [
+        ValidationFailure::PriorityFeeAboveMaxFee { max_priority_fee_per_gas: 11, max_fee_per_gas: 10 },
+    ]);
+}
+
+#[test]
+fn checks_the_gas_limit() {
+    assert_eq!(validate(tx().gas_limit(20999)), [
+        ValidationFailure::IntrinsicGasTooLow { gas_limit: 20999, intrinsic_gas: 21000 },
+    ]);
+
+    // 100 non-zero bytes cost 1600 gas, but the Prague floor is 4000
+    let calldata = || tx().data(Bytes::from(vec![1; 100])).gas_limit(23000);
+    assert_eq!(validate(calldata()), [
+        ValidationFailure::IntrinsicGasTooLow { gas_limit: 23000, intrinsic_gas: 25000 },
+    ]);
+    assert_eq!(validate_with(SpecId::CANCUN, ETHER, "", calldata()), []);
+
+    assert_eq!(validate(tx().gas_limit(30_000_001)), [
+        ValidationFailure::GasLimitAboveBlockGasLimit { gas_limit: 30_000_001, block_gas_limit: 30_000_000 },
+    ]);
+    assert_eq!(validate_with(SpecId::OSAKA, ETHER, "", tx().gas_limit(TX_GAS_LIMIT_CAP + 1)), [
+        ValidationFailure::GasLimitAboveCap { gas_limit: TX_GAS_LIMIT_CAP + 1, cap: TX_GAS_LIMIT_CAP },
+    ]);
+}
+
+#[test]
+fn checks_the_initcode_size() {
+    let create = |size: usize| tx()
+        .kind(TxKind::Create)
+        .data(Bytes::from(vec![0; size]))
+        .gas_limit(10_000_000);
+
+    assert_eq!(validate(create(MAX_INITCODE_SIZE)), []);
+    assert_eq!(validate(create(MAX_INITCODE_SIZE + 1)), [
+        ValidationFailure::InitcodeTooLarge { size: MAX_INITCODE_SIZE + 1, max_size: MAX_INITCODE_SIZE },
+    ]);
+    // no limit before Shanghai
+    assert_eq!(validate_with(SpecId::MERGE, ETHER, "", create(MAX_INITCODE_SIZE + 1)), []);
+}
+
+#[test]
+fn reports_every_failure() {
+    let tx = tx()
+        .chain_id(Some(5))
+        .nonce(2)
+        .gas_price(4)
+        .gas_priority_fee(Some(6))
+        .gas_limit(20000);
+
+    let failures = validate_with(SpecId::PRAGUE, 0, CONTRACT_CODE, tx);
+
+    let code_hash = keccak256(CONTRACT_CODE.parse::<Bytes>().unwrap());
+    assert_eq!(failures, [
+        ValidationFailure::ChainIdMismatch { chain_id: 1, tx_chain_id: 5 },
+        ValidationFailure::NonceTooLow { account_nonce: 3, tx_nonce: 2 },
+        ValidationFailure::SenderHasCode { sender: SENDER, code_hash },
+        ValidationFailure::InsufficientBalance { balance: U256::ZERO, required: U256::from(20000 * 4 + 1) },
+        ValidationFailure::MaxFeeBelowBaseFee { max_fee_per_gas: 4, base_fee: 5 },
+        ValidationFailure::PriorityFeeAboveMaxFee { max_priority_fee_per_gas: 6, max_fee_per_gas: 4 },
+        ValidationFailure::IntrinsicGasTooLow { gas_limit: 20000, intrinsic_gas: 21000 },
+    ]);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use revm::primitives::{address, keccak256, Address, Bytes, B256, KECCAK_EMPTY, U256};
+use revm::Database;
+use trace_prestate::witness::{create_in_memory_database_from_execution_witness, ExecutionWitness};
+
+use common::{rlp, rlp_account, rlp_list, Trie, EMPTY_ROOT_HASH};
+
+const CONTRACT: Address = address!("0x1000000000000000000000000000000000000001");
+const EOA: Address = address!("0x2000000000000000000000000000000000000002");
+const ABSENT: Address = address!("0x3000000000000000000000000000000000000003");
+const CODE: &str = "0x6001545000";
+
+/// Header with only the fields the witness reads filled in.
+fn header(parent_hash: B256, state_root: B256, number: u64) -> Bytes {
+    let mut fields = vec![rlp(&parent_hash), rlp(&B256::ZERO), rlp(&Address::ZERO), rlp(&state_root)];
+    fields.extend([rlp(&B256::ZERO), rlp(&B256::ZERO), rlp(&Bytes::new()), rlp(&0u64)]);
+    fields.push(rlp(&number));
+    rlp_list(&fields).into()
+}
+
+fn slot_key(slot: u64) -> Vec<u8> {
+    B256::from(U256::from(slot)).to_vec()
+}
+
+struct Setup {
+    state: Trie,
+    storage: Trie,
+    witness: ExecutionWitness,
+}
+
+fn setup() -> Setup {
+    let code: Bytes = CODE.parse().unwrap();
+    let storage = Trie::new(&[
+        (slot_key(1), rlp(&U256::from(7))),
+        (slot_key(2), rlp(&U256::from(8))),
+    ]);
+    let state = Trie::new(&[
+        (CONTRACT.to_vec(), rlp_account(1, U256::from(5), storage.root, keccak256(&code))),
+        (EOA.to_vec(), rlp_account(3, U256::from(100), EMPTY_ROOT_HASH, KECCAK_EMPTY)),
+    ]);
+    let grandparent = header(B256::repeat_byte(9), B256::repeat_byte(1), 98);
+    let parent = header(keccak256(&grandparent), state.root, 99);
+    let witness = ExecutionWitness {
+        state: state.nodes.iter().chain(storage.nodes.iter()).cloned().collect(),
+        codes: vec![code],
+        keys: vec![
+            CONTRACT.to_vec().into(),
+            EOA.to_vec().into(),
+            ABSENT.to_vec().into(),
+            slot_key(1).into(),
+            slot_key(3).into(),
+        ],
+        headers: vec![parent, grandparent],
+    };
+    Setup { state, storage, witness }
+}
+
+#[test]
+fn loads_accounts_storage_and_block_hashes() {
+    let Setup { state, witness, .. } = setup();
+
+    let mut db = create_in_memory_database_from_execution_witness(state.root, &witness).unwrap();
+
+    let contract = db.basic(CONTRACT).unwrap().unwrap();
+    assert_eq!((contract.balance, contract.nonce), (U256::from(5), 1));
+    assert_eq!(contract.code.unwrap().original_bytes(), CODE.parse::<Bytes>().unwrap());
+    assert_eq!(db.storage(CONTRACT, U256::from(1)).unwrap(), U256::from(7));
+    assert_eq!(db.storage(CONTRACT, U256::from(3)).unwrap(), U256::ZERO);
+    let eoa = db.basic(EOA).unwrap().unwrap();
+    assert_eq!((eoa.balance, eoa.nonce), (U256::from(100), 3));
+    assert!(db.basic(ABSENT).unwrap().is_none());
+    assert_eq!(db.block_hash(98).unwrap(), keccak256(&witness.headers[1]));
+    assert_eq!(db.block_hash(99).unwrap(), keccak256(&witness.headers[0]));
+}
+
+#[test]
+fn missing_account_node_is_an_error() {
+    let Setup { state, mut witness, .. } = setup();
+    let leaf = state.proof(EOA.as_slice()).pop().unwrap();
+    witness.state.retain(|node| *node != leaf);
+
+    let error = create_in_memory_database_from_execution_witness(state.root, &witness).unwrap_err();
+
+    assert!(error.contains(&format!("account {}: witness is missing trie node", EOA)), "{}", error);
+}
+
+#[test]
+fn missing_storage_node_is_an_error() {
+    let Setup { state, storage, mut witness } = setup();
+    let leaf = storage.proof(&slot_key(1)).pop().unwrap();
+    witness.state.retain(|node| *node != leaf);
+
+    let error = create_in_memory_database_from_execution_witness(state.root, &witness).unwrap_err();
+
+    assert!(
+        error.contains(&format!("account {} storage slot 1: witness is missing trie node", CONTRACT)),
+        "{}", error
+    );
+}
+
+#[test]
+fn other_accounts_do_not_hide_missing_storage_nodes() {
+    let Setup { storage, .. } = setup();
+    let code: Bytes = CODE.parse().unwrap();
+    let other = address!("0x4000000000000000000000000000000000000004");
+    let empty_contract = address!("0x5000000000000000000000000000000000000005");
+    // another contract holds slot 1 in full, a third one has no storage
+    let other_storage = Trie::new(&[(slot_key(1), rlp(&U256::from(9)))]);
+    let state = Trie::new(&[
+        (CONTRACT.to_vec(), rlp_account(1, U256::ZERO, storage.root, keccak256(&code))),
+        (other.to_vec(), rlp_account(1, U256::ZERO, other_storage.root, keccak256(&code))),
+        (empty_contract.to_vec(), rlp_account(1, U256::ZERO, EMPTY_ROOT_HASH, keccak256(&code))),
+    ]);
+    let leaf = storage.proof(&slot_key(1)).pop().unwrap();
+    let witness = ExecutionWitness {
+        state: state.nodes.iter()
+            .chain(storage.nodes.iter().filter(|node| **node != leaf))
+            .chain(other_storage.nodes.iter())
+            .cloned()
+            .collect(),
+        codes: vec![code],
+        keys: vec![
+            CONTRACT.to_vec().into(),
+            other.to_vec().into(),
+            empty_contract.to_vec().into(),
+            slot_key(1).into(),
+        ],
+        headers: vec![header(B256::ZERO, state.root, 99)],
+    };
+
+    let error = create_in_memory_database_from_execution_witness(state.root, &witness).unwrap_err();
+
+    assert!(
+        error.contains(&format!("account {} storage slot 1: witness is missing trie node", CONTRACT)),
+        "{}", error
+    );
+}
+
+#[test]
+fn headers_must_chain() {
+    let Setup { state, mut witness, .. } = setup();
+    witness.headers[1] = header(B256::repeat_byte(9), B256::repeat_byte(2), 98);
+
+    let error = create_in_memory_database_from_execution_witness(state.root, &witness).unwrap_err();
+
+    assert!(error.contains("witness header 98 is not the parent of header 99"), "{}", error);
+}
+
+#[test]
+fn latest_header_must_have_the_parent_state_root() {
+    let Setup { state, witness, .. } = setup();
+
+    let error = create_in_memory_database_from_execution_witness(
+        B256::repeat_byte(1), &witness
+    ).unwrap_err();
+
+    assert!(error.contains(&format!("has state root {}", state.root)), "{}", error);
+}